@@ -19,6 +19,20 @@ struct NewMessage {
     content: String,
     close_one_point: Option<String>,
     connected: String,
+    reply_to: Option<i32>,
+    #[serde(default)]
+    attachments: Vec<AttachmentRef>,
+    #[serde(default)]
+    send_at: Option<String>, // RFC 3339 time to deliver the message at, now when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    format: Option<String>, // "plain" (the default) or "markdown"
+}
+
+// A file returned by upload_attachment, passed along with the message it belongs to
+#[derive(Deserialize, Serialize)]
+struct AttachmentRef {
+    hash: String,
+    file_name: String,
 }
 
 // Define error types for more specific error handling
@@ -147,24 +161,46 @@ async fn send_message_other_client(message: NewMessage) -> Result<(), String> {
 
 // Command to get messages from 'my-client' by connectedPerson
 #[tauri::command]
-async fn get_messages_my_client(connected: String) -> Result<String, String> {
+async fn get_messages_my_client(
+    connected: String,
+    label: Option<String>,
+    viewer: Option<String>,
+) -> Result<String, String> {
     validate_connected_person(&connected)?;
 
     let client = Client::new();
     let url = format!("http://127.0.0.1:4875/message/my/get/{}", connected);
-    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+    let mut request = client.get(&url);
+    if let Some(label) = label {
+        request = request.query(&[("label", label)]);
+    }
+    if let Some(viewer) = viewer {
+        request = request.query(&[("viewer", viewer)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
 
     handle_response(response).await.map_err(|e| e.to_string())
 }
 
 // Command to get messages from 'other-client' by connectedPerson
 #[tauri::command]
-async fn get_messages_other_client(connected: String) -> Result<String, String> {
+async fn get_messages_other_client(
+    connected: String,
+    label: Option<String>,
+    viewer: Option<String>,
+) -> Result<String, String> {
     validate_connected_person(&connected)?;
 
     let client = Client::new();
     let url = format!("http://127.0.0.1:4875/message/other/get/{}", connected);
-    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+    let mut request = client.get(&url);
+    if let Some(label) = label {
+        request = request.query(&[("label", label)]);
+    }
+    if let Some(viewer) = viewer {
+        request = request.query(&[("viewer", viewer)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
 
     handle_response(response).await.map_err(|e| e.to_string())
 }
@@ -186,13 +222,43 @@ struct Person {
     location: Option<String>,
     occupation: Option<String>,
     extra_info: Option<String>,
+    #[serde(default)]
+    avatar_hash: Option<String>,
+    #[serde(default)]
+    avatar_url: Option<String>,
+}
+
+// Search, filter, sort and pagination options for the contact listings
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct ContactQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    q: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_age: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_age: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<u32>,
 }
 
 #[tauri::command]
-async fn get_contacts_my_client() -> Result<Vec<Person>, String> {
+async fn get_contacts_my_client(query: Option<ContactQuery>) -> Result<Vec<Person>, String> {
     let client = Client::new();
     let url = "http://127.0.0.1:4875/message/my/people";
-    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .query(&query.unwrap_or_default())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
 
     if response.status().is_success() {
         let people: Vec<Person> = response.json().await.map_err(|e| e.to_string())?;
@@ -206,10 +272,15 @@ async fn get_contacts_my_client() -> Result<Vec<Person>, String> {
 }
 
 #[tauri::command]
-async fn get_contacts_other_client() -> Result<Vec<Person>, String> {
+async fn get_contacts_other_client(query: Option<ContactQuery>) -> Result<Vec<Person>, String> {
     let client = Client::new();
     let url = "http://127.0.0.1:4875/message/other/people";
-    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .query(&query.unwrap_or_default())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
 
     if response.status().is_success() {
         let people: Vec<Person> = response.json().await.map_err(|e| e.to_string())?;
@@ -282,6 +353,1963 @@ async fn add_contact_other_client(
     }
 }
 
+#[derive(Deserialize, Serialize)]
+struct GroupMessage {
+    sender: String,
+    content: String,
+    close_one_point: Option<String>,
+}
+
+// Command to list contact groups with their member counts
+#[tauri::command]
+async fn get_contact_groups() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/message/groups")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to create a contact group
+#[tauri::command]
+async fn create_contact_group(name: String) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/groups/")
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to rename a contact group
+#[tauri::command]
+async fn rename_contact_group(group_id: i32, name: String) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/groups/{}", group_id);
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to delete a contact group
+#[tauri::command]
+async fn delete_contact_group(group_id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/groups/{}", group_id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the contact ids in a contact group
+#[tauri::command]
+async fn get_contact_group_members(group_id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/groups/{}/members", group_id);
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to assign contacts to a contact group
+#[tauri::command]
+async fn add_contacts_to_group(group_id: i32, contact_ids: Vec<String>) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/groups/{}/members/", group_id);
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "contact_ids": contact_ids }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to remove a contact from a contact group
+#[tauri::command]
+async fn remove_contact_from_group(group_id: i32, contact_id: String) -> Result<(), String> {
+    validate_connected_person(&contact_id)?;
+
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/groups/{}/members/{}",
+        group_id, contact_id
+    );
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to send a message to every member of a contact group through 'my-client'
+#[tauri::command]
+async fn send_group_message_my_client(
+    group_id: i32,
+    message: GroupMessage,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/groups/{}/send/", group_id);
+    let response = client
+        .post(&url)
+        .json(&message)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to send a message to every member of a contact group through 'other-client'
+#[tauri::command]
+async fn send_group_message_other_client(
+    group_id: i32,
+    message: GroupMessage,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/groups/{}/send/",
+        group_id
+    );
+    let response = client
+        .post(&url)
+        .json(&message)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list likely duplicate contacts in 'my-client'
+#[tauri::command]
+async fn find_duplicate_contacts_my_client(threshold: Option<f64>) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/message/my/people/duplicates");
+    if let Some(threshold) = threshold {
+        request = request.query(&[("threshold", threshold)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to merge two contacts in 'my-client' into the one that is kept
+#[tauri::command]
+async fn merge_contacts_my_client(keep_id: String, merge_id: String) -> Result<String, String> {
+    validate_connected_person(&keep_id)?;
+    validate_connected_person(&merge_id)?;
+
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/my/people/merge/")
+        .json(&serde_json::json!({ "keep_id": keep_id, "merge_id": merge_id }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list likely duplicate contacts in 'other-client'
+#[tauri::command]
+async fn find_duplicate_contacts_other_client(threshold: Option<f64>) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/message/other/people/duplicates");
+    if let Some(threshold) = threshold {
+        request = request.query(&[("threshold", threshold)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to merge two contacts in 'other-client' into the one that is kept
+#[tauri::command]
+async fn merge_contacts_other_client(keep_id: String, merge_id: String) -> Result<String, String> {
+    validate_connected_person(&keep_id)?;
+    validate_connected_person(&merge_id)?;
+
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/other/people/merge/")
+        .json(&serde_json::json!({ "keep_id": keep_id, "merge_id": merge_id }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the contact merge undo log
+#[tauri::command]
+async fn get_contact_merges() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/message/people/merges")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to undo a contact merge
+#[tauri::command]
+async fn undo_contact_merge(merge_id: i64) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/people/merges/{}/undo",
+        merge_id
+    );
+    let response = client.post(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to upload the avatar image of a 'my-client' contact
+#[tauri::command]
+async fn upload_avatar_my_client(id: String, image: Vec<u8>) -> Result<String, String> {
+    validate_connected_person(&id)?;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/people/{}/avatar", id);
+    let response = client
+        .put(&url)
+        .body(image)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to remove the avatar of a 'my-client' contact
+#[tauri::command]
+async fn delete_avatar_my_client(id: String) -> Result<(), String> {
+    validate_connected_person(&id)?;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/people/{}/avatar", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to upload the avatar image of a 'other-client' contact
+#[tauri::command]
+async fn upload_avatar_other_client(id: String, image: Vec<u8>) -> Result<String, String> {
+    validate_connected_person(&id)?;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/people/{}/avatar", id);
+    let response = client
+        .put(&url)
+        .body(image)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to remove the avatar of a 'other-client' contact
+#[tauri::command]
+async fn delete_avatar_other_client(id: String) -> Result<(), String> {
+    validate_connected_person(&id)?;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/people/{}/avatar", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to get the activity timeline of a contact, optionally limited to some event types
+#[tauri::command]
+async fn get_contact_timeline(
+    id: String,
+    types: Option<Vec<String>>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<String, String> {
+    validate_connected_person(&id)?;
+
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/people/{}/timeline", id);
+    let mut request = client.get(&url);
+    if let Some(types) = types {
+        request = request.query(&[("types", types.join(","))]);
+    }
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+    if let Some(offset) = offset {
+        request = request.query(&[("offset", offset)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to edit a message in 'my-client', previous versions are kept as revisions
+#[tauri::command]
+async fn edit_message_my_client(
+    id: i32,
+    sender: String,
+    content: String,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}", id);
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "sender": sender, "content": content }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the revisions of a message in 'my-client'
+#[tauri::command]
+async fn get_message_revisions_my_client(id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/revisions", id);
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to edit a message in 'other-client', previous versions are kept as revisions
+#[tauri::command]
+async fn edit_message_other_client(
+    id: i32,
+    sender: String,
+    content: String,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/messages/{}", id);
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "sender": sender, "content": content }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the revisions of a message in 'other-client'
+#[tauri::command]
+async fn get_message_revisions_other_client(id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/messages/{}/revisions",
+        id
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to delete a message in 'my-client', mode is "for_me" or "unsend"
+#[tauri::command]
+async fn delete_message_my_client(id: i32, mode: String, requester: String) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}", id);
+    let response = client
+        .delete(&url)
+        .query(&[("mode", mode), ("requester", requester)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to delete a message in 'other-client', mode is "for_me" or "unsend"
+#[tauri::command]
+async fn delete_message_other_client(
+    id: i32,
+    mode: String,
+    requester: String,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/messages/{}", id);
+    let response = client
+        .delete(&url)
+        .query(&[("mode", mode), ("requester", requester)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Helper function to post a delivery or read receipt for a conversation
+async fn post_receipt(
+    channel: &str,
+    connected: &str,
+    state: &str,
+    reader: String,
+    up_to_id: i32,
+) -> Result<String, String> {
+    validate_connected_person(connected)?;
+
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/{}/receipts/{}",
+        channel, connected
+    );
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "state": state, "reader": reader, "up_to_id": up_to_id }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to mark a 'my-client' conversation read up to a message id
+#[tauri::command]
+async fn mark_conversation_read_my_client(
+    connected: String,
+    reader: String,
+    up_to_id: i32,
+) -> Result<String, String> {
+    post_receipt("my", &connected, "read", reader, up_to_id).await
+}
+
+// Command to mark a 'my-client' conversation delivered up to a message id
+#[tauri::command]
+async fn mark_conversation_delivered_my_client(
+    connected: String,
+    reader: String,
+    up_to_id: i32,
+) -> Result<String, String> {
+    post_receipt("my", &connected, "delivered", reader, up_to_id).await
+}
+
+// Command to mark a 'other-client' conversation read up to a message id
+#[tauri::command]
+async fn mark_conversation_read_other_client(
+    connected: String,
+    reader: String,
+    up_to_id: i32,
+) -> Result<String, String> {
+    post_receipt("other", &connected, "read", reader, up_to_id).await
+}
+
+// Command to mark a 'other-client' conversation delivered up to a message id
+#[tauri::command]
+async fn mark_conversation_delivered_other_client(
+    connected: String,
+    reader: String,
+    up_to_id: i32,
+) -> Result<String, String> {
+    post_receipt("other", &connected, "delivered", reader, up_to_id).await
+}
+
+// Command to list every 'my-client' conversation with its last message and unread count
+#[tauri::command]
+async fn get_conversations_my_client() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/message/my/conversations")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list every 'other-client' conversation with its last message and unread count
+#[tauri::command]
+async fn get_conversations_other_client() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/message/other/conversations")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to get the whole thread a 'my-client' message belongs to
+#[tauri::command]
+async fn get_thread_my_client(id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/thread", id);
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to get the whole thread a 'other-client' message belongs to
+#[tauri::command]
+async fn get_thread_other_client(id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/messages/{}/thread", id);
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to react to a message in 'my-client' with an emoji or a :short_code:
+#[tauri::command]
+async fn add_reaction_my_client(id: i32, reactor: String, emoji: String) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/reactions", id);
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "reactor": reactor, "emoji": emoji }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to take back a reaction on a message in 'my-client'
+#[tauri::command]
+async fn remove_reaction_my_client(id: i32, reactor: String, emoji: String) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/reactions", id);
+    let response = client
+        .delete(&url)
+        .query(&[("reactor", reactor), ("emoji", emoji)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to react to a message in 'other-client' with an emoji or a :short_code:
+#[tauri::command]
+async fn add_reaction_other_client(id: i32, reactor: String, emoji: String) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/messages/{}/reactions",
+        id
+    );
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "reactor": reactor, "emoji": emoji }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to take back a reaction on a message in 'other-client'
+#[tauri::command]
+async fn remove_reaction_other_client(
+    id: i32,
+    reactor: String,
+    emoji: String,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/messages/{}/reactions",
+        id
+    );
+    let response = client
+        .delete(&url)
+        .query(&[("reactor", reactor), ("emoji", emoji)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to upload a file to attach to a message, returns the references to send with it.
+// Image metadata (EXIF, GPS) is stripped unless keep_metadata is set.
+#[tauri::command]
+async fn upload_attachment(
+    file_name: String,
+    mime: String,
+    data: Vec<u8>,
+    keep_metadata: Option<bool>,
+) -> Result<String, String> {
+    let part = reqwest::multipart::Part::bytes(data)
+        .file_name(file_name)
+        .mime_str(&mime)
+        .map_err(|e| e.to_string())?;
+    let form = reqwest::multipart::Form::new().part("file", part);
+
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/attachments")
+        .query(&[("keep_metadata", keep_metadata.unwrap_or(false))])
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to create a group conversation on 'my-client', the owner is added automatically
+#[tauri::command]
+async fn create_group_chat_my_client(
+    name: String,
+    owner: String,
+    members: Vec<String>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/my/chats")
+        .json(&serde_json::json!({ "name": name, "owner": owner, "members": members }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to create a group conversation on 'other-client', the owner is added automatically
+#[tauri::command]
+async fn create_group_chat_other_client(
+    name: String,
+    owner: String,
+    members: Vec<String>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/other/chats")
+        .json(&serde_json::json!({ "name": name, "owner": owner, "members": members }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the group conversations of 'my-client', optionally of one member only
+#[tauri::command]
+async fn get_group_chats_my_client(member: Option<String>) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/message/my/chats");
+    if let Some(member) = member {
+        request = request.query(&[("member", member)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the group conversations of 'other-client', optionally of one member only
+#[tauri::command]
+async fn get_group_chats_other_client(member: Option<String>) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/message/other/chats");
+    if let Some(member) = member {
+        request = request.query(&[("member", member)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the members of a group conversation with their roles
+#[tauri::command]
+async fn get_group_chat_members(id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/chats/{}/members", id);
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Helper to post a membership change of a group conversation
+async fn post_group_chat_action(
+    id: i32,
+    action: &str,
+    body: serde_json::Value,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/chats/{}/{}", id, action);
+    let response = client
+        .post(&url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to join a group conversation
+#[tauri::command]
+async fn join_group_chat(id: i32, member: String) -> Result<(), String> {
+    post_group_chat_action(id, "join", serde_json::json!({ "member": member })).await
+}
+
+// Command to leave a group conversation
+#[tauri::command]
+async fn leave_group_chat(id: i32, member: String) -> Result<(), String> {
+    post_group_chat_action(id, "leave", serde_json::json!({ "member": member })).await
+}
+
+// Command to remove a member from a group conversation, admins and owner only
+#[tauri::command]
+async fn kick_group_chat_member(id: i32, actor: String, member: String) -> Result<(), String> {
+    post_group_chat_action(
+        id,
+        "kick",
+        serde_json::json!({ "actor": actor, "member": member }),
+    )
+    .await
+}
+
+// Command to change the role ("owner", "admin" or "member") of a member, owner only
+#[tauri::command]
+async fn set_group_chat_role(
+    id: i32,
+    actor: String,
+    member: String,
+    role: String,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/chats/{}/role", id);
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "actor": actor, "member": member, "role": role }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to send a message to every member of a group conversation
+#[tauri::command]
+async fn send_group_chat_message(
+    id: i32,
+    sender: String,
+    content: String,
+    reply_to: Option<i32>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/chats/{}/send", id);
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "sender": sender, "content": content, "reply_to": reply_to }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to get the history of a group conversation, membership changes included
+#[tauri::command]
+async fn get_group_chat_messages(id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/chats/{}/messages", id);
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to mark a group conversation read for one member up to a message
+#[tauri::command]
+async fn mark_group_chat_read(id: i32, member: String, up_to_id: i32) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/chats/{}/receipts", id);
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "member": member, "up_to_id": up_to_id }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the scheduled messages of 'my-client', pending ones unless a status is given
+#[tauri::command]
+async fn get_scheduled_messages_my_client(
+    connected: Option<String>,
+    status: Option<String>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/message/my/scheduled");
+    if let Some(connected) = connected {
+        request = request.query(&[("connected", connected)]);
+    }
+    if let Some(status) = status {
+        request = request.query(&[("status", status)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to change the content or send time of a pending message in 'my-client'
+#[tauri::command]
+async fn edit_scheduled_message_my_client(
+    id: i32,
+    content: Option<String>,
+    send_at: Option<String>,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/scheduled/{}", id);
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "content": content, "send_at": send_at }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to cancel a pending message in 'my-client'
+#[tauri::command]
+async fn cancel_scheduled_message_my_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/scheduled/{}", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the scheduled messages of 'other-client', pending ones unless a status is given
+#[tauri::command]
+async fn get_scheduled_messages_other_client(
+    connected: Option<String>,
+    status: Option<String>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/message/other/scheduled");
+    if let Some(connected) = connected {
+        request = request.query(&[("connected", connected)]);
+    }
+    if let Some(status) = status {
+        request = request.query(&[("status", status)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to change the content or send time of a pending message in 'other-client'
+#[tauri::command]
+async fn edit_scheduled_message_other_client(
+    id: i32,
+    content: Option<String>,
+    send_at: Option<String>,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/scheduled/{}", id);
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "content": content, "send_at": send_at }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to cancel a pending message in 'other-client'
+#[tauri::command]
+async fn cancel_scheduled_message_other_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/scheduled/{}", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to get the disappearing messages timer of a conversation in 'my-client'
+#[tauri::command]
+async fn get_conversation_expiry_my_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/expiry",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to set the disappearing messages timer of a conversation in 'my-client', None turns it off
+#[tauri::command]
+async fn set_conversation_expiry_my_client(
+    connected: String,
+    expiry_seconds: Option<i32>,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/expiry",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "expiry_seconds": expiry_seconds }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to get the disappearing messages timer of a conversation in 'other-client'
+#[tauri::command]
+async fn get_conversation_expiry_other_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/expiry",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to set the disappearing messages timer of a conversation in 'other-client', None turns it off
+#[tauri::command]
+async fn set_conversation_expiry_other_client(
+    connected: String,
+    expiry_seconds: Option<i32>,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/expiry",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "expiry_seconds": expiry_seconds }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to tell whether repeated messages are collapsed in a conversation in 'my-client'
+#[tauri::command]
+async fn get_conversation_collapse_my_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/collapse",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to turn collapsing of repeated messages on or off in a conversation in 'my-client'
+#[tauri::command]
+async fn set_conversation_collapse_my_client(
+    connected: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/collapse",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "enabled": enabled }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to tell whether repeated messages are collapsed in a conversation in 'other-client'
+#[tauri::command]
+async fn get_conversation_collapse_other_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/collapse",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to turn collapsing of repeated messages on or off in a conversation in 'other-client'
+#[tauri::command]
+async fn set_conversation_collapse_other_client(
+    connected: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/collapse",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({ "enabled": enabled }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the labels of a conversation in 'my-client' with their message counts
+#[tauri::command]
+async fn get_labels_my_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/labels",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to relabel the messages between two ids of a conversation in 'my-client'
+#[tauri::command]
+async fn relabel_messages_my_client(
+    connected: String,
+    from_id: i32,
+    to_id: i32,
+    label: Option<String>,
+    current_label: Option<String>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/labels",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({
+            "from_id": from_id,
+            "to_id": to_id,
+            "label": label,
+            "current_label": current_label,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the labels of a conversation in 'other-client' with their message counts
+#[tauri::command]
+async fn get_labels_other_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/labels",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to relabel the messages between two ids of a conversation in 'other-client'
+#[tauri::command]
+async fn relabel_messages_other_client(
+    connected: String,
+    from_id: i32,
+    to_id: i32,
+    label: Option<String>,
+    current_label: Option<String>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/labels",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({
+            "from_id": from_id,
+            "to_id": to_id,
+            "label": label,
+            "current_label": current_label,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to star a 'my-client' message
+#[tauri::command]
+async fn star_message_my_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/star", id);
+    let response = client.put(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to remove the star of a 'my-client' message
+#[tauri::command]
+async fn unstar_message_my_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/star", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the starred 'my-client' messages of every conversation
+#[tauri::command]
+async fn get_starred_messages_my_client(
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = "http://127.0.0.1:4875/message/my/starred";
+    let mut request = client.get(url);
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+    if let Some(offset) = offset {
+        request = request.query(&[("offset", offset)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to pin a 'my-client' message to the top of its conversation
+#[tauri::command]
+async fn pin_message_my_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/pin", id);
+    let response = client.put(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to unpin a 'my-client' message
+#[tauri::command]
+async fn unpin_message_my_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/messages/{}/pin", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the pinned messages of a conversation in 'my-client'
+#[tauri::command]
+async fn get_pinned_messages_my_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/pinned",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to get a page of the history of a conversation in 'my-client', older than `before`
+#[tauri::command]
+async fn get_history_my_client(
+    connected: String,
+    before: Option<i32>,
+    limit: Option<u32>,
+    label: Option<String>,
+    viewer: Option<String>,
+) -> Result<String, String> {
+    validate_connected_person(&connected)?;
+
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/history",
+        connected
+    );
+    let mut request = client.get(&url);
+    if let Some(before) = before {
+        request = request.query(&[("before", before)]);
+    }
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+    if let Some(label) = label {
+        request = request.query(&[("label", label)]);
+    }
+    if let Some(viewer) = viewer {
+        request = request.query(&[("viewer", viewer)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to star an 'other-client' message
+#[tauri::command]
+async fn star_message_other_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/messages/{}/star", id);
+    let response = client.put(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to remove the star of an 'other-client' message
+#[tauri::command]
+async fn unstar_message_other_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/messages/{}/star", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the starred 'other-client' messages of every conversation
+#[tauri::command]
+async fn get_starred_messages_other_client(
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<String, String> {
+    let client = Client::new();
+    let url = "http://127.0.0.1:4875/message/other/starred";
+    let mut request = client.get(url);
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+    if let Some(offset) = offset {
+        request = request.query(&[("offset", offset)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to pin an 'other-client' message to the top of its conversation
+#[tauri::command]
+async fn pin_message_other_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/messages/{}/pin", id);
+    let response = client.put(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to unpin an 'other-client' message
+#[tauri::command]
+async fn unpin_message_other_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/messages/{}/pin", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the pinned messages of a conversation in 'other-client'
+#[tauri::command]
+async fn get_pinned_messages_other_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/pinned",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to get a page of the history of a conversation in 'other-client', older than `before`
+#[tauri::command]
+async fn get_history_other_client(
+    connected: String,
+    before: Option<i32>,
+    limit: Option<u32>,
+    label: Option<String>,
+    viewer: Option<String>,
+) -> Result<String, String> {
+    validate_connected_person(&connected)?;
+
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/history",
+        connected
+    );
+    let mut request = client.get(&url);
+    if let Some(before) = before {
+        request = request.query(&[("before", before)]);
+    }
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+    if let Some(label) = label {
+        request = request.query(&[("label", label)]);
+    }
+    if let Some(viewer) = viewer {
+        request = request.query(&[("viewer", viewer)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to get the draft of a conversation in 'my-client'
+#[tauri::command]
+async fn get_draft_my_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/draft",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to save the draft of a conversation in 'my-client', an empty draft clears it
+#[tauri::command]
+async fn save_draft_my_client(
+    connected: String,
+    content: String,
+    reply_to: Option<i32>,
+    attachments: Vec<AttachmentRef>,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/draft",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({
+            "content": content,
+            "reply_to": reply_to,
+            "attachments": attachments,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to clear the draft of a conversation in 'my-client'
+#[tauri::command]
+async fn clear_draft_my_client(connected: String) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/my/conversations/{}/draft",
+        connected
+    );
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to get the draft of a conversation in 'other-client'
+#[tauri::command]
+async fn get_draft_other_client(connected: String) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/draft",
+        connected
+    );
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to save the draft of a conversation in 'other-client', an empty draft clears it
+#[tauri::command]
+async fn save_draft_other_client(
+    connected: String,
+    content: String,
+    reply_to: Option<i32>,
+    attachments: Vec<AttachmentRef>,
+) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/draft",
+        connected
+    );
+    let response = client
+        .put(&url)
+        .json(&serde_json::json!({
+            "content": content,
+            "reply_to": reply_to,
+            "attachments": attachments,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to clear the draft of a conversation in 'other-client'
+#[tauri::command]
+async fn clear_draft_other_client(connected: String) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!(
+        "http://127.0.0.1:4875/message/other/conversations/{}/draft",
+        connected
+    );
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to forward 'my-client' messages to a conversation in "my" or "other" client,
+// optionally followed by a comment
+#[tauri::command]
+async fn forward_messages_my_client(
+    ids: Vec<i32>,
+    target: String,
+    sender: String,
+    receiver: String,
+    connected: String,
+    comment: Option<String>,
+) -> Result<String, String> {
+    validate_connected_person(&connected)?;
+
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/my/forward")
+        .json(&serde_json::json!({
+            "ids": ids,
+            "target": target,
+            "sender": sender,
+            "receiver": receiver,
+            "connected": connected,
+            "comment": comment,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to forward 'other-client' messages to a conversation in "my" or "other" client,
+// optionally followed by a comment
+#[tauri::command]
+async fn forward_messages_other_client(
+    ids: Vec<i32>,
+    target: String,
+    sender: String,
+    receiver: String,
+    connected: String,
+    comment: Option<String>,
+) -> Result<String, String> {
+    validate_connected_person(&connected)?;
+
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/other/forward")
+        .json(&serde_json::json!({
+            "ids": ids,
+            "target": target,
+            "sender": sender,
+            "receiver": receiver,
+            "connected": connected,
+            "comment": comment,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the keyword alerts matched against incoming messages
+#[tauri::command]
+async fn get_keyword_alerts() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/message/alerts")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to register a keyword alert, a plain word or phrase unless is_regex is set
+#[tauri::command]
+async fn create_keyword_alert(pattern: String, is_regex: Option<bool>) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/alerts/")
+        .json(&serde_json::json!({
+            "pattern": pattern,
+            "is_regex": is_regex.unwrap_or(false),
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to remove a keyword alert along with the messages it flagged
+#[tauri::command]
+async fn delete_keyword_alert(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/alerts/{}", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to page through the messages flagged by keyword alerts, newest first
+#[tauri::command]
+async fn get_alert_feed(before: Option<i32>, limit: Option<u32>) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/message/alerts/feed");
+    if let Some(before) = before {
+        request = request.query(&[("before", before)]);
+    }
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the rules applied to incoming 'my-client' messages
+#[tauri::command]
+async fn get_rules_my_client() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/message/my/rules")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to add a rule for incoming 'my-client' messages. The rule carries its name,
+// conditions, actions and optionally enabled, position and stop_processing.
+#[tauri::command]
+async fn create_rule_my_client(rule: serde_json::Value) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/my/rules/")
+        .json(&rule)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to replace a rule for incoming 'my-client' messages
+#[tauri::command]
+async fn update_rule_my_client(id: i32, rule: serde_json::Value) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/rules/{}", id);
+    let response = client
+        .put(&url)
+        .json(&rule)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to remove a rule for incoming 'my-client' messages
+#[tauri::command]
+async fn delete_rule_my_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/my/rules/{}", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to list the rules applied to incoming 'other-client' messages
+#[tauri::command]
+async fn get_rules_other_client() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/message/other/rules")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to add a rule for incoming 'other-client' messages. The rule carries its name,
+// conditions, actions and optionally enabled, position and stop_processing.
+#[tauri::command]
+async fn create_rule_other_client(rule: serde_json::Value) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/message/other/rules/")
+        .json(&rule)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to replace a rule for incoming 'other-client' messages
+#[tauri::command]
+async fn update_rule_other_client(id: i32, rule: serde_json::Value) -> Result<String, String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/rules/{}", id);
+    let response = client
+        .put(&url)
+        .json(&rule)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to remove a rule for incoming 'other-client' messages
+#[tauri::command]
+async fn delete_rule_other_client(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/message/other/rules/{}", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to publish a post on the wall
+#[tauri::command]
+async fn create_wall_post(author: String, content: String) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/wailing/posts/")
+        .json(&serde_json::json!({ "author": author, "content": content }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to submit a form
+#[tauri::command]
+async fn submit_form(
+    form_slug: String,
+    submitter: String,
+    content: String,
+) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/form/submissions/")
+        .json(&serde_json::json!({
+            "form_slug": form_slug,
+            "submitter": submitter,
+            "content": content,
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to pin a wall post, pinned posts are exempt from the retention policy
+#[tauri::command]
+async fn pin_wall_post(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/wailing/posts/{}/pin", id);
+    let response = client.put(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to unpin a wall post
+#[tauri::command]
+async fn unpin_wall_post(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/wailing/posts/{}/pin", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to star a form submission, starred submissions are exempt from the retention policy
+#[tauri::command]
+async fn star_form_submission(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/form/submissions/{}/star", id);
+    let response = client.put(&url).send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to remove the star of a form submission
+#[tauri::command]
+async fn unstar_form_submission(id: i32) -> Result<(), String> {
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:4875/form/submissions/{}/star", id);
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Command to get the retention policy in effect
+#[tauri::command]
+async fn get_retention_policy() -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .get("http://127.0.0.1:4875/admin/retention/policy")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to enforce the retention policy now, only a dry run unless dry_run is false
+#[tauri::command]
+async fn run_retention(dry_run: Option<bool>) -> Result<String, String> {
+    let client = Client::new();
+    let response = client
+        .post("http://127.0.0.1:4875/admin/retention/run")
+        .query(&[("dry_run", dry_run.unwrap_or(true))])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
+// Command to list the reports of the most recent retention runs
+#[tauri::command]
+async fn get_retention_runs(limit: Option<u32>) -> Result<String, String> {
+    let client = Client::new();
+    let mut request = client.get("http://127.0.0.1:4875/admin/retention/runs");
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    handle_response(response).await.map_err(|e| e.to_string())
+}
+
 #[tokio::main]
 async fn main() {
     tauri::Builder::default()
@@ -310,6 +2338,118 @@ async fn main() {
             get_contacts_other_client,
             add_contact_my_client,
             add_contact_other_client,
+            get_contact_groups,
+            create_contact_group,
+            rename_contact_group,
+            delete_contact_group,
+            get_contact_group_members,
+            add_contacts_to_group,
+            remove_contact_from_group,
+            send_group_message_my_client,
+            send_group_message_other_client,
+            find_duplicate_contacts_my_client,
+            find_duplicate_contacts_other_client,
+            merge_contacts_my_client,
+            merge_contacts_other_client,
+            get_contact_merges,
+            undo_contact_merge,
+            upload_avatar_my_client,
+            upload_avatar_other_client,
+            delete_avatar_my_client,
+            delete_avatar_other_client,
+            get_contact_timeline,
+            edit_message_my_client,
+            edit_message_other_client,
+            get_message_revisions_my_client,
+            get_message_revisions_other_client,
+            delete_message_my_client,
+            delete_message_other_client,
+            mark_conversation_read_my_client,
+            mark_conversation_delivered_my_client,
+            mark_conversation_read_other_client,
+            mark_conversation_delivered_other_client,
+            get_conversations_my_client,
+            get_conversations_other_client,
+            get_thread_my_client,
+            get_thread_other_client,
+            add_reaction_my_client,
+            add_reaction_other_client,
+            remove_reaction_my_client,
+            remove_reaction_other_client,
+            upload_attachment,
+            create_group_chat_my_client,
+            create_group_chat_other_client,
+            get_group_chats_my_client,
+            get_group_chats_other_client,
+            get_group_chat_members,
+            join_group_chat,
+            leave_group_chat,
+            kick_group_chat_member,
+            set_group_chat_role,
+            send_group_chat_message,
+            get_group_chat_messages,
+            mark_group_chat_read,
+            get_scheduled_messages_my_client,
+            get_scheduled_messages_other_client,
+            edit_scheduled_message_my_client,
+            edit_scheduled_message_other_client,
+            cancel_scheduled_message_my_client,
+            cancel_scheduled_message_other_client,
+            get_conversation_expiry_my_client,
+            get_conversation_expiry_other_client,
+            set_conversation_expiry_my_client,
+            set_conversation_expiry_other_client,
+            get_conversation_collapse_my_client,
+            get_conversation_collapse_other_client,
+            set_conversation_collapse_my_client,
+            set_conversation_collapse_other_client,
+            get_labels_my_client,
+            get_labels_other_client,
+            relabel_messages_my_client,
+            relabel_messages_other_client,
+            star_message_my_client,
+            star_message_other_client,
+            unstar_message_my_client,
+            unstar_message_other_client,
+            get_starred_messages_my_client,
+            get_starred_messages_other_client,
+            pin_message_my_client,
+            pin_message_other_client,
+            unpin_message_my_client,
+            unpin_message_other_client,
+            get_pinned_messages_my_client,
+            get_pinned_messages_other_client,
+            get_history_my_client,
+            get_history_other_client,
+            get_draft_my_client,
+            get_draft_other_client,
+            save_draft_my_client,
+            save_draft_other_client,
+            clear_draft_my_client,
+            clear_draft_other_client,
+            forward_messages_my_client,
+            forward_messages_other_client,
+            get_keyword_alerts,
+            create_keyword_alert,
+            delete_keyword_alert,
+            get_alert_feed,
+            get_rules_my_client,
+            create_rule_my_client,
+            update_rule_my_client,
+            delete_rule_my_client,
+            get_rules_other_client,
+            create_rule_other_client,
+            update_rule_other_client,
+            delete_rule_other_client,
+            create_wall_post,
+            submit_form,
+            pin_wall_post,
+            unpin_wall_post,
+            star_form_submission,
+            unstar_form_submission,
+            get_retention_policy,
+            run_retention,
+            get_retention_runs,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,10 @@
+// Function to read a numeric setting from the environment (or .env), falling back to a default
+pub fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    match std::env::var(key) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!("Ignoring invalid value '{}' for {}", value, key);
+            default
+        }),
+        Err(_) => default,
+    }
+}
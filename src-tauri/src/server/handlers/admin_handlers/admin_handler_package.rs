@@ -0,0 +1,32 @@
+use crate::server::AppState;
+use actix_web::{post, web, HttpResponse, Responder};
+
+// Handler function to reset 'retention_runs' table
+#[post("/reset-retention-runs-table")]
+pub async fn reset_retention_runs_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    let drop_table_query = "DROP TABLE IF EXISTS retention_runs;";
+    let create_table_query = "
+        CREATE TABLE retention_runs (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            run_by VARCHAR(16) NOT NULL,
+            dry_run BOOLEAN NOT NULL,
+            started_at TIMESTAMP NOT NULL,
+            finished_at TIMESTAMP NOT NULL,
+            report JSON NOT NULL,
+            INDEX (started_at)
+        );";
+
+    match sqlx::query(drop_table_query).execute(&pool.db_pool).await {
+        Ok(_) => match sqlx::query(create_table_query).execute(&pool.db_pool).await {
+            Ok(_) => HttpResponse::Ok().body("Table 'retention_runs' reset successfully"),
+            Err(e) => {
+                eprintln!("Error creating 'retention_runs' table: {}", e);
+                HttpResponse::InternalServerError().body("Error creating table")
+            }
+        },
+        Err(e) => {
+            eprintln!("Error dropping 'retention_runs' table: {}", e);
+            HttpResponse::InternalServerError().body("Error dropping table")
+        }
+    }
+}
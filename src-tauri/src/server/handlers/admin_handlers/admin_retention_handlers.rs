@@ -0,0 +1,307 @@
+use crate::server::config::env_or;
+use crate::server::handlers::message_handlers::{self, PurgeReport};
+use crate::server::AppState;
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{FromRow, MySql, QueryBuilder};
+use std::collections::BTreeMap;
+
+const DEFAULT_RUNS_LIMIT: u32 = 20;
+const MAX_RUNS_LIMIT: u32 = 200;
+
+// How many wall posts or form submissions a purge deletes per transaction
+const PURGE_BATCH_SIZE: i64 = 500;
+
+// Define the global retention rules, read from the environment (or .env). Zero keeps the
+// items forever, which is the default for every kind.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RetentionPolicy {
+    message_days: u32,
+    wall_post_days: u32,
+    form_submission_months: u32,
+}
+
+impl RetentionPolicy {
+    pub fn from_env() -> Self {
+        RetentionPolicy {
+            message_days: env_or("RETENTION_MESSAGE_DAYS", 0),
+            wall_post_days: env_or("RETENTION_WALL_POST_DAYS", 0),
+            form_submission_months: env_or("RETENTION_FORM_SUBMISSION_MONTHS", 0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.message_days > 0 || self.wall_post_days > 0 || self.form_submission_months > 0
+    }
+
+    // Starred and pinned items are never purged
+    fn message_condition(&self) -> Option<String> {
+        (self.message_days > 0).then(|| {
+            format!(
+                "timestamp < CURRENT_TIMESTAMP - INTERVAL {} DAY \
+                 AND starred_at IS NULL AND pinned_at IS NULL",
+                self.message_days
+            )
+        })
+    }
+
+    fn wall_post_condition(&self) -> Option<String> {
+        (self.wall_post_days > 0).then(|| {
+            format!(
+                "created_at < CURRENT_TIMESTAMP - INTERVAL {} DAY AND pinned_at IS NULL",
+                self.wall_post_days
+            )
+        })
+    }
+
+    fn form_submission_condition(&self) -> Option<String> {
+        (self.form_submission_months > 0).then(|| {
+            format!(
+                "submitted_at < CURRENT_TIMESTAMP - INTERVAL {} MONTH AND starred_at IS NULL",
+                self.form_submission_months
+            )
+        })
+    }
+}
+
+// Define a struct to represent consecutive purged ids, reports list them as ranges to stay small
+#[derive(Debug, Serialize)]
+pub struct IdRange {
+    first: i32,
+    last: i32,
+}
+
+// Define a struct to summarise what a retention run purged, or would purge in a dry run
+#[derive(Debug, Serialize)]
+pub struct RetentionReport {
+    pub run_id: Option<u64>,
+    pub dry_run: bool,
+    pub policy: RetentionPolicy,
+    pub messages: usize,
+    pub wall_posts: usize,
+    pub form_submissions: usize,
+    pub removed_attachments: usize,
+    pub freed_bytes: i64,
+    pub purged_ids: BTreeMap<&'static str, Vec<IdRange>>, // Per table, only tables with purges
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+// Define a struct to represent a recorded retention run
+#[derive(Debug, Serialize, FromRow)]
+struct RetentionRun {
+    id: i32,
+    run_by: String, // "schedule" or "admin"
+    dry_run: bool,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    report: Json<serde_json::Value>,
+}
+
+// Define the query parameters accepted by the manual retention run
+#[derive(Deserialize)]
+struct RunQuery {
+    dry_run: Option<bool>, // Defaults to a dry run, purging has to be asked for explicitly
+}
+
+// Define the query parameters accepted by the run listing
+#[derive(Deserialize)]
+struct RunsQuery {
+    limit: Option<u32>,
+}
+
+fn id_ranges(mut ids: Vec<i32>) -> Vec<IdRange> {
+    ids.sort_unstable();
+    let mut ranges: Vec<IdRange> = Vec::new();
+    for id in ids {
+        match ranges.last_mut() {
+            Some(range) if range.last + 1 == id => range.last = id,
+            _ => ranges.push(IdRange {
+                first: id,
+                last: id,
+            }),
+        }
+    }
+    ranges
+}
+
+// Deletes one batch of the rows of a table matching a retention condition, returns their ids
+async fn purge_batch(
+    pool: &AppState,
+    table: &str,
+    condition: &str,
+) -> Result<Vec<i32>, sqlx::Error> {
+    let mut tx = pool.db_pool.begin().await?;
+
+    let select_query = format!(
+        "SELECT id FROM {} WHERE {} ORDER BY id LIMIT ? FOR UPDATE",
+        table, condition
+    );
+    let ids = sqlx::query_scalar::<_, i32>(&select_query)
+        .bind(PURGE_BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+    if ids.is_empty() {
+        return Ok(ids);
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(format!("DELETE FROM {} WHERE id IN (", table));
+    let mut separated = builder.separated(", ");
+    for id in &ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+    builder.build().execute(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(ids)
+}
+
+// Deletes (or only lists) the rows of a table matching a retention condition, in batches so a
+// large backlog never holds its locks for long, returns the ids of the rows
+async fn apply_to_table(
+    pool: &AppState,
+    table: &str,
+    condition: &str,
+    dry_run: bool,
+) -> Result<Vec<i32>, sqlx::Error> {
+    if dry_run {
+        let query_str = format!("SELECT id FROM {} WHERE {} ORDER BY id", table, condition);
+        return sqlx::query_scalar::<_, i32>(&query_str)
+            .fetch_all(&pool.db_pool)
+            .await;
+    }
+
+    let mut purged = Vec::new();
+    loop {
+        let ids = purge_batch(pool, table, condition).await?;
+        let last_batch = ids.len() < PURGE_BATCH_SIZE as usize;
+        purged.extend(ids);
+        if last_batch {
+            return Ok(purged);
+        }
+    }
+}
+
+// Function to enforce the retention policy and record the report of the run. A dry run only
+// lists what would be purged. Messages go through the same purge as disappearing messages,
+// so their reactions, revisions, receipts and no longer used files go with them.
+pub async fn enforce_retention(
+    pool: &AppState,
+    policy: RetentionPolicy,
+    dry_run: bool,
+    run_by: &str,
+) -> Result<RetentionReport, sqlx::Error> {
+    let started_at = Utc::now();
+
+    let mut purge = PurgeReport::default();
+    if let Some(condition) = policy.message_condition() {
+        if dry_run {
+            purge.ids = message_handlers::matching_message_ids(pool, &condition).await?;
+            purge.deleted = purge.ids.values().map(Vec::len).sum();
+        } else {
+            purge = message_handlers::purge_messages(pool, &condition, "messages-purged").await?;
+        }
+    }
+
+    // Wall posts and form submissions are created through /wailing/posts/ and
+    // /form/submissions/, nothing else links to them
+    let wall_posts = match policy.wall_post_condition() {
+        Some(condition) => apply_to_table(pool, "wailing_wall_posts", &condition, dry_run).await?,
+        None => Vec::new(),
+    };
+    let form_submissions = match policy.form_submission_condition() {
+        Some(condition) => apply_to_table(pool, "form_submissions", &condition, dry_run).await?,
+        None => Vec::new(),
+    };
+
+    let mut report = RetentionReport {
+        run_id: None,
+        dry_run,
+        policy,
+        messages: purge.deleted,
+        wall_posts: wall_posts.len(),
+        form_submissions: form_submissions.len(),
+        removed_attachments: purge.removed_attachments,
+        freed_bytes: purge.freed_bytes,
+        purged_ids: BTreeMap::new(),
+        started_at,
+        finished_at: Utc::now(),
+    };
+    let tables = purge.ids.into_iter().chain([
+        ("wailing_wall_posts", wall_posts),
+        ("form_submissions", form_submissions),
+    ]);
+    for (table, ids) in tables {
+        if !ids.is_empty() {
+            report.purged_ids.insert(table, id_ranges(ids));
+        }
+    }
+
+    let result = sqlx::query(
+        "INSERT INTO retention_runs (run_by, dry_run, started_at, finished_at, report) \
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(run_by)
+    .bind(dry_run)
+    .bind(report.started_at)
+    .bind(report.finished_at)
+    .bind(Json(&report))
+    .execute(&pool.db_pool)
+    .await?;
+    report.run_id = Some(result.last_insert_id());
+
+    Ok(report)
+}
+
+// Handler function to get the retention policy in effect
+#[get("/retention/policy")]
+pub async fn get_retention_policy() -> impl Responder {
+    HttpResponse::Ok().json(RetentionPolicy::from_env())
+}
+
+// Handler function to enforce the retention policy right away, as a dry run unless
+// `dry_run=false` is given
+#[post("/retention/run")]
+pub async fn run_retention(
+    pool: web::Data<AppState>,
+    params: web::Query<RunQuery>,
+) -> impl Responder {
+    let dry_run = params.dry_run.unwrap_or(true);
+    match enforce_retention(&pool, RetentionPolicy::from_env(), dry_run, "admin").await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            eprintln!("Error enforcing the retention policy: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list the most recent retention runs with their reports
+#[get("/retention/runs")]
+pub async fn get_retention_runs(
+    pool: web::Data<AppState>,
+    params: web::Query<RunsQuery>,
+) -> impl Responder {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_RUNS_LIMIT)
+        .clamp(1, MAX_RUNS_LIMIT);
+
+    match sqlx::query_as::<_, RetentionRun>(
+        "SELECT id, run_by, dry_run, started_at, finished_at, report FROM retention_runs \
+         ORDER BY started_at DESC, id DESC LIMIT ?",
+    )
+    .bind(limit)
+    .fetch_all(&pool.db_pool)
+    .await
+    {
+        Ok(runs) => HttpResponse::Ok().json(runs),
+        Err(e) => {
+            eprintln!("Error retrieving retention runs: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
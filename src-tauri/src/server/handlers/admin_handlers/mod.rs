@@ -0,0 +1,19 @@
+mod admin_handler_package;
+mod admin_retention_handlers;
+use admin_handler_package::reset_retention_runs_table_handler;
+use admin_retention_handlers::get_retention_policy;
+use admin_retention_handlers::get_retention_runs;
+use admin_retention_handlers::run_retention;
+
+// Used by the background jobs of the server
+pub use admin_retention_handlers::enforce_retention;
+pub use admin_retention_handlers::RetentionPolicy;
+
+pub fn admin_handler_config(conf: &mut actix_web::web::ServiceConfig) {
+    let scope = actix_web::web::scope("/admin")
+        .service(reset_retention_runs_table_handler)
+        .service(get_retention_policy)
+        .service(run_retention)
+        .service(get_retention_runs);
+    conf.service(scope);
+}
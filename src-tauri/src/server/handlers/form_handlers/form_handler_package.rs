@@ -1,5 +1,5 @@
 use crate::server::AppState;
-use actix_web::{get, web, HttpResponse, Responder, Result};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, Result};
 use serde::{Deserialize, Serialize};
 
 // Define the structure for form pages
@@ -9,6 +9,17 @@ struct FormPage {
     title: String,
 }
 
+// Define the body of a new form submission
+#[derive(Deserialize)]
+struct NewFormSubmission {
+    form_slug: String,
+    submitter: String,
+    content: String,
+}
+
+const MAX_SUBMITTER_CHARS: usize = 256;
+const MAX_SUBMISSION_BYTES: usize = 65_535;
+
 // Define the handler function
 #[get("/all-form-pages")]
 pub async fn get_all_form_pages(db_pool: web::Data<AppState>) -> Result<impl Responder> {
@@ -30,3 +41,131 @@ pub async fn get_all_form_pages(db_pool: web::Data<AppState>) -> Result<impl Res
 pub async fn hello_world() -> Result<impl Responder> {
     Ok(HttpResponse::Ok().body("Hello, World ORKUN!"))
 }
+
+// Handler function to reset 'form_submissions' table
+#[post("/reset-form-submissions-table")]
+pub async fn reset_form_submissions_table_handler(db_pool: web::Data<AppState>) -> impl Responder {
+    let drop_table_query = "DROP TABLE IF EXISTS form_submissions;";
+    let create_table_query = "
+        CREATE TABLE form_submissions (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            form_slug VARCHAR(255) NOT NULL,
+            submitter VARCHAR(256) NOT NULL,
+            content TEXT NOT NULL,
+            submitted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            starred_at TIMESTAMP NULL DEFAULT NULL,
+            INDEX (submitter, submitted_at)
+        );";
+
+    match sqlx::query(drop_table_query)
+        .execute(&db_pool.db_pool)
+        .await
+    {
+        Ok(_) => match sqlx::query(create_table_query)
+            .execute(&db_pool.db_pool)
+            .await
+        {
+            Ok(_) => HttpResponse::Ok().body("Table 'form_submissions' reset successfully"),
+            Err(e) => {
+                eprintln!("Error creating 'form_submissions' table: {}", e);
+                HttpResponse::InternalServerError().body("Error creating table")
+            }
+        },
+        Err(e) => {
+            eprintln!("Error dropping 'form_submissions' table: {}", e);
+            HttpResponse::InternalServerError().body("Error dropping table")
+        }
+    }
+}
+
+// Handler function to store a filled in form
+#[post("/submissions/")]
+pub async fn submit_form(
+    db_pool: web::Data<AppState>,
+    submission: web::Json<NewFormSubmission>,
+) -> impl Responder {
+    let submitter = submission.submitter.trim();
+    if submitter.is_empty() || submitter.chars().count() > MAX_SUBMITTER_CHARS {
+        return HttpResponse::BadRequest().body("Submitter must be 1 to 256 characters");
+    }
+    if submission.content.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Submission cannot be empty");
+    }
+    if submission.content.len() > MAX_SUBMISSION_BYTES {
+        return HttpResponse::PayloadTooLarge().body("Submission is too large");
+    }
+
+    // Only forms that exist can be submitted
+    let result = sqlx::query(
+        "INSERT INTO form_submissions (form_slug, submitter, content) \
+         SELECT slug, ?, ? FROM form_pages WHERE slug = ?",
+    )
+    .bind(submitter)
+    .bind(&submission.content)
+    .bind(&submission.form_slug)
+    .execute(&db_pool.db_pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => {
+            HttpResponse::NotFound().body("Form not found")
+        }
+        Ok(result) => HttpResponse::Ok().json(serde_json::json!({ "id": result.last_insert_id() })),
+        Err(e) => {
+            eprintln!("Error saving form submission: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Starred submissions are exempt from the retention policy
+async fn set_submission_starred(db_pool: &AppState, id: i32, starred: bool) -> HttpResponse {
+    let result = sqlx::query(
+        "UPDATE form_submissions \
+         SET starred_at = CASE WHEN ? THEN COALESCE(starred_at, CURRENT_TIMESTAMP) END \
+         WHERE id = ?",
+    )
+    .bind(starred)
+    .bind(id)
+    .execute(&db_pool.db_pool)
+    .await;
+
+    match result {
+        Ok(_) => {
+            match sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM form_submissions WHERE id = ?")
+                .bind(id)
+                .fetch_one(&db_pool.db_pool)
+                .await
+            {
+                Ok(0) => HttpResponse::NotFound().finish(),
+                Ok(_) => HttpResponse::Ok().finish(),
+                Err(e) => {
+                    eprintln!("Error retrieving form submission: {}", e);
+                    HttpResponse::InternalServerError().finish()
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("Error updating form submission: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to star a form submission
+#[put("/submissions/{id}/star")]
+pub async fn star_form_submission(
+    db_pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    set_submission_starred(&db_pool, id.into_inner(), true).await
+}
+
+// Handler function to remove the star of a form submission
+#[delete("/submissions/{id}/star")]
+pub async fn unstar_form_submission(
+    db_pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    set_submission_starred(&db_pool, id.into_inner(), false).await
+}
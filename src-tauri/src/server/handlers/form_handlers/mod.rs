@@ -1,10 +1,18 @@
 mod form_handler_package;
 use form_handler_package::get_all_form_pages;
 use form_handler_package::hello_world;
+use form_handler_package::reset_form_submissions_table_handler;
+use form_handler_package::star_form_submission;
+use form_handler_package::submit_form;
+use form_handler_package::unstar_form_submission;
 
 pub fn form_handler_config(conf: &mut actix_web::web::ServiceConfig) {
     let scope = actix_web::web::scope("/form")
         .service(get_all_form_pages)
-        .service(hello_world);
+        .service(hello_world)
+        .service(reset_form_submissions_table_handler)
+        .service(star_form_submission)
+        .service(unstar_form_submission)
+        .service(submit_form);
     conf.service(scope);
 }
@@ -0,0 +1,781 @@
+use super::message_get_set_handlers::SendError;
+use crate::server::config::env_or;
+use crate::server::image_metadata::{jpeg_orientation, strip_metadata};
+use crate::server::storage::{content_hash, is_content_hash, media_dir};
+use crate::server::AppState;
+use actix_files::NamedFile;
+use actix_multipart::Multipart;
+use actix_web::http::header::{
+    self, ContentDisposition, DispositionParam, DispositionType, HeaderValue,
+};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use futures_util::TryStreamExt;
+use image::io::{Limits, Reader as ImageReader};
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySql, MySqlConnection, QueryBuilder};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_MAX_ATTACHMENT_BYTES: usize = 25 * 1024 * 1024;
+const MAX_ATTACHMENTS_PER_MESSAGE: usize = 10;
+// Unreferenced uploads are kept this long so a message can still be sent with them
+const DEFAULT_ATTACHMENT_GC_GRACE_SECONDS: i64 = 24 * 60 * 60;
+
+// Bounding boxes image previews are rendered to, in pixels
+const THUMBNAIL_SIZES: [u32; 2] = [160, 480];
+const JPEG_QUALITY: u8 = 85;
+// Larger images are refused instead of decoded, this guards against decompression bombs
+const MAX_IMAGE_DIMENSION: u32 = 16384;
+const IMAGE_MIME_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+// Only these types are accepted, SVG and HTML are left out because they can carry scripts
+const ALLOWED_MIME_TYPES: [&str; 12] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/wav",
+    "video/mp4",
+    "video/webm",
+    "application/pdf",
+    "application/zip",
+    "text/plain",
+];
+
+// Define a struct to represent a file attached to a message
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AttachmentInfo {
+    hash: String,
+    file_name: String,
+    mime: String,
+    size: i64,
+    width: Option<i32>, // Dimensions as displayed, only known for images
+    height: Option<i32>,
+    #[serde(skip)]
+    thumbnail_mime: Option<String>,
+    #[sqlx(skip)]
+    url: String,
+    #[sqlx(skip)]
+    thumbnails: Vec<ThumbnailInfo>,
+}
+
+// Define a struct to represent a rendered preview of an image attachment
+#[derive(Debug, Clone, Serialize)]
+pub struct ThumbnailInfo {
+    size: u32,
+    url: String,
+}
+
+impl AttachmentInfo {
+    fn with_urls(mut self) -> Self {
+        self.url = attachment_url(&self.hash, &self.file_name);
+        if self.thumbnail_mime.is_some() {
+            self.thumbnails = THUMBNAIL_SIZES
+                .iter()
+                .map(|size| ThumbnailInfo {
+                    size: *size,
+                    url: thumbnail_url(&self.hash, *size),
+                })
+                .collect();
+        }
+        self
+    }
+}
+
+// Define a struct to hold an upload once it is ready to be stored
+struct ProcessedUpload {
+    bytes: Vec<u8>,
+    width: Option<i32>,
+    height: Option<i32>,
+    thumbnails: Vec<(u32, Vec<u8>)>,
+    thumbnail_mime: Option<&'static str>,
+}
+
+// Define the query parameters accepted by the upload endpoint
+#[derive(Deserialize)]
+struct UploadOptions {
+    #[serde(default)]
+    keep_metadata: bool, // Store images as uploaded, including EXIF and GPS data
+}
+
+// Define a struct to capture a previously uploaded file referenced by a new message
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AttachmentRef {
+    hash: String,
+    file_name: String,
+}
+
+#[derive(FromRow)]
+struct MessageAttachmentRow {
+    message_id: i32,
+    #[sqlx(flatten)]
+    attachment: AttachmentInfo,
+}
+
+// Define a struct to summarise a garbage collection run
+#[derive(Serialize)]
+pub struct GarbageReport {
+    pub removed: usize,
+    pub freed_bytes: i64,
+}
+
+// Function to build the URL the views use to download an attachment
+pub fn attachment_url(hash: &str, file_name: &str) -> String {
+    let name: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("/message/attachments/{}?name={}", hash, name)
+}
+
+// Function to build the URL of an image preview
+pub fn thumbnail_url(hash: &str, size: u32) -> String {
+    format!("/message/attachments/{}/thumbnails/{}", hash, size)
+}
+
+fn attachment_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(hash)
+}
+
+fn thumbnail_path(dir: &Path, hash: &str, size: u32) -> PathBuf {
+    dir.join(format!("{}-{}", hash, size))
+}
+
+// Keeps only the final path segment and drops control characters
+fn sanitize_file_name(file_name: &str) -> String {
+    let name: String = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(255)
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => "attachment".to_string(),
+        name => name.to_string(),
+    }
+}
+
+// Function to check the attachment references of a new message before it is stored
+pub fn validate_attachment_refs(attachments: &[AttachmentRef]) -> Result<(), String> {
+    if attachments.len() > MAX_ATTACHMENTS_PER_MESSAGE {
+        return Err(format!(
+            "A message can have at most {} attachments",
+            MAX_ATTACHMENTS_PER_MESSAGE
+        ));
+    }
+    if attachments.iter().any(|a| !is_content_hash(&a.hash)) {
+        return Err("Invalid attachment hash".to_string());
+    }
+    Ok(())
+}
+
+// Function to link uploaded files to a message, fails for files that were never uploaded
+pub async fn link_attachments(
+    conn: &mut MySqlConnection,
+    table: &str,
+    message_id: i32,
+    attachments: &[AttachmentRef],
+) -> Result<(), SendError> {
+    for (position, attachment) in attachments.iter().enumerate() {
+        let result = query(
+            "INSERT IGNORE INTO message_attachments (message_table, message_id, hash, file_name, position) \
+             SELECT ?, ?, hash, ?, ? FROM attachments WHERE hash = ?",
+        )
+        .bind(table)
+        .bind(message_id)
+        .bind(sanitize_file_name(&attachment.file_name))
+        .bind(position as i32)
+        .bind(&attachment.hash)
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            let known =
+                sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM attachments WHERE hash = ?")
+                    .bind(&attachment.hash)
+                    .fetch_one(&mut *conn)
+                    .await?;
+            if known == 0 {
+                return Err(SendError::AttachmentNotUploaded(attachment.hash.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Function to load the attachments of several messages, in the order they were attached
+pub async fn fetch_attachments(
+    pool: &AppState,
+    table: &str,
+    message_ids: &[i32],
+) -> Result<HashMap<i32, Vec<AttachmentInfo>>, sqlx::Error> {
+    let mut attachments: HashMap<i32, Vec<AttachmentInfo>> = HashMap::new();
+    if message_ids.is_empty() {
+        return Ok(attachments);
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT ma.message_id, ma.hash, ma.file_name, a.mime, a.size, a.width, a.height, \
+         a.thumbnail_mime \
+         FROM message_attachments ma JOIN attachments a ON a.hash = ma.hash \
+         WHERE ma.message_table = ",
+    );
+    builder.push_bind(table).push(" AND ma.message_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in message_ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(") ORDER BY ma.message_id, ma.position");
+
+    for row in builder
+        .build_query_as::<MessageAttachmentRow>()
+        .fetch_all(&pool.db_pool)
+        .await?
+    {
+        attachments
+            .entry(row.message_id)
+            .or_default()
+            .push(row.attachment.with_urls());
+    }
+
+    Ok(attachments)
+}
+
+// Turns the EXIF orientation into rotated pixels
+fn apply_orientation(image: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+fn encode_image(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut encoded = Cursor::new(Vec::new());
+    let result = match format {
+        // JPEG has no alpha channel, so the pixels are converted first
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_to(&mut encoded, ImageOutputFormat::Jpeg(JPEG_QUALITY)),
+        _ => image.write_to(&mut encoded, ImageOutputFormat::Png),
+    };
+    result.map_err(|e| e.to_string())?;
+    Ok(encoded.into_inner())
+}
+
+// Decodes an image upload to record its dimensions and render the previews. Unless asked to
+// keep it, the metadata is stripped from the stored original.
+fn process_image(
+    mime: &str,
+    bytes: Vec<u8>,
+    keep_metadata: bool,
+) -> Result<ProcessedUpload, String> {
+    let format = ImageFormat::from_mime_type(mime).ok_or("Unsupported image type")?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(&bytes), format);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|e| e.to_string())?;
+
+    let orientation = match format {
+        ImageFormat::Jpeg => jpeg_orientation(&bytes).unwrap_or(1),
+        _ => 1,
+    };
+    let image = apply_orientation(image, orientation);
+
+    let stored = if keep_metadata {
+        bytes
+    } else if orientation != 1 {
+        // The orientation tag goes with the rest of the EXIF data, so the pixels are
+        // stored rotated instead
+        encode_image(&image, ImageFormat::Jpeg)?
+    } else {
+        strip_metadata(mime, &bytes).ok_or("Malformed image data")?
+    };
+
+    let (thumbnail_format, thumbnail_mime) = if image.color().has_alpha() {
+        (ImageFormat::Png, "image/png")
+    } else {
+        (ImageFormat::Jpeg, "image/jpeg")
+    };
+    let mut thumbnails = Vec::new();
+    for size in THUMBNAIL_SIZES {
+        // Small images are re-encoded at their own size, never scaled up
+        let thumbnail = if image.width().max(image.height()) > size {
+            image.thumbnail(size, size)
+        } else {
+            image.clone()
+        };
+        thumbnails.push((size, encode_image(&thumbnail, thumbnail_format)?));
+    }
+
+    Ok(ProcessedUpload {
+        bytes: stored,
+        width: Some(image.width() as i32),
+        height: Some(image.height() as i32),
+        thumbnails,
+        thumbnail_mime: Some(thumbnail_mime),
+    })
+}
+
+// Write to a unique temporary name first so readers never see a partial file
+fn write_file(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let partial = path.with_extension(format!("{}.part", uuid::Uuid::new_v4()));
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, path)
+}
+
+// Writes the blob and its previews under its hash unless an identical file is already stored
+fn store_attachment(dir: &Path, hash: &str, upload: &ProcessedUpload) -> std::io::Result<()> {
+    for (size, thumbnail) in &upload.thumbnails {
+        let path = thumbnail_path(dir, hash, *size);
+        if !path.exists() {
+            write_file(&path, thumbnail)?;
+        }
+    }
+
+    let path = attachment_path(dir, hash);
+    if path.exists() {
+        return Ok(());
+    }
+    write_file(&path, &upload.bytes)
+}
+
+async fn save_upload(
+    pool: &AppState,
+    dir: PathBuf,
+    file_name: String,
+    mime: String,
+    bytes: Vec<u8>,
+    keep_metadata: bool,
+) -> Result<AttachmentInfo, HttpResponse> {
+    let upload = if IMAGE_MIME_TYPES.contains(&mime.as_str()) {
+        let image_mime = mime.clone();
+        match web::block(move || process_image(&image_mime, bytes, keep_metadata)).await {
+            Ok(Ok(upload)) => upload,
+            Ok(Err(e)) => {
+                eprintln!("Error processing image attachment: {}", e);
+                return Err(HttpResponse::BadRequest().body("Image could not be decoded"));
+            }
+            Err(e) => {
+                eprintln!("Error running image processing: {}", e);
+                return Err(HttpResponse::InternalServerError().finish());
+            }
+        }
+    } else {
+        ProcessedUpload {
+            bytes,
+            width: None,
+            height: None,
+            thumbnails: Vec::new(),
+            thumbnail_mime: None,
+        }
+    };
+
+    // The address is taken after processing, so it covers what is actually stored
+    let hash = content_hash(&upload.bytes);
+    let size = upload.bytes.len() as i64;
+    let (width, height) = (upload.width, upload.height);
+    let thumbnail_mime = upload.thumbnail_mime.map(str::to_string);
+
+    // The row is locked before the files are checked, a purge of the same file either
+    // finishes first or waits and finds it uploaded again
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        sqlx::query_scalar::<_, String>("SELECT hash FROM attachments WHERE hash = ? FOR UPDATE")
+            .bind(&hash)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let stored = {
+            let hash = hash.clone();
+            web::block(move || store_attachment(&dir, &hash, &upload)).await
+        };
+        match stored {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                eprintln!("Error writing attachment file: {}", e);
+                return Ok(Err(HttpResponse::InternalServerError().finish()));
+            }
+            Err(e) => {
+                eprintln!("Error running attachment storage: {}", e);
+                return Ok(Err(HttpResponse::InternalServerError().finish()));
+            }
+        }
+
+        // Uploading a known file again renews its grace period
+        query(
+            "INSERT INTO attachments (hash, size, mime, width, height, thumbnail_mime) \
+             VALUES (?, ?, ?, ?, ?, ?) \
+             ON DUPLICATE KEY UPDATE uploaded_at = CURRENT_TIMESTAMP",
+        )
+        .bind(&hash)
+        .bind(size)
+        .bind(&mime)
+        .bind(width)
+        .bind(height)
+        .bind(&thumbnail_mime)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok(()))
+    }
+    .await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(response)) => return Err(response),
+        Err(e) => {
+            eprintln!("Error saving attachment: {}", e);
+            return Err(HttpResponse::InternalServerError().finish());
+        }
+    }
+
+    Ok(AttachmentInfo {
+        hash,
+        file_name,
+        mime,
+        size,
+        width,
+        height,
+        thumbnail_mime,
+        url: String::new(),
+        thumbnails: Vec::new(),
+    }
+    .with_urls())
+}
+
+// Handler function to upload message attachments, every multipart field with a file name
+// is stored and the references to pass along with the new message are returned
+#[post("/attachments")]
+pub async fn upload_attachments(
+    pool: web::Data<AppState>,
+    options: web::Query<UploadOptions>,
+    mut payload: Multipart,
+) -> impl Responder {
+    let max_bytes = env_or("ATTACHMENT_MAX_BYTES", DEFAULT_MAX_ATTACHMENT_BYTES);
+    let dir = match media_dir(&pool.tauri_app, "attachments") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Error preparing attachment directory: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let mut uploaded = Vec::new();
+    loop {
+        let mut field = match payload.try_next().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return HttpResponse::BadRequest().body(format!("Invalid upload: {}", e)),
+        };
+
+        let file_name = match field.content_disposition().and_then(|cd| cd.get_filename()) {
+            Some(file_name) => sanitize_file_name(file_name),
+            None => continue, // Plain form fields carry no file
+        };
+        if uploaded.len() == MAX_ATTACHMENTS_PER_MESSAGE {
+            return HttpResponse::BadRequest().body(format!(
+                "At most {} attachments can be uploaded at once",
+                MAX_ATTACHMENTS_PER_MESSAGE
+            ));
+        }
+
+        let mime = field
+            .content_type()
+            .map(|mime| mime.essence_str().to_string())
+            .unwrap_or_default();
+        if !ALLOWED_MIME_TYPES.contains(&mime.as_str()) {
+            return HttpResponse::UnsupportedMediaType()
+                .body(format!("Attachments of type '{}' are not allowed", mime));
+        }
+
+        let mut bytes = Vec::new();
+        loop {
+            match field.try_next().await {
+                Ok(Some(chunk)) => {
+                    if bytes.len() + chunk.len() > max_bytes {
+                        return HttpResponse::PayloadTooLarge().body("Attachment is too large");
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(e) => return HttpResponse::BadRequest().body(format!("Invalid upload: {}", e)),
+            }
+        }
+        if bytes.is_empty() {
+            return HttpResponse::BadRequest().body("Attachment cannot be empty");
+        }
+
+        match save_upload(
+            &pool,
+            dir.clone(),
+            file_name,
+            mime,
+            bytes,
+            options.keep_metadata,
+        )
+        .await
+        {
+            Ok(attachment) => uploaded.push(attachment),
+            Err(response) => return response,
+        }
+    }
+
+    if uploaded.is_empty() {
+        return HttpResponse::BadRequest().body("No file was uploaded");
+    }
+    HttpResponse::Ok().json(uploaded)
+}
+
+// Define the query parameters accepted by the download endpoint
+#[derive(Deserialize)]
+struct DownloadQuery {
+    name: Option<String>,
+}
+
+// Handler function to download an attachment, range requests are answered with partial
+// content so large files can be streamed and resumed
+#[get("/attachments/{hash}")]
+pub async fn download_attachment(
+    req: HttpRequest,
+    pool: web::Data<AppState>,
+    hash: web::Path<String>,
+    params: web::Query<DownloadQuery>,
+) -> impl Responder {
+    let hash = hash.into_inner();
+    if !is_content_hash(&hash) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let mime = match sqlx::query_scalar::<_, String>("SELECT mime FROM attachments WHERE hash = ?")
+        .bind(&hash)
+        .fetch_optional(&pool.db_pool)
+        .await
+    {
+        Ok(Some(mime)) => mime,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving attachment: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let path = match media_dir(&pool.tauri_app, "attachments") {
+        Ok(dir) => attachment_path(&dir, &hash),
+        Err(e) => {
+            eprintln!("Error preparing attachment directory: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let file = match NamedFile::open_async(&path).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error opening attachment file {}: {}", path.display(), e);
+            return HttpResponse::NotFound().finish();
+        }
+    };
+
+    // Images we can decode are shown inline, everything else is offered as a download
+    let disposition = if IMAGE_MIME_TYPES.contains(&mime.as_str()) {
+        DispositionType::Inline
+    } else {
+        DispositionType::Attachment
+    };
+    let file_name = sanitize_file_name(params.name.as_deref().unwrap_or(&hash));
+    let file = file
+        .set_content_type(
+            mime.parse()
+                .unwrap_or(actix_web::mime::APPLICATION_OCTET_STREAM),
+        )
+        .set_content_disposition(ContentDisposition {
+            disposition,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        });
+
+    let mut response = file.into_response(&req);
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=31536000, immutable"),
+    );
+    // The browser must not guess a more dangerous type than the one we declared
+    response.headers_mut().insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    response
+}
+
+// Handler function to serve the preview of an image attachment, the URL is content-addressed
+// so it never changes
+#[get("/attachments/{hash}/thumbnails/{size}")]
+pub async fn get_attachment_thumbnail(
+    req: HttpRequest,
+    pool: web::Data<AppState>,
+    path: web::Path<(String, u32)>,
+) -> impl Responder {
+    let (hash, size) = path.into_inner();
+    if !is_content_hash(&hash) || !THUMBNAIL_SIZES.contains(&size) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let thumbnail_mime = match sqlx::query_scalar::<_, Option<String>>(
+        "SELECT thumbnail_mime FROM attachments WHERE hash = ?",
+    )
+    .bind(&hash)
+    .fetch_optional(&pool.db_pool)
+    .await
+    {
+        Ok(Some(Some(thumbnail_mime))) => thumbnail_mime,
+        Ok(_) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving attachment: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let path = match media_dir(&pool.tauri_app, "attachments") {
+        Ok(dir) => thumbnail_path(&dir, &hash, size),
+        Err(e) => {
+            eprintln!("Error preparing attachment directory: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let file = match NamedFile::open_async(&path).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error opening thumbnail file {}: {}", path.display(), e);
+            return HttpResponse::NotFound().finish();
+        }
+    };
+
+    let mut response = file
+        .set_content_type(
+            thumbnail_mime
+                .parse()
+                .unwrap_or(actix_web::mime::APPLICATION_OCTET_STREAM),
+        )
+        .into_response(&req);
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=31536000, immutable"),
+    );
+    response.headers_mut().insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    response
+}
+
+// Condition on `a.hash` that holds while no message, no scheduled message waiting to be sent
+// and no draft uses the file
+const UNREFERENCED_ATTACHMENT: &str =
+    "NOT EXISTS (SELECT 1 FROM message_attachments ma WHERE ma.hash = a.hash) \
+     AND NOT EXISTS (SELECT 1 FROM scheduled_messages s WHERE s.status IN ('pending', 'sending') \
+         AND JSON_SEARCH(s.attachments, 'one', a.hash, NULL, '$[*].hash') IS NOT NULL) \
+     AND NOT EXISTS (SELECT 1 FROM message_drafts d \
+         WHERE JSON_SEARCH(d.attachments, 'one', a.hash, NULL, '$[*].hash') IS NOT NULL)";
+
+// Function to delete the given files and their thumbnails, skipping every file that is still
+// referenced. Used by the garbage collection and when disappearing messages are swept.
+pub async fn purge_attachments(
+    pool: &AppState,
+    hashes: &[String],
+) -> Result<GarbageReport, sqlx::Error> {
+    let dir = media_dir(&pool.tauri_app, "attachments").ok();
+    let select_query = format!(
+        "SELECT a.size, a.thumbnail_mime FROM attachments a WHERE a.hash = ? AND {} FOR UPDATE",
+        UNREFERENCED_ATTACHMENT
+    );
+    let mut removed = 0;
+    let mut freed_bytes = 0;
+    for hash in hashes {
+        // Checked inside the delete, a message may have linked the file in the meantime
+        let mut tx = pool.db_pool.begin().await?;
+        let stored = sqlx::query_as::<_, (i64, Option<String>)>(&select_query)
+            .bind(hash)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let (size, thumbnail_mime) = match stored {
+            Some(stored) => stored,
+            None => continue,
+        };
+        query("DELETE FROM attachments WHERE hash = ?")
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+
+        // The files go while the row is still locked, an upload of the same file waits for
+        // the commit and then writes them again
+        if let Some(dir) = &dir {
+            let mut paths = vec![attachment_path(dir, hash)];
+            if thumbnail_mime.is_some() {
+                paths.extend(
+                    THUMBNAIL_SIZES
+                        .iter()
+                        .map(|size| thumbnail_path(dir, hash, *size)),
+                );
+            }
+            for path in paths {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    eprintln!("Error removing attachment file {}: {}", path.display(), e);
+                }
+            }
+        }
+        tx.commit().await?;
+        removed += 1;
+        freed_bytes += size;
+    }
+
+    Ok(GarbageReport {
+        removed,
+        freed_bytes,
+    })
+}
+
+// Function to delete every stored file that no message references anymore. Files uploaded
+// within the grace period are kept because their message may not have been sent yet, and
+// so are the files of pending scheduled messages and drafts.
+pub async fn collect_attachment_garbage(pool: &AppState) -> Result<GarbageReport, sqlx::Error> {
+    let grace = env_or(
+        "ATTACHMENT_GC_GRACE_SECONDS",
+        DEFAULT_ATTACHMENT_GC_GRACE_SECONDS,
+    );
+    let query_str = format!(
+        "SELECT a.hash FROM attachments a \
+         WHERE a.uploaded_at < NOW() - INTERVAL ? SECOND AND {}",
+        UNREFERENCED_ATTACHMENT
+    );
+    let orphans = sqlx::query_scalar::<_, String>(&query_str)
+        .bind(grace)
+        .fetch_all(&pool.db_pool)
+        .await?;
+
+    purge_attachments(pool, &orphans).await
+}
+
+// Handler function to run the attachment garbage collection right away
+#[post("/attachments/gc")]
+pub async fn collect_attachment_garbage_handler(pool: web::Data<AppState>) -> impl Responder {
+    match collect_attachment_garbage(&pool).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            eprintln!("Error collecting unreferenced attachments: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use super::message_get_set_handlers::{NewMessage, EXPIRY_FOR_NEW_MESSAGE, NOT_EXPIRED};
+use crate::server::config::env_or;
+use crate::server::AppState;
+use actix_web::{get, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySqlConnection};
+
+// How long after a message an identical one from the same sender is still folded into it,
+// zero turns collapsing off everywhere
+const DEFAULT_COLLAPSE_WINDOW_SECONDS: i64 = 2 * 60;
+
+// Define a struct to represent the collapse setting of a conversation
+#[derive(Debug, Serialize, Deserialize)]
+struct CollapseSetting {
+    enabled: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct CollapseSettingEvent {
+    table: &'static str,
+    connected: String,
+    enabled: bool,
+}
+
+// Define a struct to represent a repeat that was folded into an earlier message
+#[derive(Clone, Serialize)]
+pub struct CollapsedMessage {
+    pub id: i32,
+    pub connected: String,
+    pub count: i32,
+}
+
+#[derive(FromRow)]
+struct LastMessage {
+    id: i32,
+    sender: String,
+    receiver: String,
+    content: String,
+    timestamp: DateTime<Utc>,
+    close_one_point: Option<String>,
+    counter: Option<i32>,
+    deleted_at: Option<DateTime<Utc>>,
+    reply_to: Option<i32>,
+    forwarded_from_id: Option<i32>,
+    kind: String,
+    format: String,
+}
+
+async fn collapse_enabled(
+    conn: &mut MySqlConnection,
+    table: &str,
+    connected: &str,
+) -> Result<bool, sqlx::Error> {
+    let enabled = sqlx::query_scalar::<_, bool>(
+        "SELECT collapse_repeats FROM conversation_settings WHERE message_table = ? AND connected = ?",
+    )
+    .bind(table)
+    .bind(connected)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(enabled.unwrap_or(true))
+}
+
+// Function to fold a new message into the last message of its conversation when it repeats
+// it: same sender, receiver, content and label, sent within the collapse window. The counter
+// of the earlier message goes up and its timestamp moves to now, it counts as unread again.
+// Replies, forwarded copies and messages with attachments are always stored on their own.
+pub async fn collapse_repeat(
+    conn: &mut MySqlConnection,
+    table: &str,
+    new_message: &NewMessage,
+) -> Result<Option<CollapsedMessage>, sqlx::Error> {
+    let window = env_or(
+        "MESSAGE_COLLAPSE_WINDOW_SECONDS",
+        DEFAULT_COLLAPSE_WINDOW_SECONDS,
+    );
+    if window <= 0 || new_message.reply_to.is_some() || !new_message.attachments.is_empty() {
+        return Ok(None);
+    }
+    if !collapse_enabled(conn, table, &new_message.connected).await? {
+        return Ok(None);
+    }
+
+    let select_query = format!(
+        "SELECT id, sender, receiver, content, timestamp, close_one_point, counter, \
+         deleted_at, reply_to, forwarded_from_id, kind, format FROM {} \
+         WHERE connected = ? AND {} ORDER BY timestamp DESC, id DESC LIMIT 1 FOR UPDATE",
+        table, NOT_EXPIRED
+    );
+    let last = match sqlx::query_as::<_, LastMessage>(&select_query)
+        .bind(&new_message.connected)
+        .fetch_optional(&mut *conn)
+        .await?
+    {
+        Some(last) => last,
+        None => return Ok(None),
+    };
+
+    let repeats = last.sender == new_message.sender
+        && last.receiver == new_message.receiver
+        && last.content == new_message.content
+        && last.format == new_message.format.as_str()
+        && last.close_one_point == new_message.close_one_point
+        && last.kind == "text"
+        && last.reply_to.is_none()
+        && last.forwarded_from_id.is_none()
+        && last.deleted_at.is_none()
+        && Utc::now()
+            .signed_duration_since(last.timestamp)
+            .num_seconds()
+            <= window;
+    if !repeats {
+        return Ok(None);
+    }
+
+    // Someone deleted the earlier copy for themselves, bumping it would bring it back
+    let hides = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM message_hides WHERE message_table = ? AND message_id = ?",
+    )
+    .bind(table)
+    .bind(last.id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if hides > 0 {
+        return Ok(None);
+    }
+
+    let attachments = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM message_attachments WHERE message_table = ? AND message_id = ?",
+    )
+    .bind(table)
+    .bind(last.id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if attachments > 0 {
+        return Ok(None);
+    }
+
+    let update_query = format!(
+        "UPDATE {} SET counter = COALESCE(counter, 1) + 1, timestamp = CURRENT_TIMESTAMP, \
+         delivered_at = NULL, read_at = NULL, expires_at = {} WHERE id = ?",
+        table, EXPIRY_FOR_NEW_MESSAGE
+    );
+    query(&update_query)
+        .bind(table)
+        .bind(&new_message.connected)
+        .bind(last.id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(Some(CollapsedMessage {
+        id: last.id,
+        connected: new_message.connected.clone(),
+        count: last.counter.unwrap_or(1) + 1,
+    }))
+}
+
+async fn get_collapse(pool: &AppState, table: &str, connected: &str) -> HttpResponse {
+    let result = match pool.db_pool.acquire().await {
+        Ok(mut conn) => collapse_enabled(&mut conn, table, connected).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(enabled) => HttpResponse::Ok().json(CollapseSetting { enabled }),
+        Err(e) => {
+            eprintln!("Error retrieving conversation settings: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Messages collapsed before the change stay collapsed
+async fn set_collapse(
+    pool: &AppState,
+    table: &'static str,
+    connected: &str,
+    setting: &CollapseSetting,
+) -> HttpResponse {
+    let result = query(
+        "INSERT INTO conversation_settings (message_table, connected, collapse_repeats) \
+         VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE collapse_repeats = VALUES(collapse_repeats)",
+    )
+    .bind(table)
+    .bind(connected)
+    .bind(setting.enabled)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(_) => {
+            pool.emit_event(
+                "conversation-collapse-changed",
+                CollapseSettingEvent {
+                    table,
+                    connected: connected.to_string(),
+                    enabled: setting.enabled,
+                },
+            );
+            HttpResponse::Ok().json(setting)
+        }
+        Err(e) => {
+            eprintln!("Error updating conversation settings: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to tell whether repeated messages are collapsed in a 'my-client' conversation
+#[get("/my/conversations/{connected}/collapse")]
+pub async fn get_conversation_collapse_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    get_collapse(&pool, "messages_send_to_my_client", &connected).await
+}
+
+// Handler function to tell whether repeated messages are collapsed in an 'other-client' conversation
+#[get("/other/conversations/{connected}/collapse")]
+pub async fn get_conversation_collapse_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    get_collapse(&pool, "messages_send_to_other_client", &connected).await
+}
+
+// Handler function to turn collapsing of repeated messages on or off in a 'my-client' conversation
+#[put("/my/conversations/{connected}/collapse")]
+pub async fn set_conversation_collapse_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    setting: web::Json<CollapseSetting>,
+) -> impl Responder {
+    set_collapse(&pool, "messages_send_to_my_client", &connected, &setting).await
+}
+
+// Handler function to turn collapsing of repeated messages on or off in an 'other-client' conversation
+#[put("/other/conversations/{connected}/collapse")]
+pub async fn set_conversation_collapse_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    setting: web::Json<CollapseSetting>,
+) -> impl Responder {
+    set_collapse(&pool, "messages_send_to_other_client", &connected, &setting).await
+}
@@ -0,0 +1,347 @@
+use super::message_contact_handlers::record_contact_edit;
+use crate::server::storage::{content_hash, is_content_hash, media_dir};
+use crate::server::AppState;
+use actix_web::http::header;
+use actix_web::{delete, get, put, web, HttpRequest, HttpResponse, Responder};
+use image::imageops::FilterType;
+use image::ImageFormat;
+use serde::Serialize;
+use sqlx::{query, MySqlConnection};
+use std::path::{Path, PathBuf};
+
+// Square sizes every avatar is rendered to, in pixels
+const AVATAR_SIZES: [u32; 4] = [32, 64, 128, 256];
+const DEFAULT_AVATAR_SIZE: u32 = 128;
+const MAX_AVATAR_BYTES: usize = 5 * 1024 * 1024;
+
+#[derive(Serialize)]
+struct AvatarResponse {
+    hash: String,
+    url: String,
+    sizes: &'static [u32],
+}
+
+// Function to build the URL the views use to load an avatar
+pub fn avatar_url(hash: &str) -> String {
+    format!("/message/avatars/{}/{}", hash, DEFAULT_AVATAR_SIZE)
+}
+
+fn avatar_path(dir: &Path, hash: &str, size: u32) -> PathBuf {
+    dir.join(format!("{}-{}.png", hash, size))
+}
+
+// Decodes the upload and writes every standard size, unless this image is already stored
+fn store_avatar(dir: &Path, hash: &str, bytes: &[u8]) -> Result<(), String> {
+    if AVATAR_SIZES
+        .iter()
+        .all(|size| avatar_path(dir, hash, *size).exists())
+    {
+        return Ok(());
+    }
+
+    let image = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    for size in AVATAR_SIZES {
+        let resized = image.resize_to_fill(size, size, FilterType::Lanczos3);
+        resized
+            .save_with_format(avatar_path(dir, hash, size), ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn remove_avatar_files(dir: &Path, hash: &str) {
+    for size in AVATAR_SIZES {
+        let path = avatar_path(dir, hash, size);
+        if let Err(e) = std::fs::remove_file(&path) {
+            eprintln!("Error removing avatar file {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Counts the contacts using an image, plus merges that can still be undone back onto it.
+// The contacts using it stay locked until the transaction ends
+async fn avatar_references(conn: &mut MySqlConnection, hash: &str) -> Result<i64, sqlx::Error> {
+    let contacts = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM contact_avatars WHERE hash = ? FOR UPDATE",
+    )
+    .bind(hash)
+    .fetch_one(&mut *conn)
+    .await?;
+    let merges = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM contact_merge_log WHERE merged_avatar = ? AND undone_at IS NULL",
+    )
+    .bind(hash)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(contacts + merges)
+}
+
+// Removes the files of an image after the transaction that unlinked it committed, unless it
+// was linked again since. The references stay locked until the files are gone, an upload of
+// the same image waits for that and then writes them again
+async fn remove_unused_avatar(pool: &AppState, hash: String) {
+    let dir = match media_dir(&pool.tauri_app, "avatars") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Error preparing avatar directory: {}", e);
+            return;
+        }
+    };
+
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        if avatar_references(&mut tx, &hash).await? == 0 {
+            if let Err(e) = web::block(move || remove_avatar_files(&dir, &hash)).await {
+                eprintln!("Error running avatar removal: {}", e);
+            }
+        }
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = result {
+        eprintln!("Error checking avatar references: {}", e);
+    }
+}
+
+async fn contact_exists(pool: &AppState, table: &str, id: &str) -> Result<bool, sqlx::Error> {
+    let query_str = format!("SELECT COUNT(*) FROM {} WHERE id = ?", table);
+    sqlx::query_scalar::<_, i64>(&query_str)
+        .bind(id)
+        .fetch_one(&pool.db_pool)
+        .await
+        .map(|count| count > 0)
+}
+
+async fn upload_avatar(pool: &AppState, table: &str, id: &str, body: web::Bytes) -> HttpResponse {
+    if body.is_empty() {
+        return HttpResponse::BadRequest().body("Avatar image cannot be empty");
+    }
+    if body.len() > MAX_AVATAR_BYTES {
+        return HttpResponse::PayloadTooLarge().body("Avatar image is too large");
+    }
+
+    match contact_exists(pool, table, id).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving contact from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    let dir = match media_dir(&pool.tauri_app, "avatars") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Error preparing avatar directory: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    // Swap the link and find out whether the replaced image is still used by anyone
+    let hash = content_hash(&body);
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        let previous = sqlx::query_scalar::<_, String>(
+            "SELECT hash FROM contact_avatars WHERE people_table = ? AND contact_id = ? FOR UPDATE",
+        )
+        .bind(table)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // The image is locked before its files are checked, a concurrent removal of the same
+        // image either finishes first or sees this link and keeps them
+        avatar_references(&mut tx, &hash).await?;
+        let stored = {
+            let hash = hash.clone();
+            web::block(move || store_avatar(&dir, &hash, &body)).await
+        };
+        match stored {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                eprintln!("Error processing avatar image: {}", e);
+                return Ok(Err(HttpResponse::BadRequest()
+                    .body("Avatar must be a PNG, JPEG, GIF or WebP image")));
+            }
+            Err(e) => {
+                eprintln!("Error running avatar processing: {}", e);
+                return Ok(Err(HttpResponse::InternalServerError().finish()));
+            }
+        }
+
+        query(
+            "INSERT INTO contact_avatars (people_table, contact_id, hash) VALUES (?, ?, ?)
+             ON DUPLICATE KEY UPDATE hash = VALUES(hash), updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(table)
+        .bind(id)
+        .bind(&hash)
+        .execute(&mut *tx)
+        .await?;
+        let details = serde_json::json!({ "hash": hash });
+        record_contact_edit(&mut *tx, Some(table), id, "avatar_updated", details).await?;
+
+        let unused = match previous {
+            Some(previous) if previous != hash => {
+                if avatar_references(&mut tx, &previous).await? == 0 {
+                    Some(previous)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok(unused))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(unused)) => {
+            if let Some(previous) = unused {
+                remove_unused_avatar(pool, previous).await;
+            }
+            HttpResponse::Ok().json(AvatarResponse {
+                url: avatar_url(&hash),
+                hash,
+                sizes: &AVATAR_SIZES,
+            })
+        }
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error saving contact avatar: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Removes the avatar link and deletes the files once no contact uses the image anymore
+async fn delete_avatar(pool: &AppState, table: &str, id: &str) -> HttpResponse {
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        let hash = sqlx::query_scalar::<_, String>(
+            "SELECT hash FROM contact_avatars WHERE people_table = ? AND contact_id = ? FOR UPDATE",
+        )
+        .bind(table)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let hash = match hash {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+
+        query("DELETE FROM contact_avatars WHERE people_table = ? AND contact_id = ?")
+            .bind(table)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let details = serde_json::json!({ "hash": hash });
+        record_contact_edit(&mut *tx, Some(table), id, "avatar_removed", details).await?;
+
+        let unused = avatar_references(&mut tx, &hash).await? == 0;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Some((hash, unused)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((hash, unused))) => {
+            if unused {
+                remove_unused_avatar(pool, hash).await;
+            }
+            HttpResponse::Ok().finish()
+        }
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error deleting contact avatar: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to upload the avatar of a connected person, the body is the raw image
+#[put("/my/people/{id}/avatar")]
+pub async fn upload_avatar_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Bytes,
+) -> impl Responder {
+    upload_avatar(&pool, "my_server_people", &id, body).await
+}
+
+// Handler function to upload the avatar of a connecting person, the body is the raw image
+#[put("/other/people/{id}/avatar")]
+pub async fn upload_avatar_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<String>,
+    body: web::Bytes,
+) -> impl Responder {
+    upload_avatar(&pool, "other_server_people", &id, body).await
+}
+
+#[delete("/my/people/{id}/avatar")]
+pub async fn delete_avatar_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<String>,
+) -> impl Responder {
+    delete_avatar(&pool, "my_server_people", &id).await
+}
+
+#[delete("/other/people/{id}/avatar")]
+pub async fn delete_avatar_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<String>,
+) -> impl Responder {
+    delete_avatar(&pool, "other_server_people", &id).await
+}
+
+// Handler function to serve an avatar, the URL is content-addressed so it never changes
+#[get("/avatars/{hash}/{size}")]
+pub async fn get_avatar(
+    req: HttpRequest,
+    pool: web::Data<AppState>,
+    path: web::Path<(String, u32)>,
+) -> impl Responder {
+    let (hash, size) = path.into_inner();
+    if !is_content_hash(&hash) || !AVATAR_SIZES.contains(&size) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let etag = format!("\"{}-{}\"", hash, size);
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish();
+    }
+
+    let dir = match media_dir(&pool.tauri_app, "avatars") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Error preparing avatar directory: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    match web::block(move || std::fs::read(avatar_path(&dir, &hash, size))).await {
+        Ok(Ok(bytes)) => HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+            .insert_header((header::ETAG, etag))
+            .body(bytes),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => HttpResponse::NotFound().finish(),
+        Ok(Err(e)) => {
+            eprintln!("Error reading avatar file: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+        Err(e) => {
+            eprintln!("Error running avatar read: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use super::message_attachment_handlers::AttachmentRef;
+use super::message_contact_handlers::record_contact_edit;
+use super::message_get_set_handlers::{
+    announce_message, check_new_message, store_message, MessageFormat, NewMessage, SendError,
+};
+use super::message_rule_handlers::apply_message_rules;
+use crate::server::AppState;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+// Define the ContactGroup struct to use with database queries and API responses
+#[derive(Debug, Serialize, FromRow)]
+struct ContactGroup {
+    id: i32,
+    name: String,
+    member_count: i64,
+}
+
+// Define a struct to capture the group name from create/rename requests
+#[derive(Deserialize)]
+struct GroupName {
+    name: String,
+}
+
+// Define a struct to capture the contacts assigned to a group
+#[derive(Deserialize)]
+struct GroupMembers {
+    contact_ids: Vec<String>,
+}
+
+// Define a struct to capture a message addressed to every member of a group
+#[derive(Deserialize)]
+struct GroupMessage {
+    sender: String,
+    content: String,
+    close_one_point: Option<String>,
+    #[serde(default)]
+    attachments: Vec<AttachmentRef>, // Linked to every copy, the files are not duplicated
+    #[serde(default)]
+    format: MessageFormat,
+}
+
+#[derive(Serialize)]
+struct GroupMessageResult {
+    delivered: usize,
+}
+
+// Define a struct to represent the copy of a group message stored in one conversation
+#[derive(Clone, Serialize)]
+struct GroupMessageCopy {
+    connected: String,
+    message_id: i32,
+}
+
+#[derive(Clone, Serialize)]
+struct GroupMessageEvent {
+    table: &'static str,
+    group_id: i32,
+    copies: Vec<GroupMessageCopy>,
+}
+
+// Helper function to validate a group name before it is stored
+fn validate_group_name(name: &str) -> Result<&str, &'static str> {
+    let name = name.trim();
+    if name.is_empty() {
+        Err("Group name cannot be empty")
+    } else if name.len() > 255 {
+        Err("Group name is too long")
+    } else {
+        Ok(name)
+    }
+}
+
+// Handler function to list every group with its member count
+#[get("/groups")]
+pub async fn get_contact_groups(pool: web::Data<AppState>) -> impl Responder {
+    let query_str = "
+        SELECT g.id, g.name, COUNT(m.contact_id) AS member_count
+        FROM contact_groups g
+        LEFT JOIN contact_group_members m ON m.group_id = g.id
+        GROUP BY g.id, g.name
+        ORDER BY g.name
+    ";
+
+    match query_as::<_, ContactGroup>(query_str)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(groups) => HttpResponse::Ok().json(groups),
+        Err(e) => {
+            eprintln!("Error retrieving contact groups: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to create a new group
+#[post("/groups/")]
+pub async fn create_contact_group(
+    pool: web::Data<AppState>,
+    group: web::Json<GroupName>,
+) -> impl Responder {
+    let name = match validate_group_name(&group.name) {
+        Ok(name) => name,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+
+    match query("INSERT INTO contact_groups (name) VALUES (?)")
+        .bind(name)
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(result) => HttpResponse::Ok().json(ContactGroup {
+            id: result.last_insert_id() as i32,
+            name: name.to_string(),
+            member_count: 0,
+        }),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            HttpResponse::Conflict().body("A group with this name already exists")
+        }
+        Err(e) => {
+            eprintln!("Error creating contact group: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to rename a group
+#[put("/groups/{group_id}")]
+pub async fn rename_contact_group(
+    pool: web::Data<AppState>,
+    group_id: web::Path<i32>,
+    group: web::Json<GroupName>,
+) -> impl Responder {
+    let name = match validate_group_name(&group.name) {
+        Ok(name) => name,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+
+    match query("UPDATE contact_groups SET name = ? WHERE id = ?")
+        .bind(name)
+        .bind(group_id.into_inner())
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            HttpResponse::Conflict().body("A group with this name already exists")
+        }
+        Err(e) => {
+            eprintln!("Error renaming contact group: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to delete a group, memberships are removed by the foreign key cascade
+#[delete("/groups/{group_id}")]
+pub async fn delete_contact_group(
+    pool: web::Data<AppState>,
+    group_id: web::Path<i32>,
+) -> impl Responder {
+    match query("DELETE FROM contact_groups WHERE id = ?")
+        .bind(group_id.into_inner())
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            eprintln!("Error deleting contact group: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list the contact ids that belong to a group
+#[get("/groups/{group_id}/members")]
+pub async fn get_contact_group_members(
+    pool: web::Data<AppState>,
+    group_id: web::Path<i32>,
+) -> impl Responder {
+    match fetch_group_member_ids(&pool, group_id.into_inner()).await {
+        Ok(members) => HttpResponse::Ok().json(members),
+        Err(e) => {
+            eprintln!("Error retrieving contact group members: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to assign contacts to a group, already assigned contacts are ignored
+#[post("/groups/{group_id}/members/")]
+pub async fn add_contact_group_members(
+    pool: web::Data<AppState>,
+    group_id: web::Path<i32>,
+    members: web::Json<GroupMembers>,
+) -> impl Responder {
+    let group_id = group_id.into_inner();
+
+    // INSERT IGNORE also silences foreign key errors, so check the group explicitly
+    match sqlx::query_scalar::<_, i32>("SELECT id FROM contact_groups WHERE id = ?")
+        .bind(group_id)
+        .fetch_optional(&pool.db_pool)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving contact group: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    let mut tx = match pool.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("Error starting transaction: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    for contact_id in &members.contact_ids {
+        let result =
+            query("INSERT IGNORE INTO contact_group_members (group_id, contact_id) VALUES (?, ?)")
+                .bind(group_id)
+                .bind(contact_id)
+                .execute(&mut *tx)
+                .await;
+
+        match result {
+            // Contacts that were already in the group are not logged again
+            Ok(result) if result.rows_affected() == 0 => {}
+            Ok(_) => {
+                let details = serde_json::json!({ "group_id": group_id });
+                let logged =
+                    record_contact_edit(&mut *tx, None, contact_id, "group_added", details).await;
+                if let Err(e) = logged {
+                    eprintln!("Error recording contact edit: {}", e);
+                    return HttpResponse::InternalServerError().finish();
+                }
+            }
+            Err(e) => {
+                eprintln!("Error assigning contact to group: {}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    match tx.commit().await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            eprintln!("Error committing group assignment: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to remove a single contact from a group
+#[delete("/groups/{group_id}/members/{contact_id}")]
+pub async fn remove_contact_group_member(
+    pool: web::Data<AppState>,
+    path: web::Path<(i32, String)>,
+) -> impl Responder {
+    let (group_id, contact_id) = path.into_inner();
+
+    match query("DELETE FROM contact_group_members WHERE group_id = ? AND contact_id = ?")
+        .bind(group_id)
+        .bind(&contact_id)
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => {
+            let details = serde_json::json!({ "group_id": group_id });
+            if let Err(e) =
+                record_contact_edit(&pool.db_pool, None, &contact_id, "group_removed", details)
+                    .await
+            {
+                eprintln!("Error recording contact edit: {}", e);
+            }
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("Error removing contact from group: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to send a message to every member of a group through 'my-client'
+#[post("/my/groups/{group_id}/send/")]
+pub async fn send_group_message_my_client(
+    pool: web::Data<AppState>,
+    group_id: web::Path<i32>,
+    message: web::Json<GroupMessage>,
+) -> impl Responder {
+    send_group_message(
+        &pool,
+        "messages_send_to_my_client",
+        group_id.into_inner(),
+        &message,
+    )
+    .await
+}
+
+// Handler function to send a message to every member of a group through 'other-client'
+#[post("/other/groups/{group_id}/send/")]
+pub async fn send_group_message_other_client(
+    pool: web::Data<AppState>,
+    group_id: web::Path<i32>,
+    message: web::Json<GroupMessage>,
+) -> impl Responder {
+    send_group_message(
+        &pool,
+        "messages_send_to_other_client",
+        group_id.into_inner(),
+        &message,
+    )
+    .await
+}
+
+async fn fetch_group_member_ids(
+    pool: &AppState,
+    group_id: i32,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT contact_id FROM contact_group_members WHERE group_id = ? ORDER BY contact_id",
+    )
+    .bind(group_id)
+    .fetch_all(&pool.db_pool)
+    .await
+}
+
+// Fans a message out to the conversation of every group member inside a single transaction.
+// Every copy is checked and stored like a message sent to that member on its own.
+async fn send_group_message(
+    pool: &AppState,
+    table: &'static str,
+    group_id: i32,
+    message: &GroupMessage,
+) -> HttpResponse {
+    let members = match fetch_group_member_ids(pool, group_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            eprintln!("Error retrieving contact group members: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    if members.is_empty() {
+        return HttpResponse::BadRequest().body("Group has no members");
+    }
+
+    let mut copies = Vec::with_capacity(members.len());
+    for member in members {
+        let new_message = NewMessage {
+            sender: message.sender.clone(),
+            receiver: member.clone(),
+            content: message.content.clone(),
+            close_one_point: message.close_one_point.clone(),
+            connected: member,
+            reply_to: None,
+            attachments: message.attachments.clone(),
+            send_at: None,
+            format: message.format,
+        };
+        if let Err(e) = check_new_message(pool, table, &new_message).await {
+            return e.to_response(table);
+        }
+        copies.push(new_message);
+    }
+
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        let mut stored = Vec::with_capacity(copies.len());
+        for new_message in &copies {
+            stored.push(store_message(&mut tx, table, new_message, None).await?);
+        }
+        tx.commit().await?;
+        Ok::<_, SendError>(stored)
+    }
+    .await;
+
+    let stored = match result {
+        Ok(stored) => stored,
+        Err(e) => return e.to_response(table),
+    };
+
+    let mut event = GroupMessageEvent {
+        table,
+        group_id,
+        copies: Vec::with_capacity(stored.len()),
+    };
+    for (new_message, stored) in copies.iter().zip(&stored) {
+        announce_message(pool, table, new_message, stored).await;
+        if stored.collapsed.is_none() {
+            apply_message_rules(pool, table, stored.id, new_message).await;
+        }
+        event.copies.push(GroupMessageCopy {
+            connected: new_message.connected.clone(),
+            message_id: stored.id,
+        });
+    }
+    pool.emit_event("group-message-sent", event);
+
+    HttpResponse::Ok().json(GroupMessageResult {
+        delivered: stored.len(),
+    })
+}
@@ -1,7 +1,31 @@
+use super::message_contact_avatar_handlers::avatar_url;
 use crate::server::AppState;
 use actix_web::{get, post, web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
-use sqlx::{query_as, FromRow};
+use sqlx::{FromRow, MySql, QueryBuilder};
+
+// Function to append an entry to the contact edit log that feeds the activity timeline
+pub async fn record_contact_edit<'c, E>(
+    executor: E,
+    people_table: Option<&str>,
+    contact_id: &str,
+    edit_type: &str,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = MySql>,
+{
+    sqlx::query(
+        "INSERT INTO contact_edits (people_table, contact_id, edit_type, details) VALUES (?, ?, ?, ?)",
+    )
+    .bind(people_table)
+    .bind(contact_id)
+    .bind(edit_type)
+    .bind(details.to_string())
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
 
 #[derive(Deserialize)]
 struct NewContact {
@@ -36,7 +60,21 @@ async fn add_contact_my_client(
         .await;
 
     match result {
-        Ok(_) => HttpResponse::Ok().finish(),
+        Ok(_) => {
+            let details = serde_json::json!({ "nick": new_contact.nick });
+            if let Err(e) = record_contact_edit(
+                &pool.db_pool,
+                Some("other_server_people"),
+                &new_contact.id,
+                "created",
+                details,
+            )
+            .await
+            {
+                eprintln!("Error recording contact edit: {}", e);
+            }
+            HttpResponse::Ok().finish()
+        }
         Err(e) => {
             eprintln!("Error adding contact: {}", e);
             HttpResponse::InternalServerError().finish()
@@ -67,7 +105,21 @@ async fn add_contact_other_client(
         .await;
 
     match result {
-        Ok(_) => HttpResponse::Ok().finish(),
+        Ok(_) => {
+            let details = serde_json::json!({ "nick": new_contact.nick });
+            if let Err(e) = record_contact_edit(
+                &pool.db_pool,
+                Some("my_server_people"),
+                &new_contact.id,
+                "created",
+                details,
+            )
+            .await
+            {
+                eprintln!("Error recording contact edit: {}", e);
+            }
+            HttpResponse::Ok().finish()
+        }
         Err(e) => {
             eprintln!("Error adding contact: {}", e);
             HttpResponse::InternalServerError().finish()
@@ -83,36 +135,197 @@ struct ProcessedPerson {
     location: Option<String>,
     occupation: Option<String>,
     extra_info: Option<String>,
+    avatar_hash: Option<String>,
+    #[sqlx(skip)]
+    avatar_url: Option<String>,
 }
 
-// Handler function to get connected people
-#[get("/my/people")]
-pub async fn get_my_server_people_handler(pool: web::Data<AppState>) -> impl Responder {
-    let query_str = "SELECT * FROM my_server_people";
-    match query_as::<_, ProcessedPerson>(query_str)
-        .fetch_all(&pool.db_pool)
+// Columns the people listings can be sorted by
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PeopleSortField {
+    #[default]
+    Id,
+    Nick,
+    Age,
+    Location,
+    Occupation,
+}
+
+impl PeopleSortField {
+    fn column(self) -> &'static str {
+        match self {
+            PeopleSortField::Id => "id",
+            PeopleSortField::Nick => "nick",
+            PeopleSortField::Age => "age",
+            PeopleSortField::Location => "location",
+            PeopleSortField::Occupation => "occupation",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn keyword(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+const DEFAULT_PEOPLE_LIMIT: u32 = 100;
+const MAX_PEOPLE_LIMIT: u32 = 500;
+
+// Define the query parameters accepted by the people listings
+#[derive(Deserialize)]
+struct PeopleFilter {
+    group: Option<String>, // Only return contacts that belong to this group
+    q: Option<String>,     // Substring searched in id, nick, occupation and location
+    min_age: Option<i32>,
+    max_age: Option<i32>,
+    #[serde(default)]
+    sort: PeopleSortField,
+    #[serde(default)]
+    order: SortOrder,
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+// Escapes the LIKE wildcards so the search term is matched literally
+fn like_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// Appends the WHERE clause shared by the listing and the total count queries
+fn push_people_conditions(builder: &mut QueryBuilder<'_, MySql>, filter: &PeopleFilter) {
+    builder.push(" WHERE 1 = 1");
+
+    if let Some(group) = &filter.group {
+        builder
+            .push(
+                " AND id IN (SELECT m.contact_id FROM contact_group_members m \
+                 JOIN contact_groups g ON g.id = m.group_id WHERE g.name = ",
+            )
+            .push_bind(group.clone())
+            .push(")");
+    }
+
+    if let Some(term) = filter.q.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        let pattern = like_pattern(term);
+        builder.push(" AND (");
+        for (i, column) in ["id", "nick", "occupation", "location"].iter().enumerate() {
+            if i > 0 {
+                builder.push(" OR ");
+            }
+            builder
+                .push(*column)
+                .push(" LIKE ")
+                .push_bind(pattern.clone());
+        }
+        builder.push(")");
+    }
+
+    if let Some(min_age) = filter.min_age {
+        builder.push(" AND age >= ").push_bind(min_age);
+    }
+    if let Some(max_age) = filter.max_age {
+        builder.push(" AND age <= ").push_bind(max_age);
+    }
+}
+
+// Runs a filtered, sorted and paginated listing over one of the people tables
+async fn fetch_people(pool: &AppState, table: &str, filter: &PeopleFilter) -> HttpResponse {
+    if let (Some(min_age), Some(max_age)) = (filter.min_age, filter.max_age) {
+        if min_age > max_age {
+            return HttpResponse::BadRequest().body("min_age cannot be greater than max_age");
+        }
+    }
+
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PEOPLE_LIMIT)
+        .clamp(1, MAX_PEOPLE_LIMIT);
+    let offset = filter.offset.unwrap_or(0);
+
+    let mut count_builder = QueryBuilder::<MySql>::new(format!("SELECT COUNT(*) FROM {}", table));
+    push_people_conditions(&mut count_builder, filter);
+    let total: i64 = match count_builder
+        .build_query_scalar::<i64>()
+        .fetch_one(&pool.db_pool)
         .await
     {
-        Ok(people) => HttpResponse::Ok().json(people),
+        Ok(total) => total,
         Err(e) => {
-            eprintln!("Error retrieving connected people: {}", e);
-            HttpResponse::InternalServerError().finish()
+            eprintln!("Error counting people in '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
         }
-    }
-}
+    };
 
-// Handler function to get connecting people
-#[get("/other/people")]
-pub async fn get_other_server_people_handler(pool: web::Data<AppState>) -> impl Responder {
-    let query_str = "SELECT * FROM other_server_people";
-    match query_as::<_, ProcessedPerson>(query_str)
+    let mut builder = QueryBuilder::<MySql>::new(format!(
+        "SELECT p.*, a.hash AS avatar_hash FROM {0} p
+         LEFT JOIN contact_avatars a ON a.people_table = '{0}' AND a.contact_id = p.id",
+        table
+    ));
+    push_people_conditions(&mut builder, filter);
+    // The primary key breaks ties so rows never move between pages
+    builder
+        .push(" ORDER BY ")
+        .push(filter.sort.column())
+        .push(" ")
+        .push(filter.order.keyword())
+        .push(", id ")
+        .push(filter.order.keyword())
+        .push(" LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    match builder
+        .build_query_as::<ProcessedPerson>()
         .fetch_all(&pool.db_pool)
         .await
     {
-        Ok(people) => HttpResponse::Ok().json(people),
+        Ok(mut people) => {
+            for person in &mut people {
+                person.avatar_url = person.avatar_hash.as_deref().map(avatar_url);
+            }
+            HttpResponse::Ok()
+                .insert_header(("X-Total-Count", total.to_string()))
+                .json(people)
+        }
         Err(e) => {
-            eprintln!("Error retrieving connecting people: {}", e);
+            eprintln!("Error retrieving people from '{}' table: {}", table, e);
             HttpResponse::InternalServerError().finish()
         }
     }
 }
+
+// Handler function to get connected people
+#[get("/my/people")]
+pub async fn get_my_server_people_handler(
+    pool: web::Data<AppState>,
+    filter: web::Query<PeopleFilter>,
+) -> impl Responder {
+    fetch_people(&pool, "my_server_people", &filter).await
+}
+
+// Handler function to get connecting people
+#[get("/other/people")]
+pub async fn get_other_server_people_handler(
+    pool: web::Data<AppState>,
+    filter: web::Query<PeopleFilter>,
+) -> impl Responder {
+    fetch_people(&pool, "other_server_people", &filter).await
+}
@@ -0,0 +1,1033 @@
+use super::message_contact_handlers::record_contact_edit;
+use super::message_conversation_handlers::refresh_conversation;
+use super::message_mention_handlers::people_table;
+use crate::server::AppState;
+use actix_web::{get, post, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{query, query_as, FromRow, MySql, MySqlConnection, QueryBuilder};
+
+const MESSAGE_TABLES: [&str; 2] = [
+    "messages_send_to_my_client",
+    "messages_send_to_other_client",
+];
+const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.5;
+// Tables holding one row per conversation, moved along with the conversation on a merge
+const CONVERSATION_KEYED_TABLES: [&str; 3] = [
+    "message_drafts",
+    "conversation_settings",
+    "message_rule_replies",
+];
+
+// Define the contact row as it is stored, also used for the undo snapshots
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct ContactRow {
+    id: String,
+    nick: Option<String>,
+    age: Option<i32>,
+    location: Option<String>,
+    occupation: Option<String>,
+    extra_info: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DuplicateCandidate {
+    first: ContactRow,
+    second: ContactRow,
+    score: f64,
+    reasons: Vec<&'static str>,
+}
+
+#[derive(Deserialize)]
+struct DuplicateQuery {
+    threshold: Option<f64>,
+}
+
+// Define a struct to capture which contact survives a merge
+#[derive(Deserialize)]
+struct MergeRequest {
+    keep_id: String,
+    merge_id: String,
+}
+
+#[derive(Serialize)]
+struct MergeResult {
+    merge_id: u64,
+    contact: ContactRow,
+    messages_rewritten: usize,
+}
+
+// Define the undo log entry written for every merge
+#[derive(Debug, Serialize, FromRow)]
+struct MergeLogEntry {
+    id: i64,
+    people_table: String,
+    kept_id: String,
+    merged_id: String,
+    kept_snapshot: String,
+    merged_snapshot: String,
+    rewritten_messages: String,
+    merged_group_ids: String,
+    added_group_ids: String,
+    merged_avatar: Option<String>,
+    avatar_moved: bool,
+    moved_rows: Option<String>, // Missing for merges logged before these rows were moved
+    merged_at: chrono::DateTime<chrono::Utc>,
+    undone_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// Message ids whose 'connected' field was rewritten, per message table
+#[derive(Default, Serialize, Deserialize)]
+struct RewrittenMessages {
+    my_client: Vec<i32>,
+    other_client: Vec<i32>,
+}
+
+// Scheduled message ids rewritten by a merge, per column
+#[derive(Default, Serialize, Deserialize)]
+struct RewrittenScheduled {
+    connected: Vec<i32>,
+    sender: Vec<i32>,
+    receiver: Vec<i32>,
+}
+
+// A mention of the merged contact, pointed at the kept contact by the merge
+#[derive(Serialize, Deserialize, FromRow)]
+struct MovedMention {
+    message_table: String,
+    message_id: i32,
+    position: i32,
+}
+
+// A conversation keyed row moved from the merged contact to the kept contact
+#[derive(Serialize, Deserialize)]
+struct MovedKey {
+    table: String,
+    message_table: String,
+}
+
+// The draft of the merged contact dropped because the kept contact had one of its own
+#[derive(Serialize, Deserialize, FromRow)]
+struct DroppedDraft {
+    message_table: String,
+    content: String,
+    reply_to: Option<i32>,
+    attachments: Json<serde_json::Value>,
+}
+
+// The conversation settings of the merged contact dropped in favour of the kept contact's
+#[derive(Serialize, Deserialize, FromRow)]
+struct DroppedSetting {
+    message_table: String,
+    expiry_seconds: Option<i32>,
+    collapse_repeats: bool,
+}
+
+// Everything besides the conversations a merge moved from the merged contact to the kept one
+#[derive(Default, Serialize, Deserialize)]
+struct MovedRows {
+    senders: RewrittenMessages,   // Messages the merged contact sent
+    receivers: RewrittenMessages, // Messages sent to the merged contact
+    mentions: Vec<MovedMention>,
+    scheduled: RewrittenScheduled,
+    keys: Vec<MovedKey>,
+    dropped_drafts: Vec<DroppedDraft>,
+    dropped_settings: Vec<DroppedSetting>,
+}
+
+// Lowercases a nick and drops everything that is not a letter or digit
+fn normalize_nick(nick: &str) -> String {
+    nick.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current[j + 1] = (previous[j + 1] + 1)
+                .min(current[j] + 1)
+                .min(previous[j] + cost);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+// Similarity between 0.0 and 1.0 derived from the edit distance
+fn similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / longest as f64
+}
+
+fn same_text(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a.as_deref().map(str::trim), b.as_deref().map(str::trim)) {
+        (Some(a), Some(b)) => !a.is_empty() && a.eq_ignore_ascii_case(b),
+        _ => false,
+    }
+}
+
+// Scores how likely two contacts describe the same person
+fn duplicate_score(first: &ContactRow, second: &ContactRow) -> (f64, Vec<&'static str>) {
+    let mut score = 0.0;
+    let mut reasons = Vec::new();
+
+    let first_nick = first
+        .nick
+        .as_deref()
+        .map(normalize_nick)
+        .unwrap_or_default();
+    let second_nick = second
+        .nick
+        .as_deref()
+        .map(normalize_nick)
+        .unwrap_or_default();
+    if !first_nick.is_empty() && first_nick == second_nick {
+        score += 0.5;
+        reasons.push("same_nick");
+    }
+
+    let id_similarity = similarity(&first.id.to_lowercase(), &second.id.to_lowercase());
+    if id_similarity >= 0.7 {
+        score += 0.3 * id_similarity;
+        reasons.push("similar_id");
+    }
+
+    if same_text(&first.location, &second.location) {
+        score += 0.1;
+        reasons.push("same_location");
+    }
+    if same_text(&first.occupation, &second.occupation) {
+        score += 0.1;
+        reasons.push("same_occupation");
+    }
+
+    (score, reasons)
+}
+
+// Fills the gaps of the surviving contact with what the merged contact knew
+fn combine_contacts(kept: &ContactRow, merged: &ContactRow) -> ContactRow {
+    let extra_info = match (&kept.extra_info, &merged.extra_info) {
+        (Some(a), Some(b)) if a != b => {
+            let mut combined = format!("{} | {}", a, b);
+            if combined.chars().count() > 300 {
+                combined = combined.chars().take(300).collect();
+            }
+            Some(combined)
+        }
+        (a, b) => a.clone().or_else(|| b.clone()),
+    };
+
+    ContactRow {
+        id: kept.id.clone(),
+        nick: kept.nick.clone().or_else(|| merged.nick.clone()),
+        age: kept.age.or(merged.age),
+        location: kept.location.clone().or_else(|| merged.location.clone()),
+        occupation: kept
+            .occupation
+            .clone()
+            .or_else(|| merged.occupation.clone()),
+        extra_info,
+    }
+}
+
+// Takes back what `combine_contacts` filled in from the merged contact. Fields changed since
+// the merge keep their current value, only the ones still as the merge left them go back.
+fn uncombine_contacts(current: &ContactRow, kept: &ContactRow, merged: &ContactRow) -> ContactRow {
+    fn restore<T: Clone + PartialEq>(current: &T, combined: &T, kept: &T) -> T {
+        if current == combined {
+            kept.clone()
+        } else {
+            current.clone()
+        }
+    }
+
+    let combined = combine_contacts(kept, merged);
+    ContactRow {
+        id: current.id.clone(),
+        nick: restore(&current.nick, &combined.nick, &kept.nick),
+        age: restore(&current.age, &combined.age, &kept.age),
+        location: restore(&current.location, &combined.location, &kept.location),
+        occupation: restore(&current.occupation, &combined.occupation, &kept.occupation),
+        extra_info: restore(&current.extra_info, &combined.extra_info, &kept.extra_info),
+    }
+}
+
+async fn find_duplicates(pool: &AppState, table: &str, threshold: Option<f64>) -> HttpResponse {
+    let threshold = threshold.unwrap_or(DEFAULT_DUPLICATE_THRESHOLD);
+    let query_str = format!(
+        "SELECT id, nick, age, location, occupation, extra_info FROM {} ORDER BY id",
+        table
+    );
+
+    let people = match query_as::<_, ContactRow>(&query_str)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(people) => people,
+        Err(e) => {
+            eprintln!("Error retrieving people from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let mut candidates = Vec::new();
+    for (i, first) in people.iter().enumerate() {
+        for second in &people[i + 1..] {
+            let (score, reasons) = duplicate_score(first, second);
+            if score >= threshold {
+                candidates.push(DuplicateCandidate {
+                    first: first.clone(),
+                    second: second.clone(),
+                    score,
+                    reasons,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    HttpResponse::Ok().json(candidates)
+}
+
+// Handler function to list likely duplicates among the connected people
+#[get("/my/people/duplicates")]
+pub async fn get_duplicate_people_my_client(
+    pool: web::Data<AppState>,
+    params: web::Query<DuplicateQuery>,
+) -> impl Responder {
+    find_duplicates(&pool, "my_server_people", params.threshold).await
+}
+
+// Handler function to list likely duplicates among the connecting people
+#[get("/other/people/duplicates")]
+pub async fn get_duplicate_people_other_client(
+    pool: web::Data<AppState>,
+    params: web::Query<DuplicateQuery>,
+) -> impl Responder {
+    find_duplicates(&pool, "other_server_people", params.threshold).await
+}
+
+async fn fetch_contact_for_update(
+    conn: &mut MySqlConnection,
+    table: &str,
+    id: &str,
+) -> Result<Option<ContactRow>, sqlx::Error> {
+    let query_str = format!(
+        "SELECT id, nick, age, location, occupation, extra_info FROM {} WHERE id = ? FOR UPDATE",
+        table
+    );
+    query_as::<_, ContactRow>(&query_str)
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+async fn fetch_group_ids(
+    conn: &mut MySqlConnection,
+    contact_id: &str,
+) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>("SELECT group_id FROM contact_group_members WHERE contact_id = ?")
+        .bind(contact_id)
+        .fetch_all(conn)
+        .await
+}
+
+async fn update_contact(
+    conn: &mut MySqlConnection,
+    table: &str,
+    contact: &ContactRow,
+) -> Result<(), sqlx::Error> {
+    let query_str = format!(
+        "UPDATE {} SET nick = ?, age = ?, location = ?, occupation = ?, extra_info = ? WHERE id = ?",
+        table
+    );
+    query(&query_str)
+        .bind(&contact.nick)
+        .bind(contact.age)
+        .bind(&contact.location)
+        .bind(&contact.occupation)
+        .bind(&contact.extra_info)
+        .bind(&contact.id)
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
+
+async fn fetch_avatar_hash(
+    conn: &mut MySqlConnection,
+    table: &str,
+    contact_id: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT hash FROM contact_avatars WHERE people_table = ? AND contact_id = ? FOR UPDATE",
+    )
+    .bind(table)
+    .bind(contact_id)
+    .fetch_optional(conn)
+    .await
+}
+
+// Hands the merged contact's avatar to the kept contact if it has none, otherwise drops it.
+// The files stay on disk while the merge log references the hash, so undo can restore it
+async fn merge_avatars(
+    conn: &mut MySqlConnection,
+    table: &str,
+    kept_id: &str,
+    merged_id: &str,
+) -> Result<(Option<String>, bool), sqlx::Error> {
+    let merged_avatar = match fetch_avatar_hash(conn, table, merged_id).await? {
+        Some(hash) => hash,
+        None => return Ok((None, false)),
+    };
+
+    let moved = fetch_avatar_hash(conn, table, kept_id).await?.is_none();
+    if moved {
+        query(
+            "UPDATE contact_avatars SET contact_id = ? WHERE people_table = ? AND contact_id = ?",
+        )
+        .bind(kept_id)
+        .bind(table)
+        .bind(merged_id)
+        .execute(&mut *conn)
+        .await?;
+    } else {
+        query("DELETE FROM contact_avatars WHERE people_table = ? AND contact_id = ?")
+            .bind(table)
+            .bind(merged_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok((Some(merged_avatar), moved))
+}
+
+// Points a contact column of the given rows at another contact, `column` is one of the
+// fixed contact columns
+async fn set_column(
+    conn: &mut MySqlConnection,
+    table: &str,
+    column: &str,
+    contact_id: &str,
+    ids: &[i32],
+) -> Result<(), sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(format!("UPDATE {} SET {} = ", table, column));
+    builder.push_bind(contact_id).push(" WHERE id IN (");
+    let mut separated = builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+
+    builder.build().execute(conn).await.map(|_| ())
+}
+
+// Moves a contact column of every row from one contact to another and returns the moved ids
+async fn rewrite_column(
+    conn: &mut MySqlConnection,
+    table: &str,
+    column: &str,
+    from: &str,
+    to: &str,
+) -> Result<Vec<i32>, sqlx::Error> {
+    let query_str = format!("SELECT id FROM {} WHERE {} = ? FOR UPDATE", table, column);
+    let ids = sqlx::query_scalar::<_, i32>(&query_str)
+        .bind(from)
+        .fetch_all(&mut *conn)
+        .await?;
+    set_column(conn, table, column, to, &ids).await?;
+    Ok(ids)
+}
+
+async fn rewrite_messages(
+    conn: &mut MySqlConnection,
+    column: &str,
+    from: &str,
+    to: &str,
+) -> Result<RewrittenMessages, sqlx::Error> {
+    Ok(RewrittenMessages {
+        my_client: rewrite_column(conn, MESSAGE_TABLES[0], column, from, to).await?,
+        other_client: rewrite_column(conn, MESSAGE_TABLES[1], column, from, to).await?,
+    })
+}
+
+async fn restore_messages(
+    conn: &mut MySqlConnection,
+    column: &str,
+    contact_id: &str,
+    rewritten: &RewrittenMessages,
+) -> Result<(), sqlx::Error> {
+    set_column(
+        conn,
+        MESSAGE_TABLES[0],
+        column,
+        contact_id,
+        &rewritten.my_client,
+    )
+    .await?;
+    set_column(
+        conn,
+        MESSAGE_TABLES[1],
+        column,
+        contact_id,
+        &rewritten.other_client,
+    )
+    .await
+}
+
+// Hands the conversation keyed rows of the merged contact to the kept contact. Where the kept
+// contact has a row of its own, that one wins and the merged contact's draft or settings are
+// kept in the log for undo.
+async fn move_keyed_rows(
+    conn: &mut MySqlConnection,
+    moved: &mut MovedRows,
+    from: &str,
+    to: &str,
+) -> Result<(), sqlx::Error> {
+    for table in CONVERSATION_KEYED_TABLES {
+        for message_table in MESSAGE_TABLES {
+            let count_query = format!(
+                "SELECT COUNT(*) FROM {} WHERE message_table = ? AND connected = ? FOR UPDATE",
+                table
+            );
+            let count = |connected: &str| {
+                sqlx::query_scalar::<_, i64>(&count_query)
+                    .bind(message_table)
+                    .bind(connected.to_string())
+            };
+            if count(from).fetch_one(&mut *conn).await? == 0 {
+                continue;
+            }
+
+            if count(to).fetch_one(&mut *conn).await? == 0 {
+                query(&format!(
+                    "UPDATE {} SET connected = ? WHERE message_table = ? AND connected = ?",
+                    table
+                ))
+                .bind(to)
+                .bind(message_table)
+                .bind(from)
+                .execute(&mut *conn)
+                .await?;
+                moved.keys.push(MovedKey {
+                    table: table.to_string(),
+                    message_table: message_table.to_string(),
+                });
+                continue;
+            }
+
+            match table {
+                "message_drafts" => moved.dropped_drafts.extend(
+                    query_as::<_, DroppedDraft>(
+                        "SELECT message_table, content, reply_to, attachments FROM message_drafts \
+                         WHERE message_table = ? AND connected = ?",
+                    )
+                    .bind(message_table)
+                    .bind(from)
+                    .fetch_optional(&mut *conn)
+                    .await?,
+                ),
+                "conversation_settings" => moved.dropped_settings.extend(
+                    query_as::<_, DroppedSetting>(
+                        "SELECT message_table, expiry_seconds, collapse_repeats \
+                         FROM conversation_settings WHERE message_table = ? AND connected = ?",
+                    )
+                    .bind(message_table)
+                    .bind(from)
+                    .fetch_optional(&mut *conn)
+                    .await?,
+                ),
+                // An auto-reply claim only throttles replies, the kept contact's one is enough
+                _ => {}
+            }
+            query(&format!(
+                "DELETE FROM {} WHERE message_table = ? AND connected = ?",
+                table
+            ))
+            .bind(message_table)
+            .bind(from)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+// Points every row naming the merged contact, besides the conversations, at the kept contact
+// so messages stay between their conversation partner and the local side
+async fn move_rows(
+    conn: &mut MySqlConnection,
+    table: &str,
+    from: &str,
+    to: &str,
+) -> Result<MovedRows, sqlx::Error> {
+    let mut moved = MovedRows {
+        senders: rewrite_messages(conn, "sender", from, to).await?,
+        receivers: rewrite_messages(conn, "receiver", from, to).await?,
+        ..MovedRows::default()
+    };
+
+    // Mentions are resolved against the people table of their message table only
+    for message_table in MESSAGE_TABLES {
+        if people_table(message_table) != Some(table) {
+            continue;
+        }
+        let mentions = query_as::<_, MovedMention>(
+            "SELECT message_table, message_id, position FROM message_mentions \
+             WHERE message_table = ? AND contact_id = ? FOR UPDATE",
+        )
+        .bind(message_table)
+        .bind(from)
+        .fetch_all(&mut *conn)
+        .await?;
+        query(
+            "UPDATE message_mentions SET contact_id = ? WHERE message_table = ? AND contact_id = ?",
+        )
+        .bind(to)
+        .bind(message_table)
+        .bind(from)
+        .execute(&mut *conn)
+        .await?;
+        moved.mentions.extend(mentions);
+    }
+
+    moved.scheduled = RewrittenScheduled {
+        connected: rewrite_column(conn, "scheduled_messages", "connected", from, to).await?,
+        sender: rewrite_column(conn, "scheduled_messages", "sender", from, to).await?,
+        receiver: rewrite_column(conn, "scheduled_messages", "receiver", from, to).await?,
+    };
+
+    move_keyed_rows(conn, &mut moved, from, to).await?;
+    Ok(moved)
+}
+
+// Gives the merged contact back what `move_rows` took from it
+async fn restore_rows(
+    conn: &mut MySqlConnection,
+    moved: &MovedRows,
+    merged_id: &str,
+    kept_id: &str,
+) -> Result<(), sqlx::Error> {
+    restore_messages(conn, "sender", merged_id, &moved.senders).await?;
+    restore_messages(conn, "receiver", merged_id, &moved.receivers).await?;
+
+    for mention in &moved.mentions {
+        query(
+            "UPDATE message_mentions SET contact_id = ? \
+             WHERE message_table = ? AND message_id = ? AND position = ?",
+        )
+        .bind(merged_id)
+        .bind(&mention.message_table)
+        .bind(mention.message_id)
+        .bind(mention.position)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let scheduled = &moved.scheduled;
+    set_column(
+        conn,
+        "scheduled_messages",
+        "connected",
+        merged_id,
+        &scheduled.connected,
+    )
+    .await?;
+    set_column(
+        conn,
+        "scheduled_messages",
+        "sender",
+        merged_id,
+        &scheduled.sender,
+    )
+    .await?;
+    set_column(
+        conn,
+        "scheduled_messages",
+        "receiver",
+        merged_id,
+        &scheduled.receiver,
+    )
+    .await?;
+
+    for key in &moved.keys {
+        // Only known table names were logged, anything else is skipped
+        let table = match CONVERSATION_KEYED_TABLES
+            .iter()
+            .find(|table| **table == key.table)
+        {
+            Some(table) => table,
+            None => continue,
+        };
+        query(&format!(
+            "UPDATE IGNORE {} SET connected = ? WHERE message_table = ? AND connected = ?",
+            table
+        ))
+        .bind(merged_id)
+        .bind(&key.message_table)
+        .bind(kept_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    for draft in &moved.dropped_drafts {
+        query(
+            "INSERT IGNORE INTO message_drafts \
+             (message_table, connected, content, reply_to, attachments) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&draft.message_table)
+        .bind(merged_id)
+        .bind(&draft.content)
+        .bind(draft.reply_to)
+        .bind(&draft.attachments)
+        .execute(&mut *conn)
+        .await?;
+    }
+    for setting in &moved.dropped_settings {
+        query(
+            "INSERT IGNORE INTO conversation_settings \
+             (message_table, connected, expiry_seconds, collapse_repeats) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&setting.message_table)
+        .bind(merged_id)
+        .bind(setting.expiry_seconds)
+        .bind(setting.collapse_repeats)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn merge_contacts(
+    conn: &mut MySqlConnection,
+    table: &str,
+    request: &MergeRequest,
+) -> Result<Result<MergeResult, HttpResponse>, sqlx::Error> {
+    let kept = match fetch_contact_for_update(conn, table, &request.keep_id).await? {
+        Some(kept) => kept,
+        None => {
+            return Ok(Err(
+                HttpResponse::NotFound().body("Contact to keep not found")
+            ))
+        }
+    };
+    let merged = match fetch_contact_for_update(conn, table, &request.merge_id).await? {
+        Some(merged) => merged,
+        None => {
+            return Ok(Err(
+                HttpResponse::NotFound().body("Contact to merge not found")
+            ))
+        }
+    };
+
+    let rewritten = rewrite_messages(conn, "connected", &merged.id, &kept.id).await?;
+    let moved = move_rows(conn, table, &merged.id, &kept.id).await?;
+
+    for message_table in MESSAGE_TABLES {
+        refresh_conversation(conn, message_table, &merged.id).await?;
+        refresh_conversation(conn, message_table, &kept.id).await?;
+    }
+
+    let kept_groups = fetch_group_ids(conn, &kept.id).await?;
+    let merged_groups = fetch_group_ids(conn, &merged.id).await?;
+    let added_groups: Vec<i32> = merged_groups
+        .iter()
+        .copied()
+        .filter(|group_id| !kept_groups.contains(group_id))
+        .collect();
+    for group_id in &added_groups {
+        query("INSERT INTO contact_group_members (group_id, contact_id) VALUES (?, ?)")
+            .bind(group_id)
+            .bind(&kept.id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    query("DELETE FROM contact_group_members WHERE contact_id = ?")
+        .bind(&merged.id)
+        .execute(&mut *conn)
+        .await?;
+
+    let (merged_avatar, avatar_moved) = merge_avatars(conn, table, &kept.id, &merged.id).await?;
+
+    let combined = combine_contacts(&kept, &merged);
+    update_contact(conn, table, &combined).await?;
+    query(&format!("DELETE FROM {} WHERE id = ?", table))
+        .bind(&merged.id)
+        .execute(&mut *conn)
+        .await?;
+
+    let log_query = "
+        INSERT INTO contact_merge_log
+            (people_table, kept_id, merged_id, kept_snapshot, merged_snapshot,
+             rewritten_messages, merged_group_ids, added_group_ids, merged_avatar, avatar_moved,
+             moved_rows)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    ";
+    // Serializing these plain structs cannot fail
+    let log = query(log_query)
+        .bind(table)
+        .bind(&kept.id)
+        .bind(&merged.id)
+        .bind(serde_json::to_string(&kept).unwrap_or_default())
+        .bind(serde_json::to_string(&merged).unwrap_or_default())
+        .bind(serde_json::to_string(&rewritten).unwrap_or_default())
+        .bind(serde_json::to_string(&merged_groups).unwrap_or_default())
+        .bind(serde_json::to_string(&added_groups).unwrap_or_default())
+        .bind(&merged_avatar)
+        .bind(avatar_moved)
+        .bind(serde_json::to_string(&moved).unwrap_or_default())
+        .execute(&mut *conn)
+        .await?;
+
+    let details = serde_json::json!({
+        "merged_id": merged.id,
+        "merge_id": log.last_insert_id(),
+    });
+    record_contact_edit(&mut *conn, Some(table), &kept.id, "merged", details).await?;
+
+    Ok(Ok(MergeResult {
+        merge_id: log.last_insert_id(),
+        contact: combined,
+        messages_rewritten: rewritten.my_client.len() + rewritten.other_client.len(),
+    }))
+}
+
+async fn merge_people(pool: &AppState, table: &str, request: &MergeRequest) -> HttpResponse {
+    if request.keep_id == request.merge_id {
+        return HttpResponse::BadRequest().body("Cannot merge a contact into itself");
+    }
+
+    let mut tx = match pool.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("Error starting transaction: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    match merge_contacts(&mut tx, table, request).await {
+        Ok(Ok(result)) => match tx.commit().await {
+            Ok(_) => HttpResponse::Ok().json(result),
+            Err(e) => {
+                eprintln!("Error committing contact merge: {}", e);
+                HttpResponse::InternalServerError().finish()
+            }
+        },
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error merging contacts in '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to merge two connected people into one
+#[post("/my/people/merge/")]
+pub async fn merge_people_my_client(
+    pool: web::Data<AppState>,
+    request: web::Json<MergeRequest>,
+) -> impl Responder {
+    merge_people(&pool, "my_server_people", &request).await
+}
+
+// Handler function to merge two connecting people into one
+#[post("/other/people/merge/")]
+pub async fn merge_people_other_client(
+    pool: web::Data<AppState>,
+    request: web::Json<MergeRequest>,
+) -> impl Responder {
+    merge_people(&pool, "other_server_people", &request).await
+}
+
+// Handler function to list the merge undo log, newest first
+#[get("/people/merges")]
+pub async fn get_contact_merges(pool: web::Data<AppState>) -> impl Responder {
+    let query_str = "SELECT * FROM contact_merge_log ORDER BY merged_at DESC, id DESC";
+
+    match query_as::<_, MergeLogEntry>(query_str)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => {
+            eprintln!("Error retrieving contact merge log: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn undo_merge(
+    conn: &mut MySqlConnection,
+    merge_id: i64,
+) -> Result<Result<ContactRow, HttpResponse>, sqlx::Error> {
+    let entry =
+        query_as::<_, MergeLogEntry>("SELECT * FROM contact_merge_log WHERE id = ? FOR UPDATE")
+            .bind(merge_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    let entry = match entry {
+        Some(entry) if entry.undone_at.is_some() => {
+            return Ok(Err(
+                HttpResponse::Conflict().body("Merge was already undone")
+            ))
+        }
+        Some(entry) => entry,
+        None => return Ok(Err(HttpResponse::NotFound().finish())),
+    };
+
+    let parsed = (
+        serde_json::from_str::<ContactRow>(&entry.kept_snapshot),
+        serde_json::from_str::<ContactRow>(&entry.merged_snapshot),
+        serde_json::from_str::<RewrittenMessages>(&entry.rewritten_messages),
+        serde_json::from_str::<Vec<i32>>(&entry.merged_group_ids),
+        serde_json::from_str::<Vec<i32>>(&entry.added_group_ids),
+        entry
+            .moved_rows
+            .as_deref()
+            .map_or(Ok(MovedRows::default()), serde_json::from_str::<MovedRows>),
+    );
+    let (kept, merged, rewritten, merged_groups, added_groups, moved) = match parsed {
+        (Ok(a), Ok(b), Ok(c), Ok(d), Ok(e), Ok(f)) => (a, b, c, d, e, f),
+        _ => {
+            eprintln!("Corrupt contact merge log entry {}", entry.id);
+            return Ok(Err(HttpResponse::InternalServerError().finish()));
+        }
+    };
+    let table = entry.people_table.as_str();
+
+    let current = match fetch_contact_for_update(conn, table, &kept.id).await? {
+        Some(current) => current,
+        None => {
+            return Ok(Err(HttpResponse::Conflict().body(
+                "The surviving contact no longer exists, undo later merges first",
+            )))
+        }
+    };
+    if fetch_contact_for_update(conn, table, &merged.id)
+        .await?
+        .is_some()
+    {
+        return Ok(Err(
+            HttpResponse::Conflict().body("A contact with the merged id exists again")
+        ));
+    }
+
+    update_contact(conn, table, &uncombine_contacts(&current, &kept, &merged)).await?;
+    query(&format!(
+        "INSERT INTO {} (id, nick, age, location, occupation, extra_info) VALUES (?, ?, ?, ?, ?, ?)",
+        table
+    ))
+    .bind(&merged.id)
+    .bind(&merged.nick)
+    .bind(merged.age)
+    .bind(&merged.location)
+    .bind(&merged.occupation)
+    .bind(&merged.extra_info)
+    .execute(&mut *conn)
+    .await?;
+
+    restore_messages(conn, "connected", &merged.id, &rewritten).await?;
+    restore_rows(conn, &moved, &merged.id, &kept.id).await?;
+    for message_table in MESSAGE_TABLES {
+        refresh_conversation(conn, message_table, &merged.id).await?;
+        refresh_conversation(conn, message_table, &kept.id).await?;
+    }
+
+    for group_id in &added_groups {
+        query("DELETE FROM contact_group_members WHERE group_id = ? AND contact_id = ?")
+            .bind(group_id)
+            .bind(&kept.id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for group_id in &merged_groups {
+        // The group may have been deleted since the merge
+        query(
+            "INSERT IGNORE INTO contact_group_members (group_id, contact_id)
+             SELECT id, ? FROM contact_groups WHERE id = ?",
+        )
+        .bind(&merged.id)
+        .bind(group_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    if let Some(hash) = &entry.merged_avatar {
+        // Only take the avatar back if the kept contact has not replaced it since
+        if entry.avatar_moved {
+            query(
+                "DELETE FROM contact_avatars
+                 WHERE people_table = ? AND contact_id = ? AND hash = ?",
+            )
+            .bind(table)
+            .bind(&kept.id)
+            .bind(hash)
+            .execute(&mut *conn)
+            .await?;
+        }
+        query(
+            "INSERT INTO contact_avatars (people_table, contact_id, hash) VALUES (?, ?, ?)
+             ON DUPLICATE KEY UPDATE hash = VALUES(hash), updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(table)
+        .bind(&merged.id)
+        .bind(hash)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    query("UPDATE contact_merge_log SET undone_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(entry.id)
+        .execute(&mut *conn)
+        .await?;
+
+    let details = serde_json::json!({ "merged_id": merged.id, "merge_id": entry.id });
+    record_contact_edit(&mut *conn, Some(table), &kept.id, "merge_undone", details).await?;
+
+    Ok(Ok(merged))
+}
+
+// Handler function to reverse a merge from the undo log
+#[post("/people/merges/{merge_id}/undo")]
+pub async fn undo_contact_merge(
+    pool: web::Data<AppState>,
+    merge_id: web::Path<i64>,
+) -> impl Responder {
+    let mut tx = match pool.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("Error starting transaction: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    match undo_merge(&mut tx, merge_id.into_inner()).await {
+        Ok(Ok(restored)) => match tx.commit().await {
+            Ok(_) => HttpResponse::Ok().json(restored),
+            Err(e) => {
+                eprintln!("Error committing contact merge undo: {}", e);
+                HttpResponse::InternalServerError().finish()
+            }
+        },
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error undoing contact merge: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::server::AppState;
+use actix_web::{get, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySql, QueryBuilder};
+
+const DEFAULT_TIMELINE_LIMIT: u32 = 50;
+const MAX_TIMELINE_LIMIT: u32 = 200;
+
+// Every kind of event the timeline can merge, with the query selecting it for one contact.
+// Unsent messages and the ones the local side deleted for itself keep their place but lose
+// their text, like in the conversation list
+const TIMELINE_SOURCES: [(&str, &str); 5] = [
+    (
+        "message_my",
+        "SELECT 'message_my' AS event_type, CAST(id AS SIGNED) AS source_id, timestamp AS occurred_at,
+                sender AS actor,
+                CASE WHEN m.deleted_at IS NULL AND NOT EXISTS (
+                    SELECT 1 FROM message_hides h
+                    WHERE h.message_table = 'messages_send_to_my_client' AND h.message_id = m.id
+                      AND h.viewer = CASE WHEN m.sender = m.connected THEN m.receiver ELSE m.sender END)
+                THEN LEFT(m.content, 200) ELSE '' END AS summary,
+                close_one_point AS details
+         FROM messages_send_to_my_client m
+         WHERE (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP) AND connected = ",
+    ),
+    (
+        "message_other",
+        "SELECT 'message_other' AS event_type, CAST(id AS SIGNED) AS source_id, timestamp AS occurred_at,
+                sender AS actor,
+                CASE WHEN m.deleted_at IS NULL AND NOT EXISTS (
+                    SELECT 1 FROM message_hides h
+                    WHERE h.message_table = 'messages_send_to_other_client' AND h.message_id = m.id
+                      AND h.viewer = CASE WHEN m.sender = m.connected THEN m.receiver ELSE m.sender END)
+                THEN LEFT(m.content, 200) ELSE '' END AS summary,
+                close_one_point AS details
+         FROM messages_send_to_other_client m
+         WHERE (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP) AND connected = ",
+    ),
+    (
+        "contact_edit",
+        "SELECT 'contact_edit' AS event_type, CAST(id AS SIGNED) AS source_id, created_at AS occurred_at,
+                people_table AS actor, edit_type AS summary, details
+         FROM contact_edits WHERE contact_id = ",
+    ),
+    (
+        "form_submission",
+        "SELECT 'form_submission' AS event_type, CAST(id AS SIGNED) AS source_id, submitted_at AS occurred_at,
+                submitter AS actor, LEFT(content, 200) AS summary, form_slug AS details
+         FROM form_submissions WHERE submitter = ",
+    ),
+    (
+        "wall_post",
+        "SELECT 'wall_post' AS event_type, CAST(id AS SIGNED) AS source_id, created_at AS occurred_at,
+                author AS actor, LEFT(content, 200) AS summary, NULL AS details
+         FROM wailing_wall_posts WHERE author = ",
+    ),
+];
+
+// Define a struct to represent a single timeline entry for API responses
+#[derive(Debug, Serialize, FromRow)]
+struct TimelineEvent {
+    event_type: String,
+    source_id: i64,
+    occurred_at: DateTime<Utc>,
+    actor: Option<String>,
+    summary: Option<String>,
+    details: Option<String>,
+}
+
+// Define the query parameters accepted by the timeline
+#[derive(Deserialize)]
+struct TimelineQuery {
+    types: Option<String>, // Comma separated event types, all types when missing
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+// Handler function to get the chronological activity feed of a contact
+#[get("/people/{id}/timeline")]
+pub async fn get_contact_timeline(
+    pool: web::Data<AppState>,
+    id: web::Path<String>,
+    params: web::Query<TimelineQuery>,
+) -> impl Responder {
+    let requested: Vec<&str> = match &params.types {
+        Some(types) => types
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .collect(),
+        None => TIMELINE_SOURCES.iter().map(|(name, _)| *name).collect(),
+    };
+
+    if let Some(unknown) = requested
+        .iter()
+        .find(|t| !TIMELINE_SOURCES.iter().any(|(name, _)| name == *t))
+    {
+        return HttpResponse::BadRequest().body(format!("Unknown event type '{}'", unknown));
+    }
+    if requested.is_empty() {
+        return HttpResponse::Ok().json(Vec::<TimelineEvent>::new());
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_TIMELINE_LIMIT)
+        .clamp(1, MAX_TIMELINE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let mut builder = QueryBuilder::<MySql>::new("SELECT * FROM (");
+    let selected = TIMELINE_SOURCES
+        .iter()
+        .filter(|(name, _)| requested.contains(name));
+    for (i, (_, source_query)) in selected.enumerate() {
+        if i > 0 {
+            builder.push(" UNION ALL ");
+        }
+        builder.push(*source_query).push_bind(id.as_str());
+    }
+    // Event type and source id break ties so entries never move between pages
+    builder
+        .push(") timeline ORDER BY occurred_at DESC, event_type, source_id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    match builder
+        .build_query_as::<TimelineEvent>()
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => {
+            eprintln!("Error retrieving contact timeline: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use super::message_get_set_handlers::NOT_EXPIRED;
+use crate::server::AppState;
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{query, FromRow, MySqlConnection};
+
+// Define a struct to represent a conversation overview for API responses
+#[derive(Debug, Serialize, FromRow)]
+struct ConversationSummary {
+    connected: String,
+    nick: Option<String>,
+    last_message_id: Option<i32>, // None while the conversation only has a draft
+    last_sender: Option<String>,
+    last_preview: String,
+    last_activity: DateTime<Utc>,
+    unread_count: i32,
+    expiry_seconds: Option<i32>, // Disappearing messages timer of the conversation
+    draft_preview: Option<String>, // Set while the conversation has an unsent draft
+}
+
+// Function to recompute the stored summary of one conversation from its messages.
+// Incoming messages (sent by the conversation partner) without a read receipt are unread,
+// expired disappearing messages are left out. The summary is the local side's view, so the
+// messages it deleted for itself lose their preview and no longer count as unread.
+// Group conversations have no partner sending to them, their read state is kept per member
+// with the receipts and counted by the chat listing, so their unread count stays 0 here.
+pub async fn refresh_conversation(
+    conn: &mut MySqlConnection,
+    table: &str,
+    connected: &str,
+) -> Result<(), sqlx::Error> {
+    // A single upsert, so concurrent refreshes never see the summary missing
+    let insert_query = format!(
+        "
+        INSERT INTO conversations
+            (message_table, connected, last_message_id, last_sender, last_preview,
+             last_activity, unread_count)
+        SELECT ?, m.connected, m.id, m.sender,
+            CASE WHEN m.deleted_at IS NULL AND NOT EXISTS (
+                    SELECT 1 FROM message_hides h
+                    WHERE h.message_table = ? AND h.message_id = m.id
+                      AND h.viewer = CASE WHEN m.sender = m.connected THEN m.receiver ELSE m.sender END)
+                THEN LEFT(m.content, 100) ELSE '' END,
+            m.timestamp,
+            (SELECT COUNT(*) FROM {0} u
+             WHERE u.connected = m.connected AND u.sender = u.connected
+               AND u.read_at IS NULL AND u.deleted_at IS NULL AND {1}
+               AND NOT EXISTS (
+                   SELECT 1 FROM message_hides h
+                   WHERE h.message_table = ? AND h.message_id = u.id AND h.viewer = u.receiver))
+        FROM {0} m
+        WHERE m.connected = ? AND {1}
+        ORDER BY m.timestamp DESC, m.id DESC
+        LIMIT 1
+        ON DUPLICATE KEY UPDATE
+            last_message_id = VALUES(last_message_id), last_sender = VALUES(last_sender),
+            last_preview = VALUES(last_preview), last_activity = VALUES(last_activity),
+            unread_count = VALUES(unread_count)
+        ",
+        table, NOT_EXPIRED
+    );
+    query(&insert_query)
+        .bind(table)
+        .bind(table)
+        .bind(table)
+        .bind(connected)
+        .execute(&mut *conn)
+        .await?;
+
+    // Once no message is left the conversation disappears from the list
+    let delete_query = format!(
+        "DELETE FROM conversations WHERE message_table = ? AND connected = ? \
+         AND NOT EXISTS (SELECT 1 FROM {} WHERE connected = ? AND {})",
+        table, NOT_EXPIRED
+    );
+    query(&delete_query)
+        .bind(table)
+        .bind(connected)
+        .bind(connected)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+// Function to refresh a conversation summary outside of a transaction, failures are only
+// logged because the summary can always be rebuilt from the messages
+pub async fn update_conversation(pool: &AppState, table: &str, connected: &str) {
+    let result = match pool.db_pool.acquire().await {
+        Ok(mut conn) => refresh_conversation(&mut conn, table, connected).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        eprintln!(
+            "Error refreshing conversation '{}' in '{}': {}",
+            connected, table, e
+        );
+    }
+}
+
+// Conversations with nothing but a draft are listed too, at the time of the draft
+async fn list_conversations(pool: &AppState, table: &str, people_table: &str) -> HttpResponse {
+    let query_str = format!(
+        "
+        SELECT c.connected, COALESCE(p.nick, g.name) AS nick, c.last_message_id, c.last_sender,
+            CASE WHEN m.expires_at <= CURRENT_TIMESTAMP THEN '' ELSE c.last_preview END AS last_preview,
+            c.last_activity, c.unread_count, s.expiry_seconds,
+            LEFT(d.content, 100) AS draft_preview
+        FROM conversations c
+        LEFT JOIN {0} p ON p.id = c.connected
+        LEFT JOIN group_chats g ON CONCAT('group:', g.id) = c.connected
+        LEFT JOIN {1} m ON m.id = c.last_message_id
+        LEFT JOIN conversation_settings s
+            ON s.message_table = c.message_table AND s.connected = c.connected
+        LEFT JOIN message_drafts d
+            ON d.message_table = c.message_table AND d.connected = c.connected
+        WHERE c.message_table = ?
+        UNION ALL
+        SELECT d.connected, COALESCE(p.nick, g.name) AS nick, NULL, NULL, '', d.updated_at, 0,
+            s.expiry_seconds, LEFT(d.content, 100)
+        FROM message_drafts d
+        LEFT JOIN {0} p ON p.id = d.connected
+        LEFT JOIN group_chats g ON CONCAT('group:', g.id) = d.connected
+        LEFT JOIN conversation_settings s
+            ON s.message_table = d.message_table AND s.connected = d.connected
+        WHERE d.message_table = ? AND NOT EXISTS (
+            SELECT 1 FROM conversations c
+            WHERE c.message_table = d.message_table AND c.connected = d.connected)
+        ORDER BY last_activity DESC, last_message_id DESC
+        ",
+        people_table, table
+    );
+
+    match sqlx::query_as::<_, ConversationSummary>(&query_str)
+        .bind(table)
+        .bind(table)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(conversations) => HttpResponse::Ok().json(conversations),
+        Err(e) => {
+            eprintln!("Error retrieving conversations for '{}': {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list every 'my-client' conversation, most recent first
+#[get("/my/conversations")]
+pub async fn get_conversations_my_client(pool: web::Data<AppState>) -> impl Responder {
+    list_conversations(&pool, "messages_send_to_my_client", "my_server_people").await
+}
+
+// Handler function to list every 'other-client' conversation, most recent first
+#[get("/other/conversations")]
+pub async fn get_conversations_other_client(pool: web::Data<AppState>) -> impl Responder {
+    list_conversations(
+        &pool,
+        "messages_send_to_other_client",
+        "other_server_people",
+    )
+    .await
+}
+
+async fn rebuild_conversations(pool: &AppState) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.db_pool.begin().await?;
+    query("DELETE FROM conversations").execute(&mut *tx).await?;
+
+    let mut rebuilt = 0;
+    for table in [
+        "messages_send_to_my_client",
+        "messages_send_to_other_client",
+    ] {
+        let query_str = format!("SELECT DISTINCT connected FROM {}", table);
+        let partners = sqlx::query_scalar::<_, String>(&query_str)
+            .fetch_all(&mut *tx)
+            .await?;
+        for connected in &partners {
+            refresh_conversation(&mut tx, table, connected).await?;
+        }
+        rebuilt += partners.len();
+    }
+
+    tx.commit().await?;
+    Ok(rebuilt)
+}
+
+// Handler function to rebuild every conversation summary from the message tables
+#[post("/conversations/rebuild")]
+pub async fn rebuild_conversations_handler(pool: web::Data<AppState>) -> impl Responder {
+    match rebuild_conversations(&pool).await {
+        Ok(rebuilt) => HttpResponse::Ok().body(format!("Rebuilt {} conversations", rebuilt)),
+        Err(e) => {
+            eprintln!("Error rebuilding conversations: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use super::message_conversation_handlers::refresh_conversation;
+use super::message_get_set_handlers::NOT_EXPIRED;
+use crate::server::config::env_or;
+use crate::server::AppState;
+use actix_web::{delete, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySqlConnection};
+
+// How long after sending a message the sender may still unsend it
+const DEFAULT_UNSEND_WINDOW_SECONDS: i64 = 60 * 60;
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum DeleteMode {
+    ForMe,  // Hide the message locally, the other party still sees it
+    Unsend, // Tombstone the message for both parties, sender only
+}
+
+// Define the query parameters accepted by the delete endpoints
+#[derive(Deserialize)]
+struct DeleteQuery {
+    mode: DeleteMode,
+    requester: String,
+}
+
+#[derive(Clone, Serialize)]
+struct MessageDeletedEvent {
+    table: &'static str,
+    id: i32,
+    connected: String,
+    mode: DeleteMode,
+}
+
+#[derive(FromRow)]
+struct DeletableMessage {
+    sender: String,
+    receiver: String,
+    connected: String,
+    timestamp: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+// Why a delete was refused, checked against the locked message row
+enum Refusal {
+    NotFound,
+    AlreadyUnsent,
+    NotParticipant,
+    NotSender,
+    WindowPassed,
+}
+
+impl Refusal {
+    fn to_response(&self) -> HttpResponse {
+        match self {
+            Refusal::NotFound => HttpResponse::NotFound().finish(),
+            Refusal::AlreadyUnsent => HttpResponse::Gone().body("Message was already unsent"),
+            Refusal::NotParticipant => {
+                HttpResponse::Forbidden().body("Only a participant can delete a message")
+            }
+            Refusal::NotSender => {
+                HttpResponse::Forbidden().body("Only the sender can unsend a message")
+            }
+            Refusal::WindowPassed => HttpResponse::Forbidden().body("The unsend window has passed"),
+        }
+    }
+}
+
+// Checks and applies the delete while the message row is locked, returns the conversation
+async fn apply_delete(
+    conn: &mut MySqlConnection,
+    table: &str,
+    id: i32,
+    params: &DeleteQuery,
+) -> Result<Result<String, Refusal>, sqlx::Error> {
+    let select_query = format!(
+        "SELECT sender, receiver, connected, timestamp, deleted_at FROM {} WHERE id = ? AND {} \
+         FOR UPDATE",
+        table, NOT_EXPIRED
+    );
+    let message = match sqlx::query_as::<_, DeletableMessage>(&select_query)
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+    {
+        Some(message) => message,
+        None => return Ok(Err(Refusal::NotFound)),
+    };
+
+    if message.deleted_at.is_some() {
+        return Ok(Err(Refusal::AlreadyUnsent));
+    }
+
+    match params.mode {
+        DeleteMode::ForMe => {
+            if params.requester != message.sender && params.requester != message.receiver {
+                return Ok(Err(Refusal::NotParticipant));
+            }
+
+            // Only the requester's view changes, the shared row stays as it is
+            query(
+                "INSERT IGNORE INTO message_hides (message_table, message_id, viewer) \
+                 VALUES (?, ?, ?)",
+            )
+            .bind(table)
+            .bind(id)
+            .bind(&params.requester)
+            .execute(&mut *conn)
+            .await?;
+        }
+        DeleteMode::Unsend => {
+            if params.requester != message.sender {
+                return Ok(Err(Refusal::NotSender));
+            }
+
+            let window = env_or("UNSEND_WINDOW_SECONDS", DEFAULT_UNSEND_WINDOW_SECONDS);
+            if Utc::now()
+                .signed_duration_since(message.timestamp)
+                .num_seconds()
+                > window
+            {
+                return Ok(Err(Refusal::WindowPassed));
+            }
+
+            unsend_message(conn, table, id).await?;
+        }
+    }
+
+    refresh_conversation(conn, table, &message.connected).await?;
+    Ok(Ok(message.connected))
+}
+
+async fn delete_message(
+    pool: &AppState,
+    table: &'static str,
+    id: i32,
+    params: &DeleteQuery,
+) -> HttpResponse {
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        let applied = apply_delete(&mut tx, table, id, params).await?;
+        if applied.is_ok() {
+            tx.commit().await?;
+        }
+        Ok::<_, sqlx::Error>(applied)
+    }
+    .await;
+
+    match result {
+        Ok(Ok(connected)) => {
+            pool.emit_event(
+                "message-deleted",
+                MessageDeletedEvent {
+                    table,
+                    id,
+                    connected,
+                    mode: params.mode,
+                },
+            );
+            HttpResponse::Ok().finish()
+        }
+        Ok(Err(refusal)) => refusal.to_response(),
+        Err(e) => {
+            eprintln!("Error deleting message from '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Clears the content, stars, pins, every revision, reaction, attachment link, mention, alert
+// hit and personal hide. The row itself stays behind as a tombstone, files nothing references
+// anymore are left to the garbage collection
+async fn unsend_message(
+    conn: &mut MySqlConnection,
+    table: &str,
+    id: i32,
+) -> Result<(), sqlx::Error> {
+    let update_query = format!(
+        "UPDATE {} SET content = '', content_html = NULL, deleted_at = CURRENT_TIMESTAMP, \
+         starred_at = NULL, pinned_at = NULL WHERE id = ?",
+        table
+    );
+    query(&update_query).bind(id).execute(&mut *conn).await?;
+    for linked_table in [
+        "message_revisions",
+        "message_reactions",
+        "message_attachments",
+        "message_mentions",
+        "keyword_alert_hits",
+        "message_hides",
+    ] {
+        let delete_query = format!(
+            "DELETE FROM {} WHERE message_table = ? AND message_id = ?",
+            linked_table
+        );
+        query(&delete_query)
+            .bind(table)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// Handler function to delete a message sent to 'my-client'
+#[delete("/my/messages/{id}")]
+pub async fn delete_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    params: web::Query<DeleteQuery>,
+) -> impl Responder {
+    delete_message(
+        &pool,
+        "messages_send_to_my_client",
+        id.into_inner(),
+        &params,
+    )
+    .await
+}
+
+// Handler function to delete a message sent to 'other-client'
+#[delete("/other/messages/{id}")]
+pub async fn delete_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    params: web::Query<DeleteQuery>,
+) -> impl Responder {
+    delete_message(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        &params,
+    )
+    .await
+}
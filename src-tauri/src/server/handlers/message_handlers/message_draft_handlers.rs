@@ -0,0 +1,192 @@
+use super::message_attachment_handlers::{validate_attachment_refs, AttachmentRef};
+use crate::server::AppState;
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{query, FromRow};
+
+// Define a struct to represent the unsent message of a conversation
+#[derive(Debug, Serialize, FromRow)]
+struct Draft {
+    content: String,
+    reply_to: Option<i32>, // Checked when the message is sent, the target may be gone by then
+    attachments: Json<Vec<AttachmentRef>>, // Files uploaded beforehand through /attachments
+    updated_at: DateTime<Utc>,
+}
+
+// Define a struct to capture a draft from the request
+#[derive(Deserialize)]
+struct DraftUpdate {
+    content: String,
+    reply_to: Option<i32>,
+    #[serde(default)]
+    attachments: Vec<AttachmentRef>,
+}
+
+impl DraftUpdate {
+    fn is_empty(&self) -> bool {
+        self.content.trim().is_empty() && self.reply_to.is_none() && self.attachments.is_empty()
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct DraftEvent {
+    table: &'static str,
+    connected: String,
+}
+
+async fn get_draft(pool: &AppState, table: &str, connected: &str) -> HttpResponse {
+    match sqlx::query_as::<_, Draft>(
+        "SELECT content, reply_to, attachments, updated_at FROM message_drafts \
+         WHERE message_table = ? AND connected = ?",
+    )
+    .bind(table)
+    .bind(connected)
+    .fetch_optional(&pool.db_pool)
+    .await
+    {
+        Ok(Some(draft)) => HttpResponse::Ok().json(draft),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving draft: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Saving an empty draft clears it
+async fn save_draft(
+    pool: &AppState,
+    table: &'static str,
+    connected: &str,
+    draft: &DraftUpdate,
+) -> HttpResponse {
+    if draft.is_empty() {
+        return clear_draft(pool, table, connected).await;
+    }
+    if let Err(reason) = validate_attachment_refs(&draft.attachments) {
+        return HttpResponse::BadRequest().body(reason);
+    }
+
+    let result = query(
+        "INSERT INTO message_drafts (message_table, connected, content, reply_to, attachments) \
+         VALUES (?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE content = VALUES(content), \
+         reply_to = VALUES(reply_to), attachments = VALUES(attachments)",
+    )
+    .bind(table)
+    .bind(connected)
+    .bind(&draft.content)
+    .bind(draft.reply_to)
+    .bind(Json(&draft.attachments))
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(_) => {
+            pool.emit_event(
+                "draft-saved",
+                DraftEvent {
+                    table,
+                    connected: connected.to_string(),
+                },
+            );
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("Error saving draft: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Function to drop the draft of a conversation, returns whether there was one
+pub async fn discard_draft(
+    pool: &AppState,
+    table: &'static str,
+    connected: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = query("DELETE FROM message_drafts WHERE message_table = ? AND connected = ?")
+        .bind(table)
+        .bind(connected)
+        .execute(&pool.db_pool)
+        .await?;
+
+    let discarded = result.rows_affected() > 0;
+    if discarded {
+        pool.emit_event(
+            "draft-cleared",
+            DraftEvent {
+                table,
+                connected: connected.to_string(),
+            },
+        );
+    }
+    Ok(discarded)
+}
+
+async fn clear_draft(pool: &AppState, table: &'static str, connected: &str) -> HttpResponse {
+    match discard_draft(pool, table, connected).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            eprintln!("Error clearing draft: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to get the draft of a 'my-client' conversation
+#[get("/my/conversations/{connected}/draft")]
+pub async fn get_draft_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    get_draft(&pool, "messages_send_to_my_client", &connected).await
+}
+
+// Handler function to get the draft of an 'other-client' conversation
+#[get("/other/conversations/{connected}/draft")]
+pub async fn get_draft_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    get_draft(&pool, "messages_send_to_other_client", &connected).await
+}
+
+// Handler function to save the draft of a 'my-client' conversation
+#[put("/my/conversations/{connected}/draft")]
+pub async fn save_draft_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    draft: web::Json<DraftUpdate>,
+) -> impl Responder {
+    save_draft(&pool, "messages_send_to_my_client", &connected, &draft).await
+}
+
+// Handler function to save the draft of an 'other-client' conversation
+#[put("/other/conversations/{connected}/draft")]
+pub async fn save_draft_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    draft: web::Json<DraftUpdate>,
+) -> impl Responder {
+    save_draft(&pool, "messages_send_to_other_client", &connected, &draft).await
+}
+
+// Handler function to clear the draft of a 'my-client' conversation
+#[delete("/my/conversations/{connected}/draft")]
+pub async fn clear_draft_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    clear_draft(&pool, "messages_send_to_my_client", &connected).await
+}
+
+// Handler function to clear the draft of an 'other-client' conversation
+#[delete("/other/conversations/{connected}/draft")]
+pub async fn clear_draft_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    clear_draft(&pool, "messages_send_to_other_client", &connected).await
+}
@@ -0,0 +1,232 @@
+use super::message_conversation_handlers::update_conversation;
+use super::message_get_set_handlers::{
+    fetch_message, to_responses, MessageFormat, MessageResponse, NOT_EXPIRED,
+};
+use super::message_mention_handlers::store_mentions;
+use crate::server::AppState;
+use actix_web::{get, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow};
+
+// Define a struct to capture the replacement content of a message
+#[derive(Deserialize)]
+struct MessageEdit {
+    sender: String, // Only the original sender may edit a message
+    content: String,
+}
+
+// Define a struct to represent a previous version of a message
+#[derive(Debug, Serialize, FromRow)]
+struct MessageRevision {
+    id: i64,
+    content: String,
+    written_at: Option<DateTime<Utc>>,
+    replaced_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize)]
+struct MessageEditedEvent {
+    table: &'static str,
+    message: MessageResponse,
+}
+
+#[derive(FromRow)]
+struct EditableMessage {
+    sender: String,
+    content: String,
+    timestamp: DateTime<Utc>,
+    edited_at: Option<DateTime<Utc>>,
+    deleted_at: Option<DateTime<Utc>>,
+    kind: String,
+    forwarded_from_id: Option<i32>,
+    format: String,
+}
+
+async fn edit_message(
+    pool: &AppState,
+    table: &'static str,
+    id: i32,
+    edit: &MessageEdit,
+) -> HttpResponse {
+    if edit.content.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Message content cannot be empty");
+    }
+
+    let mut tx = match pool.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("Error starting transaction: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let select_query = format!(
+        "SELECT sender, content, timestamp, edited_at, deleted_at, kind, forwarded_from_id, format \
+         FROM {} WHERE id = ? AND {} FOR UPDATE",
+        table, NOT_EXPIRED
+    );
+    let current = match sqlx::query_as::<_, EditableMessage>(&select_query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+    {
+        Ok(Some(current)) => current,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving message from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    if current.deleted_at.is_some() {
+        return HttpResponse::Gone().body("Message was unsent");
+    }
+    if current.kind != "text" || current.sender != edit.sender {
+        return HttpResponse::Forbidden().body("Only the sender can edit a message");
+    }
+    // A forwarded copy has to keep saying what the original said
+    if current.forwarded_from_id.is_some() {
+        return HttpResponse::Forbidden().body("Forwarded messages cannot be edited");
+    }
+    if current.content == edit.content {
+        return HttpResponse::BadRequest().body("Message content is unchanged");
+    }
+
+    // The replaced version was written when the message was sent or last edited
+    let revision = query(
+        "INSERT INTO message_revisions (message_table, message_id, content, written_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(table)
+    .bind(id)
+    .bind(&current.content)
+    .bind(current.edited_at.unwrap_or(current.timestamp))
+    .execute(&mut *tx)
+    .await;
+    if let Err(e) = revision {
+        eprintln!("Error saving message revision: {}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    let update_query = format!(
+        "UPDATE {} SET content = ?, content_html = ?, edited_at = CURRENT_TIMESTAMP WHERE id = ?",
+        table
+    );
+    if let Err(e) = query(&update_query)
+        .bind(&edit.content)
+        .bind(MessageFormat::parse(&current.format).render(&edit.content))
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+    {
+        eprintln!("Error updating message in '{}' table: {}", table, e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    // Mentions follow the new content, an edit does not notify anyone again
+    if let Err(e) = store_mentions(&mut tx, table, id, &edit.content).await {
+        eprintln!("Error updating mentions in '{}' table: {}", table, e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    if let Err(e) = tx.commit().await {
+        eprintln!("Error committing message edit: {}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    let message = match fetch_message(pool, table, id).await {
+        Ok(Some(message)) => message,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving message from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    update_conversation(pool, table, &message.connected).await;
+
+    match to_responses(pool, table, None, vec![message]).await {
+        Ok(mut responses) => {
+            let message = responses.remove(0);
+            pool.emit_event(
+                "message-edited",
+                MessageEditedEvent {
+                    table,
+                    message: message.clone(),
+                },
+            );
+            HttpResponse::Ok().json(message)
+        }
+        Err(e) => {
+            eprintln!(
+                "Error loading message details from '{}' table: {}",
+                table, e
+            );
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_revisions(pool: &AppState, table: &str, id: i32) -> HttpResponse {
+    let query_str = "
+        SELECT id, content, written_at, replaced_at
+        FROM message_revisions
+        WHERE message_table = ? AND message_id = ?
+        ORDER BY replaced_at DESC, id DESC
+    ";
+
+    match sqlx::query_as::<_, MessageRevision>(query_str)
+        .bind(table)
+        .bind(id)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(revisions) => HttpResponse::Ok().json(revisions),
+        Err(e) => {
+            eprintln!("Error retrieving message revisions: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to edit a message sent to 'my-client'
+#[put("/my/messages/{id}")]
+pub async fn edit_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    edit: web::Json<MessageEdit>,
+) -> impl Responder {
+    edit_message(&pool, "messages_send_to_my_client", id.into_inner(), &edit).await
+}
+
+// Handler function to edit a message sent to 'other-client'
+#[put("/other/messages/{id}")]
+pub async fn edit_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    edit: web::Json<MessageEdit>,
+) -> impl Responder {
+    edit_message(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        &edit,
+    )
+    .await
+}
+
+// Handler function to list the previous versions of a 'my-client' message, newest first
+#[get("/my/messages/{id}/revisions")]
+pub async fn get_message_revisions_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    get_revisions(&pool, "messages_send_to_my_client", id.into_inner()).await
+}
+
+// Handler function to list the previous versions of an 'other-client' message, newest first
+#[get("/other/messages/{id}/revisions")]
+pub async fn get_message_revisions_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    get_revisions(&pool, "messages_send_to_other_client", id.into_inner()).await
+}
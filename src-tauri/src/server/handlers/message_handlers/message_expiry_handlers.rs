@@ -0,0 +1,321 @@
+use super::message_attachment_handlers::purge_attachments;
+use super::message_conversation_handlers::refresh_conversation;
+use crate::server::AppState;
+use actix_web::{get, put, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySql, MySqlConnection, QueryBuilder};
+use std::collections::BTreeMap;
+
+// Bounds of the disappearing messages timer, from half a minute up to a year
+const MIN_EXPIRY_SECONDS: i32 = 30;
+const MAX_EXPIRY_SECONDS: i32 = 365 * 24 * 60 * 60;
+
+// How many messages of one table a purge deletes per transaction
+const PURGE_BATCH_SIZE: i64 = 500;
+
+// Define a struct to represent the disappearing messages setting of a conversation
+#[derive(Debug, Serialize, Deserialize)]
+struct ExpirySetting {
+    expiry_seconds: Option<i32>, // None turns disappearing messages off
+}
+
+#[derive(Clone, Serialize)]
+struct ExpiryEvent {
+    table: &'static str,
+    connected: String,
+    expiry_seconds: Option<i32>,
+}
+
+#[derive(Clone, Serialize)]
+struct PurgedMessagesEvent {
+    table: &'static str,
+    connected: String,
+    ids: Vec<i32>,
+}
+
+#[derive(FromRow)]
+struct PurgedMessage {
+    id: i32,
+    connected: String,
+}
+
+// Define a struct to summarise the messages removed by a purge
+#[derive(Debug, Default, Serialize)]
+pub struct PurgeReport {
+    pub deleted: usize,
+    #[serde(skip)]
+    pub ids: BTreeMap<&'static str, Vec<i32>>, // Deleted message ids per table
+    pub removed_attachments: usize,
+    pub freed_bytes: i64,
+}
+
+async fn get_expiry(pool: &AppState, table: &str, connected: &str) -> HttpResponse {
+    match sqlx::query_scalar::<_, Option<i32>>(
+        "SELECT expiry_seconds FROM conversation_settings WHERE message_table = ? AND connected = ?",
+    )
+    .bind(table)
+    .bind(connected)
+    .fetch_optional(&pool.db_pool)
+    .await
+    {
+        Ok(expiry_seconds) => HttpResponse::Ok().json(ExpirySetting {
+            expiry_seconds: expiry_seconds.flatten(),
+        }),
+        Err(e) => {
+            eprintln!("Error retrieving conversation settings: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Only messages sent after the change pick the new timer up, the expiry of earlier messages
+// stays as it was when they were sent
+async fn set_expiry(
+    pool: &AppState,
+    table: &'static str,
+    connected: &str,
+    setting: &ExpirySetting,
+) -> HttpResponse {
+    if setting
+        .expiry_seconds
+        .is_some_and(|s| !(MIN_EXPIRY_SECONDS..=MAX_EXPIRY_SECONDS).contains(&s))
+    {
+        return HttpResponse::BadRequest().body(format!(
+            "The expiry has to be between {} and {} seconds",
+            MIN_EXPIRY_SECONDS, MAX_EXPIRY_SECONDS
+        ));
+    }
+
+    let result = query(
+        "INSERT INTO conversation_settings (message_table, connected, expiry_seconds) \
+         VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE expiry_seconds = VALUES(expiry_seconds)",
+    )
+    .bind(table)
+    .bind(connected)
+    .bind(setting.expiry_seconds)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(_) => {
+            pool.emit_event(
+                "conversation-expiry-changed",
+                ExpiryEvent {
+                    table,
+                    connected: connected.to_string(),
+                    expiry_seconds: setting.expiry_seconds,
+                },
+            );
+            HttpResponse::Ok().json(setting)
+        }
+        Err(e) => {
+            eprintln!("Error updating conversation settings: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Deletes the rows of a table pointing at one of the given messages
+async fn delete_linked_rows(
+    conn: &mut MySqlConnection,
+    linked_table: &str,
+    table: &str,
+    ids: &[i32],
+) -> Result<(), sqlx::Error> {
+    let mut builder = QueryBuilder::<MySql>::new(format!(
+        "DELETE FROM {} WHERE message_table = ",
+        linked_table
+    ));
+    builder.push_bind(table).push(" AND message_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+    builder.build().execute(&mut *conn).await.map(|_| ())
+}
+
+// Hard deletes one batch of the messages of a table matching `condition` together with
+// everything attached to them, returns the deleted messages and the files they used
+async fn purge_batch(
+    pool: &AppState,
+    table: &'static str,
+    condition: &str,
+) -> Result<(Vec<PurgedMessage>, Vec<String>), sqlx::Error> {
+    let mut tx = pool.db_pool.begin().await?;
+
+    let select_query = format!(
+        "SELECT id, connected FROM {} WHERE {} ORDER BY id LIMIT ? FOR UPDATE",
+        table, condition
+    );
+    let purged = sqlx::query_as::<_, PurgedMessage>(&select_query)
+        .bind(PURGE_BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+    if purged.is_empty() {
+        return Ok((purged, Vec::new()));
+    }
+    let ids: Vec<i32> = purged.iter().map(|m| m.id).collect();
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT DISTINCT hash FROM message_attachments WHERE message_table = ",
+    );
+    builder.push_bind(table).push(" AND message_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in &ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+    let hashes = builder
+        .build_query_scalar::<String>()
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for linked_table in [
+        "message_attachments",
+        "message_reactions",
+        "message_revisions",
+        "group_chat_receipts",
+        "message_mentions",
+        "keyword_alert_hits",
+        "message_hides",
+    ] {
+        delete_linked_rows(&mut tx, linked_table, table, &ids).await?;
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(format!("DELETE FROM {} WHERE id IN (", table));
+    let mut separated = builder.separated(", ");
+    for id in &ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+    builder.build().execute(&mut *tx).await?;
+
+    let mut conversations: Vec<&str> = purged.iter().map(|m| m.connected.as_str()).collect();
+    conversations.sort_unstable();
+    conversations.dedup();
+    for connected in conversations {
+        refresh_conversation(&mut tx, table, connected).await?;
+    }
+
+    tx.commit().await?;
+    Ok((purged, hashes))
+}
+
+// Function to hard delete every message matching `condition`, a fixed SQL fragment on the
+// message columns, along with its reactions, revisions, receipts, mentions and alert hits.
+// Files only the deleted messages used are removed right away instead of waiting for the
+// attachment garbage collection. Every affected conversation gets `event` with the deleted ids.
+pub async fn purge_messages(
+    pool: &AppState,
+    condition: &str,
+    event: &'static str,
+) -> Result<PurgeReport, sqlx::Error> {
+    let mut report = PurgeReport::default();
+    let mut hashes = Vec::new();
+
+    for table in [
+        "messages_send_to_my_client",
+        "messages_send_to_other_client",
+    ] {
+        loop {
+            let (purged, batch_hashes) = purge_batch(pool, table, condition).await?;
+            let last_batch = purged.len() < PURGE_BATCH_SIZE as usize;
+            report.deleted += purged.len();
+            report
+                .ids
+                .entry(table)
+                .or_default()
+                .extend(purged.iter().map(|m| m.id));
+            hashes.extend(batch_hashes);
+
+            let mut events: Vec<PurgedMessagesEvent> = Vec::new();
+            for message in purged {
+                match events.iter_mut().find(|e| e.connected == message.connected) {
+                    Some(existing) => existing.ids.push(message.id),
+                    None => events.push(PurgedMessagesEvent {
+                        table,
+                        connected: message.connected,
+                        ids: vec![message.id],
+                    }),
+                }
+            }
+            for payload in events {
+                pool.emit_event(event, payload);
+            }
+
+            if last_batch {
+                break;
+            }
+        }
+    }
+
+    hashes.sort_unstable();
+    hashes.dedup();
+    let attachments = purge_attachments(pool, &hashes).await?;
+    report.removed_attachments = attachments.removed;
+    report.freed_bytes = attachments.freed_bytes;
+    Ok(report)
+}
+
+// Function to list the ids of the messages a purge with `condition` would delete, per table
+pub async fn matching_message_ids(
+    pool: &AppState,
+    condition: &str,
+) -> Result<BTreeMap<&'static str, Vec<i32>>, sqlx::Error> {
+    let mut ids = BTreeMap::new();
+    for table in [
+        "messages_send_to_my_client",
+        "messages_send_to_other_client",
+    ] {
+        let query_str = format!("SELECT id FROM {} WHERE {} ORDER BY id", table, condition);
+        let table_ids = sqlx::query_scalar::<_, i32>(&query_str)
+            .fetch_all(&pool.db_pool)
+            .await?;
+        ids.insert(table, table_ids);
+    }
+    Ok(ids)
+}
+
+// Function to delete every disappearing message that is due
+pub async fn sweep_expired_messages(pool: &AppState) -> Result<PurgeReport, sqlx::Error> {
+    purge_messages(pool, "expires_at <= CURRENT_TIMESTAMP", "messages-expired").await
+}
+
+// Handler function to get the disappearing messages timer of a 'my-client' conversation
+#[get("/my/conversations/{connected}/expiry")]
+pub async fn get_conversation_expiry_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    get_expiry(&pool, "messages_send_to_my_client", &connected).await
+}
+
+// Handler function to get the disappearing messages timer of an 'other-client' conversation
+#[get("/other/conversations/{connected}/expiry")]
+pub async fn get_conversation_expiry_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    get_expiry(&pool, "messages_send_to_other_client", &connected).await
+}
+
+// Handler function to set or clear the disappearing messages timer of a 'my-client' conversation
+#[put("/my/conversations/{connected}/expiry")]
+pub async fn set_conversation_expiry_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    setting: web::Json<ExpirySetting>,
+) -> impl Responder {
+    set_expiry(&pool, "messages_send_to_my_client", &connected, &setting).await
+}
+
+// Handler function to set or clear the disappearing messages timer of an 'other-client' conversation
+#[put("/other/conversations/{connected}/expiry")]
+pub async fn set_conversation_expiry_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    setting: web::Json<ExpirySetting>,
+) -> impl Responder {
+    set_expiry(&pool, "messages_send_to_other_client", &connected, &setting).await
+}
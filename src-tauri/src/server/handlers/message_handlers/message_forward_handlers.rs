@@ -0,0 +1,287 @@
+use super::message_conversation_handlers::update_conversation;
+use super::message_get_set_handlers::{
+    check_participants, fetch_hidden_ids, SendError, EXPIRY_FOR_NEW_MESSAGE, NOT_EXPIRED,
+};
+use super::message_mention_handlers::scan_new_message;
+use crate::server::AppState;
+use actix_web::{post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySql, MySqlConnection, QueryBuilder};
+
+const MAX_FORWARDED_MESSAGES: usize = 50;
+
+// Define the channels messages can be forwarded to
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    My,
+    Other,
+}
+
+impl Channel {
+    fn table(self) -> &'static str {
+        match self {
+            Channel::My => "messages_send_to_my_client",
+            Channel::Other => "messages_send_to_other_client",
+        }
+    }
+}
+
+// Define a struct to capture messages passed along to another conversation
+#[derive(Deserialize)]
+pub struct ForwardRequest {
+    pub ids: Vec<i32>,
+    pub target: Channel, // "my" or "other", the channel of the conversation forwarded to
+    pub sender: String,
+    pub receiver: String,
+    pub connected: String,
+    pub comment: Option<String>, // Sent as a message of its own after the forwarded ones
+}
+
+// Define a struct to represent the outcome of a forward for API responses
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardResult {
+    ids: Vec<i32>, // The copies, in the order of the originals
+    comment_id: Option<i32>,
+}
+
+#[derive(Clone, Serialize)]
+struct ForwardEvent {
+    table: &'static str,
+    connected: String,
+    source_table: &'static str,
+    #[serde(flatten)]
+    result: ForwardResult,
+}
+
+#[derive(FromRow)]
+struct SourceMessage {
+    id: i32,
+    sender: String,
+    content: String,
+    deleted_at: Option<DateTime<Utc>>,
+    kind: String,
+    format: String,
+    content_html: Option<String>,
+    forwarded_from_table: Option<String>,
+    forwarded_from_id: Option<i32>,
+    forwarded_from_sender: Option<String>,
+}
+
+// Define a struct to represent one message stored by a forward, a copy or the comment
+struct ForwardedMessage<'a> {
+    content: &'a str,
+    format: &'a str,
+    content_html: Option<&'a str>,
+    forwarded_from: (Option<&'a str>, Option<i32>, Option<&'a str>), // table, id, sender
+}
+
+async fn fetch_sources(
+    pool: &AppState,
+    table: &str,
+    ids: &[i32],
+) -> Result<Vec<SourceMessage>, sqlx::Error> {
+    let mut builder = QueryBuilder::<MySql>::new(format!(
+        "SELECT id, sender, content, deleted_at, kind, format, content_html, \
+         forwarded_from_table, forwarded_from_id, forwarded_from_sender FROM {} WHERE {} AND id IN (",
+        table, NOT_EXPIRED
+    ));
+    let mut separated = builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(") ORDER BY timestamp, id");
+    builder
+        .build_query_as::<SourceMessage>()
+        .fetch_all(&pool.db_pool)
+        .await
+}
+
+// Stores one forwarded copy or the comment, returns the id of the new message
+async fn insert_forwarded(
+    conn: &mut MySqlConnection,
+    table: &str,
+    forward: &ForwardRequest,
+    message: &ForwardedMessage<'_>,
+) -> Result<i32, sqlx::Error> {
+    let insert_query = format!(
+        "INSERT INTO {} (sender, receiver, content, format, content_html, connected, \
+         forwarded_from_table, forwarded_from_id, forwarded_from_sender, expires_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, {})",
+        table, EXPIRY_FOR_NEW_MESSAGE
+    );
+    let (from_table, from_id, from_sender) = message.forwarded_from;
+    let result = query(&insert_query)
+        .bind(&forward.sender)
+        .bind(&forward.receiver)
+        .bind(message.content)
+        .bind(message.format)
+        .bind(message.content_html)
+        .bind(&forward.connected)
+        .bind(from_table)
+        .bind(from_id)
+        .bind(from_sender)
+        .bind(table)
+        .bind(&forward.connected)
+        .execute(&mut *conn)
+        .await?;
+    Ok(result.last_insert_id() as i32)
+}
+
+// Function to copy the messages in one transaction. Forwarding a forwarded message points
+// back to the first original, attachments are linked again without copying the files. Labels,
+// replies and reactions stay behind with the originals.
+pub async fn forward_messages(
+    pool: &AppState,
+    source_table: &'static str,
+    forward: &ForwardRequest,
+) -> Result<ForwardResult, SendError> {
+    let mut ids = forward.ids.clone();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() || ids.len() > MAX_FORWARDED_MESSAGES {
+        return Err(SendError::BadRequest(format!(
+            "Between 1 and {} messages can be forwarded at once",
+            MAX_FORWARDED_MESSAGES
+        )));
+    }
+
+    // The copies are stored like sent messages and are checked the same way
+    check_participants(&forward.sender, &forward.receiver, &forward.connected)
+        .map_err(SendError::BadRequest)?;
+    let comment = forward
+        .comment
+        .as_deref()
+        .map(str::trim)
+        .filter(|comment| !comment.is_empty());
+
+    // Messages the forwarder deleted for themselves count as gone for them
+    let sources = fetch_sources(pool, source_table, &ids).await?;
+    let hidden = fetch_hidden_ids(pool, source_table, &forward.sender, &ids).await?;
+    if sources.len() != ids.len() {
+        return Err(SendError::NotFound(
+            "Some of the messages do not exist".to_string(),
+        ));
+    }
+    if sources
+        .iter()
+        .any(|m| m.kind != "text" || hidden.contains(&m.id) || m.deleted_at.is_some())
+    {
+        return Err(SendError::BadRequest(
+            "Unsent, hidden and group membership messages cannot be forwarded".to_string(),
+        ));
+    }
+
+    let table = forward.target.table();
+    let mut tx = pool.db_pool.begin().await?;
+
+    let mut copies = Vec::with_capacity(sources.len());
+    for source in &sources {
+        let copy = ForwardedMessage {
+            content: &source.content,
+            format: &source.format,
+            content_html: source.content_html.as_deref(),
+            forwarded_from: match source.forwarded_from_id {
+                Some(_) => (
+                    source.forwarded_from_table.as_deref(),
+                    source.forwarded_from_id,
+                    source.forwarded_from_sender.as_deref(),
+                ),
+                None => (Some(source_table), Some(source.id), Some(&source.sender)),
+            },
+        };
+        let id = insert_forwarded(&mut tx, table, forward, &copy).await?;
+
+        query(
+            "INSERT INTO message_attachments (message_table, message_id, hash, file_name, position) \
+             SELECT ?, ?, hash, file_name, position FROM message_attachments \
+             WHERE message_table = ? AND message_id = ?",
+        )
+        .bind(table)
+        .bind(id)
+        .bind(source_table)
+        .bind(source.id)
+        .execute(&mut *tx)
+        .await?;
+        copies.push(id);
+    }
+
+    let comment_id = match comment {
+        Some(comment) => {
+            let comment = ForwardedMessage {
+                content: comment,
+                format: "plain",
+                content_html: None,
+                forwarded_from: (None, None, None),
+            };
+            Some(insert_forwarded(&mut tx, table, forward, &comment).await?)
+        }
+        None => None,
+    };
+
+    tx.commit().await?;
+    let result = ForwardResult {
+        ids: copies,
+        comment_id,
+    };
+
+    update_conversation(pool, table, &forward.connected).await;
+    // The copies and the comment are new messages of the target conversation, their
+    // mentions and keyword alerts are resolved like those of any sent message
+    let scanned = sources
+        .iter()
+        .map(|source| source.content.as_str())
+        .zip(&result.ids)
+        .chain(comment.zip(result.comment_id.as_ref()));
+    for (content, id) in scanned {
+        scan_new_message(
+            pool,
+            table,
+            *id,
+            &forward.sender,
+            &forward.connected,
+            content,
+        )
+        .await;
+    }
+    pool.emit_event(
+        "messages-forwarded",
+        ForwardEvent {
+            table,
+            connected: forward.connected.clone(),
+            source_table,
+            result: result.clone(),
+        },
+    );
+    Ok(result)
+}
+
+async fn forward_to_response(
+    pool: &AppState,
+    source_table: &'static str,
+    forward: &ForwardRequest,
+) -> HttpResponse {
+    match forward_messages(pool, source_table, forward).await {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(e) => e.to_response(forward.target.table()),
+    }
+}
+
+// Handler function to forward 'my-client' messages to a conversation of either channel
+#[post("/my/forward")]
+pub async fn forward_messages_my_client(
+    pool: web::Data<AppState>,
+    forward: web::Json<ForwardRequest>,
+) -> impl Responder {
+    forward_to_response(&pool, "messages_send_to_my_client", &forward).await
+}
+
+// Handler function to forward 'other-client' messages to a conversation of either channel
+#[post("/other/forward")]
+pub async fn forward_messages_other_client(
+    pool: web::Data<AppState>,
+    forward: web::Json<ForwardRequest>,
+) -> impl Responder {
+    forward_to_response(&pool, "messages_send_to_other_client", &forward).await
+}
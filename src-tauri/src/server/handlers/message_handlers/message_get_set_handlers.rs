@@ -1,137 +1,819 @@
+use super::message_attachment_handlers::{
+    fetch_attachments, link_attachments, validate_attachment_refs, AttachmentInfo, AttachmentRef,
+};
+use super::message_collapse_handlers::{collapse_repeat, CollapsedMessage};
+use super::message_conversation_handlers::update_conversation;
+use super::message_draft_handlers::discard_draft;
+use super::message_group_chat_handlers::{is_group_conversation, GROUP_CONVERSATION_REFUSED};
+use super::message_mention_handlers::{fetch_mentions, scan_new_message, Mention};
+use super::message_pin_handlers::fetch_pinned_ids;
+use super::message_reaction_handlers::{fetch_reactions, ReactionSummary};
+use super::message_rule_handlers::apply_message_rules;
+use super::message_scheduled_handlers::schedule_message;
+use crate::server::markdown::render_markdown;
 use crate::server::AppState;
 use actix_web::{get, post, web, HttpResponse, Responder};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{query, FromRow};
+use sqlx::{query, FromRow, MySql, MySqlConnection, QueryBuilder};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// Columns selected for every message, in the order of the Message struct
+pub const MESSAGE_COLUMNS: &str = "id, sender, receiver, content, timestamp, close_one_point, \
+     connected, edited_at, deleted_at, delivered_at, read_at, reply_to, thread_root_id, kind, \
+     expires_at, counter, starred_at, pinned_at, forwarded_from_table, forwarded_from_id, \
+     forwarded_from_sender, format, content_html";
+
+// Condition keeping disappearing messages out of every read once they are due, even before
+// the sweeper got to delete them
+pub const NOT_EXPIRED: &str = "(expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)";
+
+// Expiry of a message stored right now in a conversation, NULL unless the conversation has a
+// disappearing messages timer. Binds the message table and the conversation.
+pub const EXPIRY_FOR_NEW_MESSAGE: &str =
+    "(SELECT CURRENT_TIMESTAMP + INTERVAL s.expiry_seconds SECOND \
+     FROM conversation_settings s WHERE s.message_table = ? AND s.connected = ?)";
 
 // Define the Message struct to use with database queries
 #[derive(Debug, FromRow)]
-struct Message {
+pub struct Message {
+    pub id: i32,
+    pub sender: String,
+    pub receiver: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    pub close_one_point: Option<String>,
+    pub connected: String,
+    pub edited_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub hidden: bool, // Deleted for me by whoever is viewing, set by to_responses
+    pub deleted_at: Option<DateTime<Utc>>, // Unsent by the sender, kept as a tombstone
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub read_at: Option<DateTime<Utc>>,
+    pub reply_to: Option<i32>,
+    pub thread_root_id: Option<i32>, // The first message of the thread, None for roots
+    pub kind: String,                // "text", or "system" for group membership changes
+    pub expires_at: Option<DateTime<Utc>>, // Set for disappearing messages
+    pub counter: Option<i32>,        // How many identical messages were collapsed into this one
+    pub starred_at: Option<DateTime<Utc>>,
+    pub pinned_at: Option<DateTime<Utc>>,
+    pub forwarded_from_table: Option<String>, // Where the original of a forwarded copy was sent
+    pub forwarded_from_id: Option<i32>,
+    pub forwarded_from_sender: Option<String>,
+    pub format: String,               // "plain" or "markdown"
+    pub content_html: Option<String>, // Sanitised rendering of markdown content
+}
+
+// Define a struct to represent a message record for API responses
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageResponse {
     id: i32,
     sender: String,
     receiver: String,
     content: String,
-    timestamp: DateTime<Utc>,
+    format: String,
+    content_html: Option<String>, // Safe to insert as HTML, only set for markdown
+    timestamp: String,            // Change to String for API responses
     close_one_point: Option<String>,
     connected: String,
+    edited: bool,
+    edited_at: Option<String>,
+    deletion: Option<&'static str>, // "hidden" or "unsent" for tombstones
+    status: &'static str,           // "sent", "delivered" or "read"
+    delivered_at: Option<String>,
+    read_at: Option<String>,
+    reply_to: Option<i32>,
+    kind: String,
+    expires_at: Option<String>,
+    count: i32,
+    starred: bool,
+    pinned: bool,
+    forwarded_from: Option<ForwardedFrom>,
+    mentions: Vec<Mention>, // Contacts mentioned as @nick in the content
+    quoted: Option<QuotedMessage>, // Compact preview of the message replied to
+    reactions: Vec<ReactionSummary>,
+    attachments: Vec<AttachmentInfo>,
 }
 
-// Define a struct to represent a message record for API responses
-#[derive(Debug, Serialize)]
-struct MessageResponse {
+// Define a struct to represent the quoted parent of a reply
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotedMessage {
+    id: i32,
+    sender: String,
+    preview: String,
+    deleted: bool,
+}
+
+// Define a struct to represent the original of a forwarded message, it may be gone by now
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardedFrom {
+    table: String,
     id: i32,
     sender: String,
-    receiver: String,
-    content: String,
-    timestamp: String, // Change to String for API responses
-    close_one_point: Option<String>,
-    connected: String,
 }
 
+const QUOTE_PREVIEW_CHARS: usize = 100;
+
 impl Message {
-    fn to_response(&self) -> MessageResponse {
+    pub fn to_response(&self) -> MessageResponse {
         MessageResponse {
             id: self.id,
             sender: self.sender.clone(),
             receiver: self.receiver.clone(),
-            content: self.content.clone(),
+            // Tombstones keep their place in the history but never their content
+            content: match self.deletion() {
+                Some(_) => String::new(),
+                None => self.content.clone(),
+            },
+            format: self.format.clone(),
+            content_html: match self.deletion() {
+                Some(_) => None,
+                None => self.content_html.clone(),
+            },
             timestamp: self.timestamp.to_rfc3339(), // Convert to string in RFC 3339 format
             close_one_point: self.close_one_point.clone(),
             connected: self.connected.clone(),
+            edited: self.edited_at.is_some(),
+            edited_at: self.edited_at.map(|t| t.to_rfc3339()),
+            deletion: self.deletion(),
+            status: self.status(),
+            delivered_at: self.delivered_at.map(|t| t.to_rfc3339()),
+            read_at: self.read_at.map(|t| t.to_rfc3339()),
+            reply_to: self.reply_to,
+            kind: self.kind.clone(),
+            expires_at: self.expires_at.map(|t| t.to_rfc3339()),
+            count: self.counter.unwrap_or(1),
+            starred: self.starred_at.is_some(),
+            pinned: self.pinned_at.is_some(),
+            forwarded_from: match (
+                &self.forwarded_from_table,
+                self.forwarded_from_id,
+                &self.forwarded_from_sender,
+            ) {
+                (Some(table), Some(id), Some(sender)) => Some(ForwardedFrom {
+                    table: table.clone(),
+                    id,
+                    sender: sender.clone(),
+                }),
+                _ => None,
+            },
+            mentions: Vec::new(),
+            quoted: None,
+            reactions: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    fn to_quote(&self) -> QuotedMessage {
+        let deleted = self.deletion().is_some();
+        QuotedMessage {
+            id: self.id,
+            sender: self.sender.clone(),
+            preview: if deleted {
+                String::new()
+            } else {
+                self.content.chars().take(QUOTE_PREVIEW_CHARS).collect()
+            },
+            deleted,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        if self.read_at.is_some() {
+            "read"
+        } else if self.delivered_at.is_some() {
+            "delivered"
+        } else {
+            "sent"
+        }
+    }
+
+    fn deletion(&self) -> Option<&'static str> {
+        if self.deleted_at.is_some() {
+            Some("unsent")
+        } else if self.hidden {
+            Some("hidden")
+        } else {
+            None
         }
     }
 }
 
-#[get("/my/get/{connected}")]
-pub async fn get_messages_my_client(
-    pool: web::Data<AppState>,
-    connected: web::Path<String>,
-) -> impl Responder {
-    let query_str = "SELECT id, sender, receiver, content, timestamp, close_one_point, connected FROM messages_send_to_my_client WHERE connected = ? ORDER BY timestamp DESC";
+// Function to load a single message from one of the message tables
+pub async fn fetch_message(
+    pool: &AppState,
+    table: &str,
+    id: i32,
+) -> Result<Option<Message>, sqlx::Error> {
+    let query_str = format!(
+        "SELECT {} FROM {} WHERE id = ? AND {}",
+        MESSAGE_COLUMNS, table, NOT_EXPIRED
+    );
+    sqlx::query_as::<_, Message>(&query_str)
+        .bind(id)
+        .fetch_optional(&pool.db_pool)
+        .await
+}
+
+// Function to find which of the given messages the viewer deleted for themselves
+pub async fn fetch_hidden_ids(
+    pool: &AppState,
+    table: &str,
+    viewer: &str,
+    ids: &[i32],
+) -> Result<HashSet<i32>, sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let mut builder =
+        QueryBuilder::<MySql>::new("SELECT message_id FROM message_hides WHERE message_table = ");
+    builder
+        .push_bind(table)
+        .push(" AND viewer = ")
+        .push_bind(viewer)
+        .push(" AND message_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+
+    let hidden = builder
+        .build_query_scalar::<i32>()
+        .fetch_all(&pool.db_pool)
+        .await?;
+    Ok(hidden.into_iter().collect())
+}
+
+// Function to turn message rows into API responses, with the quoted parents of replies,
+// the aggregated reactions, the attached files and the mentioned contacts. Messages the
+// viewer deleted for themselves come back as tombstones, without a viewer nothing is hidden
+pub async fn to_responses(
+    pool: &AppState,
+    table: &str,
+    viewer: Option<&str>,
+    mut messages: Vec<Message>,
+) -> Result<Vec<MessageResponse>, sqlx::Error> {
+    let mut parent_ids: Vec<i32> = messages.iter().filter_map(|m| m.reply_to).collect();
+    parent_ids.sort_unstable();
+    parent_ids.dedup();
+
+    let hidden = match viewer {
+        Some(viewer) => {
+            let ids: Vec<i32> = messages
+                .iter()
+                .map(|m| m.id)
+                .chain(parent_ids.iter().copied())
+                .collect();
+            fetch_hidden_ids(pool, table, viewer, &ids).await?
+        }
+        None => HashSet::new(),
+    };
+    for message in &mut messages {
+        message.hidden = hidden.contains(&message.id);
+    }
+
+    let mut quotes = HashMap::new();
+    if !parent_ids.is_empty() {
+        let mut builder = QueryBuilder::<MySql>::new(format!(
+            "SELECT {} FROM {} WHERE {} AND id IN (",
+            MESSAGE_COLUMNS, table, NOT_EXPIRED
+        ));
+        let mut separated = builder.separated(", ");
+        for id in &parent_ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+
+        for parent in builder
+            .build_query_as::<Message>()
+            .fetch_all(&pool.db_pool)
+            .await?
+        {
+            let parent = Message {
+                hidden: hidden.contains(&parent.id),
+                ..parent
+            };
+            quotes.insert(parent.id, parent.to_quote());
+        }
+    }
+
+    let message_ids: Vec<i32> = messages.iter().map(|m| m.id).collect();
+    let mut reactions = fetch_reactions(pool, table, &message_ids).await?;
+    let mut attachments = fetch_attachments(pool, table, &message_ids).await?;
+    let mut mentions = fetch_mentions(pool, table, &message_ids).await?;
 
-    match sqlx::query_as::<_, Message>(query_str)
-        .bind(connected.as_str())
+    Ok(messages
+        .iter()
+        .map(|message| {
+            let mut response = message.to_response();
+            response.quoted = message.reply_to.and_then(|id| quotes.get(&id).cloned());
+            response.reactions = reactions.remove(&message.id).unwrap_or_default();
+            // Files and mentions of tombstones stay hidden just like their content
+            if message.deletion().is_none() {
+                response.attachments = attachments.remove(&message.id).unwrap_or_default();
+                response.mentions = mentions.remove(&message.id).unwrap_or_default();
+            }
+            response
+        })
+        .collect())
+}
+
+// Define the query parameters accepted by the message listing
+#[derive(Deserialize)]
+struct MessageQuery {
+    label: Option<String>,  // Only messages with this close_one_point label
+    viewer: Option<String>, // Whose deleted for me messages come back as tombstones
+}
+
+async fn list_messages(
+    pool: &AppState,
+    table: &str,
+    connected: &str,
+    params: &MessageQuery,
+) -> HttpResponse {
+    if is_group_conversation(connected) {
+        return HttpResponse::BadRequest().body(GROUP_CONVERSATION_REFUSED);
+    }
+
+    let query_str = format!(
+        "SELECT {} FROM {} WHERE connected = ? AND (? IS NULL OR close_one_point = ?) AND {} \
+         ORDER BY timestamp DESC, id DESC",
+        MESSAGE_COLUMNS, table, NOT_EXPIRED
+    );
+
+    let messages = match sqlx::query_as::<_, Message>(&query_str)
+        .bind(connected)
+        .bind(&params.label)
+        .bind(&params.label)
         .fetch_all(&pool.db_pool)
         .await
     {
-        Ok(messages) => {
-            let response: Vec<MessageResponse> =
-                messages.into_iter().map(|m| m.to_response()).collect();
-            HttpResponse::Ok().json(response)
+        Ok(messages) => messages,
+        Err(e) => {
+            eprintln!("Error retrieving messages from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
         }
+    };
+
+    match to_responses(pool, table, params.viewer.as_deref(), messages).await {
+        Ok(response) => HttpResponse::Ok().json(response),
         Err(e) => {
-            eprintln!("Error retrieving messages from 'my-client' table: {}", e);
+            eprintln!(
+                "Error loading message details from '{}' table: {}",
+                table, e
+            );
             HttpResponse::InternalServerError().finish()
         }
     }
 }
 
+#[get("/my/get/{connected}")]
+pub async fn get_messages_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    params: web::Query<MessageQuery>,
+) -> impl Responder {
+    list_messages(&pool, "messages_send_to_my_client", &connected, &params).await
+}
+
 #[get("/other/get/{connected}")]
 pub async fn get_messages_other_client(
     pool: web::Data<AppState>,
     connected: web::Path<String>,
+    params: web::Query<MessageQuery>,
 ) -> impl Responder {
-    let query_str = "
-        SELECT id, sender, receiver, content, timestamp, close_one_point, connected 
-        FROM messages_send_to_other_client 
-        WHERE connected = ? 
-        ORDER BY timestamp DESC
-    ";
-
-    match sqlx::query_as::<_, Message>(query_str)
-        .bind(connected.as_str())
-        .fetch_all(&pool.db_pool)
-        .await
-    {
-        Ok(messages) => {
-            let response: Vec<MessageResponse> =
-                messages.into_iter().map(|m| m.to_response()).collect();
-            HttpResponse::Ok().json(response)
-        }
+    list_messages(&pool, "messages_send_to_other_client", &connected, &params).await
+}
+
+pub const DEFAULT_HISTORY_LIMIT: u32 = 50;
+pub const MAX_HISTORY_LIMIT: u32 = 200;
+
+// Define the query parameters accepted by the paginated history
+#[derive(Deserialize)]
+struct HistoryQuery {
+    before: Option<i32>, // Only messages older than this one, the newest page when missing
+    limit: Option<u32>,
+    label: Option<String>,
+    viewer: Option<String>, // Whose deleted for me messages come back as tombstones
+}
+
+// Define a struct to represent one page of a conversation for API responses
+#[derive(Serialize)]
+struct HistoryPage {
+    messages: Vec<MessageResponse>,
+    pinned_ids: Vec<i32>, // Pinned messages of the conversation, shown above the history
+    next_before: Option<i32>, // Cursor of the next older page, None on the last page
+}
+
+// Pages in the same order as the message listing, by timestamp and then id. A collapsed repeat
+// keeps its id but moves to the time of the latest copy, paging by id alone would put it in the
+// wrong page.
+async fn get_history(
+    pool: &AppState,
+    table: &str,
+    connected: &str,
+    params: &HistoryQuery,
+) -> HttpResponse {
+    if is_group_conversation(connected) {
+        return HttpResponse::BadRequest().body(GROUP_CONVERSATION_REFUSED);
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .clamp(1, MAX_HISTORY_LIMIT);
+    let query_str = format!(
+        "SELECT {} FROM {} WHERE connected = ? \
+         AND (? IS NULL OR timestamp < COALESCE(?, timestamp) \
+              OR (timestamp = COALESCE(?, timestamp) AND id < ?)) \
+         AND (? IS NULL OR close_one_point = ?) AND {} \
+         ORDER BY timestamp DESC, id DESC LIMIT ?",
+        MESSAGE_COLUMNS, table, NOT_EXPIRED
+    );
+    let cursor_query = format!("SELECT timestamp FROM {} WHERE id = ?", table);
+
+    let result = async {
+        // A cursor message that was deleted since falls back to the messages before its id
+        let cursor_timestamp = match params.before {
+            Some(before) => {
+                sqlx::query_scalar::<_, DateTime<Utc>>(&cursor_query)
+                    .bind(before)
+                    .fetch_optional(&pool.db_pool)
+                    .await?
+            }
+            None => None,
+        };
+        let messages = sqlx::query_as::<_, Message>(&query_str)
+            .bind(connected)
+            .bind(params.before)
+            .bind(cursor_timestamp)
+            .bind(cursor_timestamp)
+            .bind(params.before)
+            .bind(&params.label)
+            .bind(&params.label)
+            .bind(limit)
+            .fetch_all(&pool.db_pool)
+            .await?;
+        let next_before = match messages.last() {
+            Some(oldest) if messages.len() == limit as usize => Some(oldest.id),
+            _ => None,
+        };
+        Ok::<_, sqlx::Error>(HistoryPage {
+            messages: to_responses(pool, table, params.viewer.as_deref(), messages).await?,
+            pinned_ids: fetch_pinned_ids(pool, table, connected).await?,
+            next_before,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(page) => HttpResponse::Ok().json(page),
         Err(e) => {
-            eprintln!("Error retrieving messages from 'other-client' table: {}", e);
+            eprintln!("Error retrieving history from '{}' table: {}", table, e);
             HttpResponse::InternalServerError().finish()
         }
     }
 }
 
-// Define a struct to capture the message payload from the request
-#[derive(Deserialize)]
-struct NewMessage {
-    sender: String,
-    receiver: String,
-    content: String,
-    close_one_point: Option<String>, // Optional field
-    connected: String,               // Optional field to track conversation partner
+// Handler function to get one page of a 'my-client' conversation, newest first
+#[get("/my/conversations/{connected}/history")]
+pub async fn get_history_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    params: web::Query<HistoryQuery>,
+) -> impl Responder {
+    get_history(&pool, "messages_send_to_my_client", &connected, &params).await
 }
 
-// Handler function to send a message to 'my-client'
-#[post("/my/send/")]
-pub async fn send_message_my_client(
+// Handler function to get one page of an 'other-client' conversation, newest first
+#[get("/other/conversations/{connected}/history")]
+pub async fn get_history_other_client(
     pool: web::Data<AppState>,
-    new_message: web::Json<NewMessage>,
+    connected: web::Path<String>,
+    params: web::Query<HistoryQuery>,
 ) -> impl Responder {
-    let query_str = "
-        INSERT INTO messages_send_to_my_client (sender, receiver, content, close_one_point, connected)
-        VALUES (?, ?, ?, ?, ?)
-    ";
+    get_history(&pool, "messages_send_to_other_client", &connected, &params).await
+}
 
-    let result = query(query_str)
+// Define the formats message content can be written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    #[default]
+    Plain,
+    Markdown,
+}
+
+impl MessageFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageFormat::Plain => "plain",
+            MessageFormat::Markdown => "markdown",
+        }
+    }
+
+    pub fn parse(format: &str) -> MessageFormat {
+        match format {
+            "markdown" => MessageFormat::Markdown,
+            _ => MessageFormat::Plain,
+        }
+    }
+
+    // Function to render content to the HTML stored alongside it, plain text has none and is
+    // always shown as text
+    pub fn render(&self, content: &str) -> Option<String> {
+        match self {
+            MessageFormat::Plain => None,
+            MessageFormat::Markdown => Some(render_markdown(content)),
+        }
+    }
+}
+
+// Define a struct to capture the message payload from the request
+#[derive(Deserialize)]
+pub struct NewMessage {
+    pub sender: String,
+    pub receiver: String,
+    pub content: String,
+    pub close_one_point: Option<String>, // Optional field
+    pub connected: String,               // Optional field to track conversation partner
+    pub reply_to: Option<i32>,           // Optional parent message in the same conversation
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>, // Files uploaded beforehand through /attachments
+    #[serde(default)]
+    pub send_at: Option<DateTime<Utc>>, // Deliver later instead of right away
+    #[serde(default)]
+    pub format: MessageFormat, // Plain text unless markdown is asked for
+}
+
+// Define the reasons a new message is refused
+#[derive(Debug)]
+pub enum SendError {
+    BadRequest(String),
+    NotFound(String),
+    AttachmentNotUploaded(String), // Hash of a file that was never uploaded
+    Database(sqlx::Error),
+}
+
+impl SendError {
+    pub fn to_response(&self, table: &str) -> HttpResponse {
+        match self {
+            SendError::BadRequest(reason) => HttpResponse::BadRequest().body(reason.clone()),
+            SendError::NotFound(reason) => HttpResponse::NotFound().body(reason.clone()),
+            SendError::AttachmentNotUploaded(_) => {
+                HttpResponse::BadRequest().body(self.to_string())
+            }
+            SendError::Database(e) => {
+                eprintln!("Error inserting message into '{}' table: {}", table, e);
+                HttpResponse::InternalServerError().finish()
+            }
+        }
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::BadRequest(reason) | SendError::NotFound(reason) => write!(f, "{}", reason),
+            SendError::AttachmentNotUploaded(hash) => {
+                write!(f, "Attachment {} was not uploaded", hash)
+            }
+            SendError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SendError {
+    fn from(e: sqlx::Error) -> Self {
+        SendError::Database(e)
+    }
+}
+
+// Function to map a stored message table name back to the table, only known names are
+// ever formatted into queries
+pub fn known_message_table(name: &str) -> Option<&'static str> {
+    match name {
+        "messages_send_to_my_client" => Some("messages_send_to_my_client"),
+        "messages_send_to_other_client" => Some("messages_send_to_other_client"),
+        _ => None,
+    }
+}
+
+#[derive(FromRow)]
+struct ReplyParent {
+    id: i32,
+    connected: String,
+    thread_root_id: Option<i32>,
+}
+
+// Function to check who a message is between, for every path storing one-to-one messages.
+// The conversation is the partner, so it has to be the sender or the receiver.
+pub fn check_participants(sender: &str, receiver: &str, connected: &str) -> Result<(), String> {
+    // Group messages go through the chat endpoints, which check the membership
+    if is_group_conversation(connected) {
+        return Err(GROUP_CONVERSATION_REFUSED.to_string());
+    }
+    if [sender, receiver, connected]
+        .iter()
+        .any(|id| id.trim().is_empty())
+    {
+        return Err("Messages need a sender, a receiver and a conversation".to_string());
+    }
+    if connected != sender && connected != receiver {
+        return Err("The conversation has to be the sender or the receiver".to_string());
+    }
+    Ok(())
+}
+
+// Function to check a new message before it is stored or scheduled, returns the thread a
+// reply belongs to. The replied message has to be part of the same conversation.
+pub async fn check_new_message(
+    pool: &AppState,
+    table: &str,
+    new_message: &NewMessage,
+) -> Result<Option<i32>, SendError> {
+    check_participants(
+        &new_message.sender,
+        &new_message.receiver,
+        &new_message.connected,
+    )
+    .map_err(SendError::BadRequest)?;
+    validate_attachment_refs(&new_message.attachments).map_err(SendError::BadRequest)?;
+
+    let parent_id = match new_message.reply_to {
+        Some(parent_id) => parent_id,
+        None => return Ok(None),
+    };
+
+    let query_str = format!(
+        "SELECT id, connected, thread_root_id FROM {} WHERE id = ? AND {}",
+        table, NOT_EXPIRED
+    );
+    match sqlx::query_as::<_, ReplyParent>(&query_str)
+        .bind(parent_id)
+        .fetch_optional(&pool.db_pool)
+        .await
+        .map_err(SendError::Database)?
+    {
+        Some(parent) if parent.connected == new_message.connected => {
+            Ok(Some(parent.thread_root_id.unwrap_or(parent.id)))
+        }
+        Some(_) => Err(SendError::BadRequest(
+            "The replied message belongs to another conversation".to_string(),
+        )),
+        None => Err(SendError::NotFound(
+            "The replied message does not exist".to_string(),
+        )),
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct CollapsedEvent {
+    table: &'static str,
+    #[serde(flatten)]
+    collapsed: CollapsedMessage,
+}
+
+// Define a struct to represent a message that was just stored
+pub struct StoredMessage {
+    pub id: i32,
+    pub collapsed: Option<CollapsedMessage>, // Set when it was folded into the last message
+}
+
+// Function to insert a checked message and link its attachments on the given connection, a
+// repeat of the last message is folded into it instead. The caller owns the transaction and
+// announces the message with `announce_message` once it is committed.
+pub async fn store_message(
+    conn: &mut MySqlConnection,
+    table: &'static str,
+    new_message: &NewMessage,
+    thread_root_id: Option<i32>,
+) -> Result<StoredMessage, SendError> {
+    if let Some(collapsed) = collapse_repeat(&mut *conn, table, new_message).await? {
+        return Ok(StoredMessage {
+            id: collapsed.id,
+            collapsed: Some(collapsed),
+        });
+    }
+
+    let query_str = format!(
+        "INSERT INTO {} (sender, receiver, content, close_one_point, connected, reply_to, thread_root_id, \
+         format, content_html, expires_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, {})",
+        table, EXPIRY_FOR_NEW_MESSAGE
+    );
+
+    let result = query(&query_str)
         .bind(&new_message.sender)
         .bind(&new_message.receiver)
         .bind(&new_message.content)
         .bind(&new_message.close_one_point)
         .bind(&new_message.connected)
-        .execute(&pool.db_pool)
+        .bind(new_message.reply_to)
+        .bind(thread_root_id)
+        .bind(new_message.format.as_str())
+        .bind(new_message.format.render(&new_message.content))
+        .bind(table)
+        .bind(&new_message.connected)
+        .execute(&mut *conn)
+        .await?;
+
+    let message_id = result.last_insert_id() as i32;
+    link_attachments(&mut *conn, table, message_id, &new_message.attachments).await?;
+
+    Ok(StoredMessage {
+        id: message_id,
+        collapsed: None,
+    })
+}
+
+// Function to keep the conversation summary up to date and resolve the mentions and keyword
+// alerts of a committed message, collapsed repeats only refresh the summary
+pub async fn announce_message(
+    pool: &AppState,
+    table: &'static str,
+    new_message: &NewMessage,
+    stored: &StoredMessage,
+) {
+    if let Some(collapsed) = &stored.collapsed {
+        pool.emit_event(
+            "message-collapsed",
+            CollapsedEvent {
+                table,
+                collapsed: collapsed.clone(),
+            },
+        );
+    }
+    update_conversation(pool, table, &new_message.connected).await;
+    // A collapsed repeat has the content of the message it was folded into, which was
+    // scanned when it was first sent
+    if stored.collapsed.is_none() {
+        scan_new_message(
+            pool,
+            table,
+            stored.id,
+            &new_message.sender,
+            &new_message.connected,
+            &new_message.content,
+        )
         .await;
+    }
+}
 
-    match result {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(e) => {
-            eprintln!("Error inserting message into 'my-client' table: {}", e);
-            HttpResponse::InternalServerError().finish()
+// Function to store a new message right away, keep the conversation summary up to date and
+// resolve its mentions and keyword alerts, returns the stored message and whether it was
+// folded into the last one
+pub async fn deliver_message(
+    pool: &AppState,
+    table: &'static str,
+    new_message: &NewMessage,
+) -> Result<StoredMessage, SendError> {
+    let thread_root_id = check_new_message(pool, table, new_message).await?;
+    let mut tx = pool.db_pool.begin().await?;
+    let stored = store_message(&mut tx, table, new_message, thread_root_id).await?;
+    tx.commit().await?;
+    announce_message(pool, table, new_message, &stored).await;
+    Ok(stored)
+}
+
+async fn send_message(
+    pool: &AppState,
+    table: &'static str,
+    new_message: &NewMessage,
+) -> HttpResponse {
+    // A send time in the past is simply sent now
+    let response = match new_message.send_at.filter(|send_at| *send_at > Utc::now()) {
+        Some(send_at) => schedule_message(pool, table, new_message, send_at).await,
+        None => match deliver_message(pool, table, new_message).await {
+            Ok(stored) => {
+                // The rules already ran on the message a repeat was folded into
+                if stored.collapsed.is_none() {
+                    apply_message_rules(pool, table, stored.id, new_message).await;
+                }
+                HttpResponse::Ok().finish()
+            }
+            Err(e) => e.to_response(table),
+        },
+    };
+
+    // The draft went out with the message, the send succeeded even if clearing it fails
+    if response.status().is_success() {
+        if let Err(e) = discard_draft(pool, table, &new_message.connected).await {
+            eprintln!("Error clearing draft after sending: {}", e);
         }
     }
+    response
+}
+
+// Handler function to send a message to 'my-client'
+#[post("/my/send/")]
+pub async fn send_message_my_client(
+    pool: web::Data<AppState>,
+    new_message: web::Json<NewMessage>,
+) -> impl Responder {
+    send_message(&pool, "messages_send_to_my_client", &new_message).await
 }
 
 // Handler function to send a message to 'other-client'
@@ -140,25 +822,83 @@ pub async fn send_message_other_client(
     pool: web::Data<AppState>,
     new_message: web::Json<NewMessage>,
 ) -> impl Responder {
-    let query_str = "
-        INSERT INTO messages_send_to_other_client (sender, receiver, content, close_one_point, connected)
-        VALUES (?, ?, ?, ?, ?)
-    ";
+    send_message(&pool, "messages_send_to_other_client", &new_message).await
+}
 
-    let result = query(query_str)
-        .bind(&new_message.sender)
-        .bind(&new_message.receiver)
-        .bind(&new_message.content)
-        .bind(&new_message.close_one_point)
-        .bind(&new_message.connected)
-        .execute(&pool.db_pool)
-        .await;
+// Define the query parameters accepted by the thread view
+#[derive(Deserialize)]
+struct ThreadQuery {
+    viewer: Option<String>, // Whose deleted for me messages come back as tombstones
+}
 
-    match result {
-        Ok(_) => HttpResponse::Ok().finish(),
+async fn get_thread(pool: &AppState, table: &str, id: i32, params: &ThreadQuery) -> HttpResponse {
+    let message = match fetch_message(pool, table, id).await {
+        Ok(Some(message)) => message,
+        Ok(None) => return HttpResponse::NotFound().finish(),
         Err(e) => {
-            eprintln!("Error inserting message into 'other-client' table: {}", e);
+            eprintln!("Error retrieving message from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let root_id = message.thread_root_id.unwrap_or(message.id);
+
+    let query_str = format!(
+        "SELECT {} FROM {} WHERE (id = ? OR thread_root_id = ?) AND {} ORDER BY timestamp, id",
+        MESSAGE_COLUMNS, table, NOT_EXPIRED
+    );
+    let messages = match sqlx::query_as::<_, Message>(&query_str)
+        .bind(root_id)
+        .bind(root_id)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(messages) => messages,
+        Err(e) => {
+            eprintln!("Error retrieving thread from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    match to_responses(pool, table, params.viewer.as_deref(), messages).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => {
+            eprintln!(
+                "Error loading message details from '{}' table: {}",
+                table, e
+            );
             HttpResponse::InternalServerError().finish()
         }
     }
 }
+
+// Handler function to get the whole thread a 'my-client' message belongs to, oldest first
+#[get("/my/messages/{id}/thread")]
+pub async fn get_thread_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    params: web::Query<ThreadQuery>,
+) -> impl Responder {
+    get_thread(
+        &pool,
+        "messages_send_to_my_client",
+        id.into_inner(),
+        &params,
+    )
+    .await
+}
+
+// Handler function to get the whole thread an 'other-client' message belongs to, oldest first
+#[get("/other/messages/{id}/thread")]
+pub async fn get_thread_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    params: web::Query<ThreadQuery>,
+) -> impl Responder {
+    get_thread(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        &params,
+    )
+    .await
+}
@@ -0,0 +1,943 @@
+use super::message_conversation_handlers::refresh_conversation;
+use super::message_get_set_handlers::{
+    known_message_table, to_responses, Message, MessageFormat, MessageResponse,
+    DEFAULT_HISTORY_LIMIT, EXPIRY_FOR_NEW_MESSAGE, MAX_HISTORY_LIMIT, MESSAGE_COLUMNS, NOT_EXPIRED,
+};
+use super::message_mention_handlers::scan_new_message;
+use crate::server::AppState;
+use actix_web::{get, post, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySqlConnection};
+
+// Sender of the messages that record membership changes
+const SYSTEM_SENDER: &str = "system";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ChatRole {
+    Member,
+    Admin,
+    Owner,
+}
+
+impl ChatRole {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::Member => "member",
+            ChatRole::Admin => "admin",
+            ChatRole::Owner => "owner",
+        }
+    }
+
+    fn parse(role: &str) -> ChatRole {
+        match role {
+            "owner" => ChatRole::Owner,
+            "admin" => ChatRole::Admin,
+            _ => ChatRole::Member,
+        }
+    }
+}
+
+// Define a struct to represent a group conversation for API responses
+#[derive(Debug, Serialize, FromRow)]
+struct GroupChat {
+    id: i32,
+    name: String,
+    conversation: String, // The 'connected' value of the chat messages
+    created_by: String,
+    created_at: DateTime<Utc>,
+    member_count: i64,
+    unread_count: i64, // Messages the listed member has not read, 0 without a member
+}
+
+// Define a struct to represent a member of a group conversation
+#[derive(Debug, Serialize, FromRow)]
+struct ChatMember {
+    member: String,
+    role: String,
+    joined_at: DateTime<Utc>,
+}
+
+// Define a struct to capture a new group conversation
+#[derive(Deserialize)]
+struct NewGroupChat {
+    name: String,
+    owner: String,
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+// Define the query parameters accepted by the chat listing
+#[derive(Deserialize)]
+struct ChatListQuery {
+    member: Option<String>, // Only chats this person belongs to
+}
+
+// Define the query parameters of the reads of one chat, only members may read it
+#[derive(Deserialize)]
+struct ChatViewerQuery {
+    member: String,
+}
+
+// Define the query parameters accepted by the chat history, paged like a conversation
+#[derive(Deserialize)]
+struct ChatHistoryQuery {
+    member: String,      // Whose deleted for me messages come back as tombstones
+    before: Option<i32>, // Only messages older than this one, the newest page when missing
+    limit: Option<u32>,
+}
+
+// Define a struct to represent one page of a chat history for API responses
+#[derive(Serialize)]
+struct ChatHistoryPage {
+    messages: Vec<MessageResponse>,
+    next_before: Option<i32>, // Cursor of the next older page, None on the last page
+}
+
+// Define a struct to capture a member joining or leaving by themselves
+#[derive(Deserialize)]
+struct MemberAction {
+    member: String,
+}
+
+// Define a struct to capture a change one member makes to another
+#[derive(Deserialize)]
+struct ModerationAction {
+    actor: String,
+    member: String,
+    role: Option<ChatRole>, // Only used when changing roles
+}
+
+// Define a struct to capture a message addressed to a group conversation
+#[derive(Deserialize)]
+struct ChatMessage {
+    sender: String,
+    content: String,
+    close_one_point: Option<String>,
+    reply_to: Option<i32>,
+    #[serde(default)]
+    format: MessageFormat,
+}
+
+// Define a struct to capture a read receipt of one member
+#[derive(Deserialize)]
+struct ChatReceipt {
+    member: String,
+    up_to_id: i32, // Inclusive
+}
+
+#[derive(Clone, Serialize)]
+struct ChatEvent {
+    chat_id: i32,
+    conversation: String,
+    members: Vec<String>, // Every member that should be notified
+    message_id: Option<i32>,
+}
+
+// Prefix of the 'connected' value of group conversations, reserved for the chat endpoints
+pub const GROUP_CONVERSATION_PREFIX: &str = "group:";
+pub const GROUP_CONVERSATION_REFUSED: &str =
+    "Group conversations are only reachable through the chat endpoints";
+
+// Function to build the 'connected' value shared by every message of a group conversation
+pub fn chat_conversation(chat_id: i32) -> String {
+    format!("{}{}", GROUP_CONVERSATION_PREFIX, chat_id)
+}
+
+// Function to tell group conversations apart, they are only reachable through the chat
+// endpoints which check the membership
+pub fn is_group_conversation(connected: &str) -> bool {
+    connected.starts_with(GROUP_CONVERSATION_PREFIX)
+}
+
+// Function to look up the message table of a chat, the row stays locked until the
+// surrounding transaction ends so membership changes are applied one at a time
+async fn fetch_chat_table(
+    conn: &mut MySqlConnection,
+    chat_id: i32,
+) -> Result<Option<&'static str>, sqlx::Error> {
+    let table = sqlx::query_scalar::<_, String>(
+        "SELECT message_table FROM group_chats WHERE id = ? FOR UPDATE",
+    )
+    .bind(chat_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(table.as_deref().and_then(known_message_table))
+}
+
+async fn fetch_role(
+    conn: &mut MySqlConnection,
+    chat_id: i32,
+    member: &str,
+) -> Result<Option<ChatRole>, sqlx::Error> {
+    let role = sqlx::query_scalar::<_, String>(
+        "SELECT role FROM group_chat_members WHERE group_id = ? AND member = ?",
+    )
+    .bind(chat_id)
+    .bind(member)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(role.map(|role| ChatRole::parse(&role)))
+}
+
+// Function to look up the message table of a chat for one of its members, everybody else
+// is refused
+async fn fetch_chat_for_member(
+    conn: &mut MySqlConnection,
+    chat_id: i32,
+    member: &str,
+) -> Result<Result<&'static str, HttpResponse>, sqlx::Error> {
+    let table =
+        sqlx::query_scalar::<_, String>("SELECT message_table FROM group_chats WHERE id = ?")
+            .bind(chat_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    let table = match table.as_deref().and_then(known_message_table) {
+        Some(table) => table,
+        None => return Ok(Err(HttpResponse::NotFound().finish())),
+    };
+    if fetch_role(conn, chat_id, member).await?.is_none() {
+        return Ok(Err(
+            HttpResponse::Forbidden().body("Only members can read this chat")
+        ));
+    }
+    Ok(Ok(table))
+}
+
+async fn fetch_members(
+    conn: &mut MySqlConnection,
+    chat_id: i32,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT member FROM group_chat_members WHERE group_id = ? ORDER BY joined_at, member",
+    )
+    .bind(chat_id)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+// Inserts a message into the chat history and a pending receipt for every member but the
+// sender, returns the id of the message
+async fn insert_chat_message(
+    conn: &mut MySqlConnection,
+    table: &str,
+    chat_id: i32,
+    sender: &str,
+    (content, format): (&str, MessageFormat),
+    kind: &str,
+    extra: (Option<&str>, Option<i32>, Option<i32>), // close_one_point, reply_to, thread_root_id
+) -> Result<i32, sqlx::Error> {
+    let conversation = chat_conversation(chat_id);
+    let (close_one_point, reply_to, thread_root_id) = extra;
+    let query_str = format!(
+        "INSERT INTO {} (sender, receiver, content, close_one_point, connected, reply_to, thread_root_id, kind, \
+         format, content_html, expires_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, {})",
+        table, EXPIRY_FOR_NEW_MESSAGE
+    );
+    let message_id = query(&query_str)
+        .bind(sender)
+        .bind(&conversation)
+        .bind(content)
+        .bind(close_one_point)
+        .bind(&conversation)
+        .bind(reply_to)
+        .bind(thread_root_id)
+        .bind(kind)
+        .bind(format.as_str())
+        .bind(format.render(content))
+        .bind(table)
+        .bind(&conversation)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_id() as i32;
+
+    query(
+        "INSERT INTO group_chat_receipts (group_id, message_table, message_id, member) \
+         SELECT group_id, ?, ?, member FROM group_chat_members WHERE group_id = ? AND member <> ?",
+    )
+    .bind(table)
+    .bind(message_id)
+    .bind(chat_id)
+    .bind(sender)
+    .execute(&mut *conn)
+    .await?;
+
+    refresh_conversation(conn, table, &conversation).await?;
+    Ok(message_id)
+}
+
+// Records a membership change in the history of the chat
+async fn insert_system_message(
+    conn: &mut MySqlConnection,
+    table: &str,
+    chat_id: i32,
+    content: &str,
+) -> Result<i32, sqlx::Error> {
+    insert_chat_message(
+        conn,
+        table,
+        chat_id,
+        SYSTEM_SENDER,
+        (content, MessageFormat::Plain),
+        "system",
+        (None, None, None),
+    )
+    .await
+}
+
+fn validate_chat_name(name: &str) -> Result<&str, &'static str> {
+    let name = name.trim();
+    if name.is_empty() {
+        Err("Chat name cannot be empty")
+    } else if name.len() > 255 {
+        Err("Chat name is too long")
+    } else {
+        Ok(name)
+    }
+}
+
+async fn create_chat(pool: &AppState, table: &str, chat: &NewGroupChat) -> HttpResponse {
+    let name = match validate_chat_name(&chat.name) {
+        Ok(name) => name,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+    if chat.owner.trim().is_empty() || chat.owner == SYSTEM_SENDER {
+        return HttpResponse::BadRequest().body("Invalid chat owner");
+    }
+
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        let chat_id = query("INSERT INTO group_chats (message_table, name, created_by) VALUES (?, ?, ?)")
+            .bind(table)
+            .bind(name)
+            .bind(&chat.owner)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_id() as i32;
+
+        query("INSERT INTO group_chat_members (group_id, member, role) VALUES (?, ?, 'owner')")
+            .bind(chat_id)
+            .bind(&chat.owner)
+            .execute(&mut *tx)
+            .await?;
+        for member in &chat.members {
+            if member != &chat.owner && member != SYSTEM_SENDER {
+                query("INSERT IGNORE INTO group_chat_members (group_id, member, role) VALUES (?, ?, 'member')")
+                    .bind(chat_id)
+                    .bind(member)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        let content = format!("{} created the group \"{}\"", chat.owner, name);
+        insert_system_message(&mut tx, table, chat_id, &content).await?;
+        let members = fetch_members(&mut tx, chat_id).await?;
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>((chat_id, members))
+    }
+    .await;
+
+    match result {
+        Ok((chat_id, members)) => {
+            pool.emit_event(
+                "group-chat-changed",
+                ChatEvent {
+                    chat_id,
+                    conversation: chat_conversation(chat_id),
+                    members,
+                    message_id: None,
+                },
+            );
+            HttpResponse::Ok().json(serde_json::json!({
+                "id": chat_id,
+                "conversation": chat_conversation(chat_id),
+            }))
+        }
+        Err(e) => {
+            eprintln!("Error creating group chat: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to create a group conversation on 'my-client'
+#[post("/my/chats")]
+pub async fn create_group_chat_my_client(
+    pool: web::Data<AppState>,
+    chat: web::Json<NewGroupChat>,
+) -> impl Responder {
+    create_chat(&pool, "messages_send_to_my_client", &chat).await
+}
+
+// Handler function to create a group conversation on 'other-client'
+#[post("/other/chats")]
+pub async fn create_group_chat_other_client(
+    pool: web::Data<AppState>,
+    chat: web::Json<NewGroupChat>,
+) -> impl Responder {
+    create_chat(&pool, "messages_send_to_other_client", &chat).await
+}
+
+// Read state is kept per member in the receipts, so only the chat listing of one member
+// carries unread counts, the conversation summaries of group chats always show 0
+async fn list_chats(pool: &AppState, table: &str, params: &ChatListQuery) -> HttpResponse {
+    let query_str = format!(
+        "
+        SELECT g.id, g.name, CONCAT(?, g.id) AS conversation, g.created_by, g.created_at,
+            (SELECT COUNT(*) FROM group_chat_members m WHERE m.group_id = g.id) AS member_count,
+            (SELECT COUNT(*) FROM group_chat_receipts r
+             JOIN {0} msg ON msg.id = r.message_id
+             WHERE r.group_id = g.id AND r.message_table = g.message_table AND r.member = ?
+               AND r.read_at IS NULL AND msg.deleted_at IS NULL AND {1}) AS unread_count
+        FROM group_chats g
+        WHERE g.message_table = ?
+          AND (? IS NULL OR EXISTS (
+              SELECT 1 FROM group_chat_members m WHERE m.group_id = g.id AND m.member = ?))
+        ORDER BY g.name
+        ",
+        table, NOT_EXPIRED
+    );
+
+    match sqlx::query_as::<_, GroupChat>(&query_str)
+        .bind(GROUP_CONVERSATION_PREFIX)
+        .bind(&params.member)
+        .bind(table)
+        .bind(&params.member)
+        .bind(&params.member)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(chats) => HttpResponse::Ok().json(chats),
+        Err(e) => {
+            eprintln!("Error retrieving group chats: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list the group conversations of 'my-client'
+#[get("/my/chats")]
+pub async fn get_group_chats_my_client(
+    pool: web::Data<AppState>,
+    params: web::Query<ChatListQuery>,
+) -> impl Responder {
+    list_chats(&pool, "messages_send_to_my_client", &params).await
+}
+
+// Handler function to list the group conversations of 'other-client'
+#[get("/other/chats")]
+pub async fn get_group_chats_other_client(
+    pool: web::Data<AppState>,
+    params: web::Query<ChatListQuery>,
+) -> impl Responder {
+    list_chats(&pool, "messages_send_to_other_client", &params).await
+}
+
+// Handler function to list the members of a group conversation with their roles, only
+// members can see who else is in it
+#[get("/chats/{id}/members")]
+pub async fn get_group_chat_members(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    params: web::Query<ChatViewerQuery>,
+) -> impl Responder {
+    let chat_id = chat_id.into_inner();
+    let result = async {
+        let mut conn = pool.db_pool.acquire().await?;
+        if let Err(response) = fetch_chat_for_member(&mut conn, chat_id, &params.member).await? {
+            return Ok(Err(response));
+        }
+        let members = sqlx::query_as::<_, ChatMember>(
+            "SELECT member, role, joined_at FROM group_chat_members WHERE group_id = ? \
+             ORDER BY FIELD(role, 'owner', 'admin', 'member'), joined_at, member",
+        )
+        .bind(chat_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok::<_, sqlx::Error>(Ok(members))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(members)) => HttpResponse::Ok().json(members),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error retrieving group chat members: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// The kinds of membership change, every one of them is checked and recorded the same way
+enum MembershipChange<'a> {
+    Join(&'a str),
+    Leave(&'a str),
+    Kick {
+        actor: &'a str,
+        member: &'a str,
+    },
+    SetRole {
+        actor: &'a str,
+        member: &'a str,
+        role: ChatRole,
+    },
+}
+
+// Applies a membership change inside one transaction and records it as a system message.
+// Returns the response to send and, on success, the event to push.
+async fn change_membership(
+    conn: &mut MySqlConnection,
+    chat_id: i32,
+    change: &MembershipChange<'_>,
+) -> Result<Result<ChatEvent, HttpResponse>, sqlx::Error> {
+    let table = match fetch_chat_table(conn, chat_id).await? {
+        Some(chat) => chat,
+        None => return Ok(Err(HttpResponse::NotFound().finish())),
+    };
+
+    let content = match *change {
+        MembershipChange::Join(member) => {
+            if member.trim().is_empty() || member == SYSTEM_SENDER {
+                return Ok(Err(HttpResponse::BadRequest().body("Invalid member")));
+            }
+            let result = query(
+                "INSERT IGNORE INTO group_chat_members (group_id, member, role) VALUES (?, ?, 'member')",
+            )
+            .bind(chat_id)
+            .bind(member)
+            .execute(&mut *conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Ok(Err(HttpResponse::Conflict().body("Already a member")));
+            }
+            format!("{} joined the group", member)
+        }
+        MembershipChange::Leave(member) => {
+            let role = match fetch_role(conn, chat_id, member).await? {
+                Some(role) => role,
+                None => return Ok(Err(HttpResponse::NotFound().body("Not a member"))),
+            };
+            remove_member(conn, chat_id, member).await?;
+
+            // An owner hands the group over to the longest standing admin, or member
+            let mut content = format!("{} left the group", member);
+            if role == ChatRole::Owner {
+                let successor = sqlx::query_scalar::<_, String>(
+                    "SELECT member FROM group_chat_members WHERE group_id = ? \
+                     ORDER BY FIELD(role, 'admin', 'member'), joined_at, member LIMIT 1",
+                )
+                .bind(chat_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+                if let Some(successor) = successor {
+                    set_role(conn, chat_id, &successor, ChatRole::Owner).await?;
+                    content.push_str(&format!(", {} is now the owner", successor));
+                }
+            }
+            content
+        }
+        MembershipChange::Kick { actor, member } => {
+            let actor_role = fetch_role(conn, chat_id, actor).await?;
+            let member_role = match fetch_role(conn, chat_id, member).await? {
+                Some(role) => role,
+                None => return Ok(Err(HttpResponse::NotFound().body("Not a member"))),
+            };
+            // Admins and owners can remove anyone ranked below them
+            match actor_role {
+                Some(actor_role) if actor_role >= ChatRole::Admin && actor_role > member_role => {}
+                _ => {
+                    return Ok(Err(HttpResponse::Forbidden()
+                        .body("Only a higher ranked admin can remove this member")))
+                }
+            }
+            remove_member(conn, chat_id, member).await?;
+            format!("{} removed {} from the group", actor, member)
+        }
+        MembershipChange::SetRole {
+            actor,
+            member,
+            role,
+        } => {
+            if fetch_role(conn, chat_id, actor).await? != Some(ChatRole::Owner) {
+                return Ok(Err(
+                    HttpResponse::Forbidden().body("Only the owner can change roles")
+                ));
+            }
+            if actor == member {
+                return Ok(Err(
+                    HttpResponse::BadRequest().body("The owner cannot change their own role")
+                ));
+            }
+            if fetch_role(conn, chat_id, member).await?.is_none() {
+                return Ok(Err(HttpResponse::NotFound().body("Not a member")));
+            }
+
+            set_role(conn, chat_id, member, role).await?;
+            if role == ChatRole::Owner {
+                // Ownership is transferred, there is only ever one owner
+                set_role(conn, chat_id, actor, ChatRole::Admin).await?;
+                format!("{} made {} the owner", actor, member)
+            } else {
+                format!("{} made {} {}", actor, member, role.as_str())
+            }
+        }
+    };
+
+    let message_id = insert_system_message(conn, table, chat_id, &content).await?;
+    let mut members = fetch_members(conn, chat_id).await?;
+    // Whoever left or was removed still learns about it
+    if let MembershipChange::Leave(member) | MembershipChange::Kick { member, .. } = *change {
+        members.push(member.to_string());
+    }
+
+    Ok(Ok(ChatEvent {
+        chat_id,
+        conversation: chat_conversation(chat_id),
+        members,
+        message_id: Some(message_id),
+    }))
+}
+
+async fn remove_member(
+    conn: &mut MySqlConnection,
+    chat_id: i32,
+    member: &str,
+) -> Result<(), sqlx::Error> {
+    query("DELETE FROM group_chat_members WHERE group_id = ? AND member = ?")
+        .bind(chat_id)
+        .bind(member)
+        .execute(&mut *conn)
+        .await
+        .map(|_| ())
+}
+
+async fn set_role(
+    conn: &mut MySqlConnection,
+    chat_id: i32,
+    member: &str,
+    role: ChatRole,
+) -> Result<(), sqlx::Error> {
+    query("UPDATE group_chat_members SET role = ? WHERE group_id = ? AND member = ?")
+        .bind(role.as_str())
+        .bind(chat_id)
+        .bind(member)
+        .execute(&mut *conn)
+        .await
+        .map(|_| ())
+}
+
+async fn apply_membership_change(
+    pool: &AppState,
+    chat_id: i32,
+    change: MembershipChange<'_>,
+) -> HttpResponse {
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        let outcome = change_membership(&mut tx, chat_id, &change).await?;
+        if outcome.is_ok() {
+            tx.commit().await?;
+        }
+        Ok::<_, sqlx::Error>(outcome)
+    }
+    .await;
+
+    match result {
+        Ok(Ok(event)) => {
+            pool.emit_event("group-chat-changed", event);
+            HttpResponse::Ok().finish()
+        }
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error changing group chat membership: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to join a group conversation
+#[post("/chats/{id}/join")]
+pub async fn join_group_chat(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    action: web::Json<MemberAction>,
+) -> impl Responder {
+    apply_membership_change(
+        &pool,
+        chat_id.into_inner(),
+        MembershipChange::Join(&action.member),
+    )
+    .await
+}
+
+// Handler function to leave a group conversation
+#[post("/chats/{id}/leave")]
+pub async fn leave_group_chat(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    action: web::Json<MemberAction>,
+) -> impl Responder {
+    apply_membership_change(
+        &pool,
+        chat_id.into_inner(),
+        MembershipChange::Leave(&action.member),
+    )
+    .await
+}
+
+// Handler function to remove a member from a group conversation, admins and owner only
+#[post("/chats/{id}/kick")]
+pub async fn kick_group_chat_member(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    action: web::Json<ModerationAction>,
+) -> impl Responder {
+    apply_membership_change(
+        &pool,
+        chat_id.into_inner(),
+        MembershipChange::Kick {
+            actor: &action.actor,
+            member: &action.member,
+        },
+    )
+    .await
+}
+
+// Handler function to change the role of a member, owner only
+#[put("/chats/{id}/role")]
+pub async fn set_group_chat_role(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    action: web::Json<ModerationAction>,
+) -> impl Responder {
+    let role = match action.role {
+        Some(role) => role,
+        None => return HttpResponse::BadRequest().body("A role is required"),
+    };
+    apply_membership_change(
+        &pool,
+        chat_id.into_inner(),
+        MembershipChange::SetRole {
+            actor: &action.actor,
+            member: &action.member,
+            role,
+        },
+    )
+    .await
+}
+
+// Handler function to send a message to a group conversation. The message is stored once
+// in the chat history and fanned out to the members as pending receipts and an event.
+#[post("/chats/{id}/send")]
+pub async fn send_group_chat_message(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    message: web::Json<ChatMessage>,
+) -> impl Responder {
+    let chat_id = chat_id.into_inner();
+    if message.content.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Message content cannot be empty");
+    }
+
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+        let table = match fetch_chat_table(&mut tx, chat_id).await? {
+            Some(table) => table,
+            None => return Ok(Err(HttpResponse::NotFound().finish())),
+        };
+        if fetch_role(&mut tx, chat_id, &message.sender)
+            .await?
+            .is_none()
+        {
+            return Ok(Err(
+                HttpResponse::Forbidden().body("Only members can write to this chat")
+            ));
+        }
+
+        let thread_root_id = match message.reply_to {
+            Some(parent_id) => {
+                let query_str = format!(
+                    "SELECT COALESCE(thread_root_id, id) FROM {} WHERE id = ? AND connected = ? AND {}",
+                    table, NOT_EXPIRED
+                );
+                match sqlx::query_scalar::<_, i32>(&query_str)
+                    .bind(parent_id)
+                    .bind(chat_conversation(chat_id))
+                    .fetch_optional(&mut *tx)
+                    .await?
+                {
+                    Some(root) => Some(root),
+                    None => {
+                        return Ok(Err(HttpResponse::BadRequest()
+                            .body("The replied message is not part of this chat")))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let message_id = insert_chat_message(
+            &mut tx,
+            table,
+            chat_id,
+            &message.sender,
+            (&message.content, message.format),
+            "text",
+            (
+                message.close_one_point.as_deref(),
+                message.reply_to,
+                thread_root_id,
+            ),
+        )
+        .await?;
+        let members = fetch_members(&mut tx, chat_id).await?;
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok((table, message_id, members)))
+    }
+    .await;
+
+    match result {
+        Ok(Ok((table, message_id, members))) => {
+            scan_new_message(
+                &pool,
+                table,
+                message_id,
+                &message.sender,
+                &chat_conversation(chat_id),
+                &message.content,
+            )
+            .await;
+            pool.emit_event(
+                "group-chat-message",
+                ChatEvent {
+                    chat_id,
+                    conversation: chat_conversation(chat_id),
+                    members,
+                    message_id: Some(message_id),
+                },
+            );
+            HttpResponse::Ok().json(serde_json::json!({ "id": message_id }))
+        }
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error sending group chat message: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to get one page of the history of a group conversation for one of its
+// members, newest first and including system messages. Paged like the history of a
+// conversation, messages the member deleted for themselves come back as tombstones.
+#[get("/chats/{id}/messages")]
+pub async fn get_group_chat_messages(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    params: web::Query<ChatHistoryQuery>,
+) -> impl Responder {
+    let chat_id = chat_id.into_inner();
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .clamp(1, MAX_HISTORY_LIMIT);
+
+    let result = async {
+        let mut conn = pool.db_pool.acquire().await?;
+        let table = match fetch_chat_for_member(&mut conn, chat_id, &params.member).await? {
+            Ok(table) => table,
+            Err(response) => return Ok(Err(response)),
+        };
+
+        // A cursor message that was deleted since falls back to the messages before its id
+        let cursor_query = format!("SELECT timestamp FROM {} WHERE id = ?", table);
+        let cursor_timestamp = match params.before {
+            Some(before) => {
+                sqlx::query_scalar::<_, DateTime<Utc>>(&cursor_query)
+                    .bind(before)
+                    .fetch_optional(&mut *conn)
+                    .await?
+            }
+            None => None,
+        };
+        let query_str = format!(
+            "SELECT {} FROM {} WHERE connected = ? \
+             AND (? IS NULL OR timestamp < COALESCE(?, timestamp) \
+                  OR (timestamp = COALESCE(?, timestamp) AND id < ?)) \
+             AND {} ORDER BY timestamp DESC, id DESC LIMIT ?",
+            MESSAGE_COLUMNS, table, NOT_EXPIRED
+        );
+        let messages = sqlx::query_as::<_, Message>(&query_str)
+            .bind(chat_conversation(chat_id))
+            .bind(params.before)
+            .bind(cursor_timestamp)
+            .bind(cursor_timestamp)
+            .bind(params.before)
+            .bind(limit)
+            .fetch_all(&mut *conn)
+            .await?;
+        drop(conn);
+
+        let next_before = match messages.last() {
+            Some(oldest) if messages.len() == limit as usize => Some(oldest.id),
+            _ => None,
+        };
+        Ok::<_, sqlx::Error>(Ok(ChatHistoryPage {
+            messages: to_responses(&pool, table, Some(&params.member), messages).await?,
+            next_before,
+        }))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(page)) => HttpResponse::Ok().json(page),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error retrieving group chat history: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to mark the messages of a group conversation read for one member
+#[post("/chats/{id}/receipts")]
+pub async fn post_group_chat_receipt(
+    pool: web::Data<AppState>,
+    chat_id: web::Path<i32>,
+    receipt: web::Json<ChatReceipt>,
+) -> impl Responder {
+    let chat_id = chat_id.into_inner();
+    let result = async {
+        let mut conn = pool.db_pool.acquire().await?;
+        if let Err(response) = fetch_chat_for_member(&mut conn, chat_id, &receipt.member).await? {
+            return Ok(Err(response));
+        }
+        let updated = query(
+            "UPDATE group_chat_receipts \
+             SET delivered_at = COALESCE(delivered_at, CURRENT_TIMESTAMP), \
+                 read_at = COALESCE(read_at, CURRENT_TIMESTAMP) \
+             WHERE group_id = ? AND member = ? AND message_id <= ? AND read_at IS NULL",
+        )
+        .bind(chat_id)
+        .bind(&receipt.member)
+        .bind(receipt.up_to_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        Ok::<_, sqlx::Error>(Ok(updated))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(updated)) => HttpResponse::Ok().json(serde_json::json!({ "updated": updated })),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error updating group chat receipts: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
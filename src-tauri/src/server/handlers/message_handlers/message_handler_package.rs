@@ -1,3 +1,4 @@
+use super::message_mention_handlers::invalidate_alert_cache;
 use crate::server::AppState;
 use actix_web::{post, web, HttpResponse, Responder};
 use sqlx::query;
@@ -17,9 +18,26 @@ pub async fn reset_messages_send_to_my_client_table_handler(
             timestamp TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
             counter INT DEFAULT 1,
             close_one_point VARCHAR(255),
-            connected_person VARCHAR(255),
-            INDEX (connected_person),
-            INDEX (close_one_point)
+            connected VARCHAR(255) NOT NULL,
+            edited_at TIMESTAMP NULL DEFAULT NULL,
+            deleted_at TIMESTAMP NULL DEFAULT NULL,
+            delivered_at TIMESTAMP NULL DEFAULT NULL,
+            read_at TIMESTAMP NULL DEFAULT NULL,
+            reply_to INT NULL DEFAULT NULL,
+            thread_root_id INT NULL DEFAULT NULL,
+            kind VARCHAR(16) NOT NULL DEFAULT 'text',
+            expires_at TIMESTAMP NULL DEFAULT NULL,
+            starred_at TIMESTAMP NULL DEFAULT NULL,
+            pinned_at TIMESTAMP NULL DEFAULT NULL,
+            forwarded_from_table VARCHAR(64) NULL DEFAULT NULL,
+            forwarded_from_id INT NULL DEFAULT NULL,
+            forwarded_from_sender VARCHAR(255) NULL DEFAULT NULL,
+            format VARCHAR(16) NOT NULL DEFAULT 'plain',
+            content_html TEXT NULL DEFAULT NULL,
+            INDEX (connected, timestamp),
+            INDEX (thread_root_id),
+            INDEX (close_one_point),
+            INDEX (expires_at)
         );";
 
     match query(drop_table_query).execute(&pool.db_pool).await {
@@ -54,9 +72,26 @@ pub async fn reset_messages_send_to_other_client_table_handler(
             timestamp TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
             counter INT DEFAULT 1,
             close_one_point VARCHAR(255),
-            connected_person VARCHAR(255),
-            INDEX (connected_person),
-            INDEX (close_one_point)
+            connected VARCHAR(255) NOT NULL,
+            edited_at TIMESTAMP NULL DEFAULT NULL,
+            deleted_at TIMESTAMP NULL DEFAULT NULL,
+            delivered_at TIMESTAMP NULL DEFAULT NULL,
+            read_at TIMESTAMP NULL DEFAULT NULL,
+            reply_to INT NULL DEFAULT NULL,
+            thread_root_id INT NULL DEFAULT NULL,
+            kind VARCHAR(16) NOT NULL DEFAULT 'text',
+            expires_at TIMESTAMP NULL DEFAULT NULL,
+            starred_at TIMESTAMP NULL DEFAULT NULL,
+            pinned_at TIMESTAMP NULL DEFAULT NULL,
+            forwarded_from_table VARCHAR(64) NULL DEFAULT NULL,
+            forwarded_from_id INT NULL DEFAULT NULL,
+            forwarded_from_sender VARCHAR(255) NULL DEFAULT NULL,
+            format VARCHAR(16) NOT NULL DEFAULT 'plain',
+            content_html TEXT NULL DEFAULT NULL,
+            INDEX (connected, timestamp),
+            INDEX (thread_root_id),
+            INDEX (close_one_point),
+            INDEX (expires_at)
         );";
 
     match query(drop_table_query).execute(&pool.db_pool).await {
@@ -139,3 +174,508 @@ pub async fn reset_connecting_people_table_handler(pool: web::Data<AppState>) ->
         }
     }
 }
+
+// Helper function to drop and recreate a table, shared by the reset handlers below
+async fn reset_table(pool: &AppState, table: &str, create_table_query: &str) -> HttpResponse {
+    let drop_table_query = format!("DROP TABLE IF EXISTS {};", table);
+
+    match query(&drop_table_query).execute(&pool.db_pool).await {
+        Ok(_) => match query(create_table_query).execute(&pool.db_pool).await {
+            Ok(_) => HttpResponse::Ok().body(format!("Table '{}' reset successfully", table)),
+            Err(e) => {
+                eprintln!("Error creating '{}' table: {}", table, e);
+                HttpResponse::InternalServerError().body("Error creating table")
+            }
+        },
+        Err(e) => {
+            eprintln!("Error dropping '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().body("Error dropping table")
+        }
+    }
+}
+
+// Handler function to reset 'contact_groups' and 'contact_group_members' tables
+#[post("/reset-contact-groups-table")]
+pub async fn reset_contact_groups_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    // Members reference groups, so they have to be dropped first and created last
+    if let Err(e) = query("DROP TABLE IF EXISTS contact_group_members;")
+        .execute(&pool.db_pool)
+        .await
+    {
+        eprintln!("Error dropping 'contact_group_members' table: {}", e);
+        return HttpResponse::InternalServerError().body("Error dropping table");
+    }
+
+    let response = reset_table(
+        &pool,
+        "contact_groups",
+        "
+        CREATE TABLE contact_groups (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            name VARCHAR(255) NOT NULL UNIQUE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+    .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    reset_table(
+        &pool,
+        "contact_group_members",
+        "
+        CREATE TABLE contact_group_members (
+            group_id INT NOT NULL,
+            contact_id VARCHAR(256) NOT NULL,
+            added_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (group_id, contact_id),
+            INDEX (contact_id),
+            FOREIGN KEY (group_id) REFERENCES contact_groups(id) ON DELETE CASCADE
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'contact_merge_log' table
+#[post("/reset-contact-merge-log-table")]
+pub async fn reset_contact_merge_log_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "contact_merge_log",
+        "
+        CREATE TABLE contact_merge_log (
+            id BIGINT AUTO_INCREMENT PRIMARY KEY,
+            people_table VARCHAR(64) NOT NULL,
+            kept_id VARCHAR(256) NOT NULL,
+            merged_id VARCHAR(256) NOT NULL,
+            kept_snapshot TEXT NOT NULL,
+            merged_snapshot TEXT NOT NULL,
+            rewritten_messages MEDIUMTEXT NOT NULL,
+            merged_group_ids TEXT NOT NULL,
+            added_group_ids TEXT NOT NULL,
+            merged_avatar CHAR(64) NULL DEFAULT NULL,
+            avatar_moved BOOLEAN NOT NULL DEFAULT FALSE,
+            moved_rows MEDIUMTEXT NULL DEFAULT NULL,
+            merged_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            undone_at TIMESTAMP NULL DEFAULT NULL,
+            INDEX (kept_id),
+            INDEX (merged_id)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'contact_avatars' table
+#[post("/reset-contact-avatars-table")]
+pub async fn reset_contact_avatars_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "contact_avatars",
+        "
+        CREATE TABLE contact_avatars (
+            people_table VARCHAR(64) NOT NULL,
+            contact_id VARCHAR(256) NOT NULL,
+            hash CHAR(64) NOT NULL,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (people_table, contact_id),
+            INDEX (hash)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'contact_edits' table
+#[post("/reset-contact-edits-table")]
+pub async fn reset_contact_edits_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "contact_edits",
+        "
+        CREATE TABLE contact_edits (
+            id BIGINT AUTO_INCREMENT PRIMARY KEY,
+            people_table VARCHAR(64),
+            contact_id VARCHAR(256) NOT NULL,
+            edit_type VARCHAR(64) NOT NULL,
+            details TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            INDEX (contact_id, created_at)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'message_revisions' table
+#[post("/reset-message-revisions-table")]
+pub async fn reset_message_revisions_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "message_revisions",
+        "
+        CREATE TABLE message_revisions (
+            id BIGINT AUTO_INCREMENT PRIMARY KEY,
+            message_table VARCHAR(64) NOT NULL,
+            message_id INT NOT NULL,
+            content TEXT NOT NULL,
+            written_at TIMESTAMP NULL DEFAULT NULL,
+            replaced_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            INDEX (message_table, message_id)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'conversations' table, rebuild it afterwards to backfill
+#[post("/reset-conversations-table")]
+pub async fn reset_conversations_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "conversations",
+        "
+        CREATE TABLE conversations (
+            message_table VARCHAR(64) NOT NULL,
+            connected VARCHAR(255) NOT NULL,
+            last_message_id INT NOT NULL,
+            last_sender VARCHAR(255) NOT NULL,
+            last_preview VARCHAR(400) NOT NULL,
+            last_activity TIMESTAMP NOT NULL,
+            unread_count INT NOT NULL DEFAULT 0,
+            PRIMARY KEY (message_table, connected),
+            INDEX (message_table, last_activity)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'message_reactions' table
+#[post("/reset-message-reactions-table")]
+pub async fn reset_message_reactions_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "message_reactions",
+        "
+        CREATE TABLE message_reactions (
+            message_table VARCHAR(64) NOT NULL,
+            message_id INT NOT NULL,
+            reactor VARCHAR(255) NOT NULL,
+            emoji VARCHAR(64) NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (message_table, message_id, reactor, emoji)
+        ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin;",
+    )
+    .await
+}
+
+// Handler function to reset 'attachments' and 'message_attachments' tables, the stored
+// files are left alone and removed by the next garbage collection run
+#[post("/reset-attachments-table")]
+pub async fn reset_attachments_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    let response = reset_table(
+        &pool,
+        "attachments",
+        "
+        CREATE TABLE attachments (
+            hash CHAR(64) PRIMARY KEY,
+            size BIGINT NOT NULL,
+            mime VARCHAR(127) NOT NULL,
+            width INT NULL,
+            height INT NULL,
+            thumbnail_mime VARCHAR(32) NULL,
+            uploaded_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+    .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    reset_table(
+        &pool,
+        "message_attachments",
+        "
+        CREATE TABLE message_attachments (
+            message_table VARCHAR(64) NOT NULL,
+            message_id INT NOT NULL,
+            hash CHAR(64) NOT NULL,
+            file_name VARCHAR(255) NOT NULL,
+            position INT NOT NULL DEFAULT 0,
+            PRIMARY KEY (message_table, message_id, hash),
+            INDEX (hash)
+        ) DEFAULT CHARSET = utf8mb4;",
+    )
+    .await
+}
+
+// Handler function to reset the group chat tables (chats, members and member receipts)
+#[post("/reset-group-chats-table")]
+pub async fn reset_group_chats_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    // Members and receipts reference chats, so they have to be dropped first and created last
+    for table in ["group_chat_receipts", "group_chat_members"] {
+        if let Err(e) = query(&format!("DROP TABLE IF EXISTS {};", table))
+            .execute(&pool.db_pool)
+            .await
+        {
+            eprintln!("Error dropping '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().body("Error dropping table");
+        }
+    }
+
+    let response = reset_table(
+        &pool,
+        "group_chats",
+        "
+        CREATE TABLE group_chats (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            message_table VARCHAR(64) NOT NULL,
+            name VARCHAR(255) NOT NULL,
+            created_by VARCHAR(255) NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            INDEX (message_table)
+        );",
+    )
+    .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let response = reset_table(
+        &pool,
+        "group_chat_members",
+        "
+        CREATE TABLE group_chat_members (
+            group_id INT NOT NULL,
+            member VARCHAR(255) NOT NULL,
+            role VARCHAR(16) NOT NULL DEFAULT 'member',
+            joined_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (group_id, member),
+            INDEX (member),
+            FOREIGN KEY (group_id) REFERENCES group_chats(id) ON DELETE CASCADE
+        );",
+    )
+    .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    reset_table(
+        &pool,
+        "group_chat_receipts",
+        "
+        CREATE TABLE group_chat_receipts (
+            group_id INT NOT NULL,
+            message_table VARCHAR(64) NOT NULL,
+            message_id INT NOT NULL,
+            member VARCHAR(255) NOT NULL,
+            delivered_at TIMESTAMP NULL DEFAULT NULL,
+            read_at TIMESTAMP NULL DEFAULT NULL,
+            PRIMARY KEY (message_table, message_id, member),
+            INDEX (group_id, member, read_at),
+            FOREIGN KEY (group_id) REFERENCES group_chats(id) ON DELETE CASCADE
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'scheduled_messages' table
+#[post("/reset-scheduled-messages-table")]
+pub async fn reset_scheduled_messages_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "scheduled_messages",
+        "
+        CREATE TABLE scheduled_messages (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            message_table VARCHAR(64) NOT NULL,
+            sender VARCHAR(255) NOT NULL,
+            receiver VARCHAR(255) NOT NULL,
+            content TEXT NOT NULL,
+            close_one_point VARCHAR(255),
+            connected VARCHAR(255) NOT NULL,
+            reply_to INT NULL DEFAULT NULL,
+            attachments JSON NOT NULL,
+            format VARCHAR(16) NOT NULL DEFAULT 'plain',
+            send_at TIMESTAMP NOT NULL,
+            status VARCHAR(16) NOT NULL DEFAULT 'pending',
+            message_id INT NULL DEFAULT NULL,
+            error VARCHAR(255) NULL DEFAULT NULL,
+            attempts INT NOT NULL DEFAULT 0,
+            claimed_at TIMESTAMP NULL DEFAULT NULL,
+            claim_token CHAR(36) NULL DEFAULT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            INDEX (status, send_at),
+            INDEX (message_table, connected)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'conversation_settings' table
+#[post("/reset-conversation-settings-table")]
+pub async fn reset_conversation_settings_table_handler(
+    pool: web::Data<AppState>,
+) -> impl Responder {
+    reset_table(
+        &pool,
+        "conversation_settings",
+        "
+        CREATE TABLE conversation_settings (
+            message_table VARCHAR(64) NOT NULL,
+            connected VARCHAR(255) NOT NULL,
+            expiry_seconds INT NULL DEFAULT NULL,
+            collapse_repeats BOOLEAN NOT NULL DEFAULT TRUE,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+            PRIMARY KEY (message_table, connected)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'message_drafts' table
+#[post("/reset-message-drafts-table")]
+pub async fn reset_message_drafts_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "message_drafts",
+        "
+        CREATE TABLE message_drafts (
+            message_table VARCHAR(64) NOT NULL,
+            connected VARCHAR(255) NOT NULL,
+            content TEXT NOT NULL,
+            reply_to INT NULL DEFAULT NULL,
+            attachments JSON NOT NULL,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+            PRIMARY KEY (message_table, connected)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'message_hides' table, the messages someone deleted for themselves
+#[post("/reset-message-hides-table")]
+pub async fn reset_message_hides_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "message_hides",
+        "
+        CREATE TABLE message_hides (
+            message_table VARCHAR(64) NOT NULL,
+            message_id INT NOT NULL,
+            viewer VARCHAR(255) NOT NULL,
+            hidden_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (message_table, message_id, viewer),
+            INDEX (message_table, viewer)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'message_mentions' table
+#[post("/reset-message-mentions-table")]
+pub async fn reset_message_mentions_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    reset_table(
+        &pool,
+        "message_mentions",
+        "
+        CREATE TABLE message_mentions (
+            message_table VARCHAR(64) NOT NULL,
+            message_id INT NOT NULL,
+            position INT NOT NULL,
+            contact_id VARCHAR(256) NOT NULL,
+            nick VARCHAR(255) NOT NULL,
+            PRIMARY KEY (message_table, message_id, position),
+            INDEX (message_table, contact_id)
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'keyword_alerts' and 'keyword_alert_hits' tables
+#[post("/reset-keyword-alerts-table")]
+pub async fn reset_keyword_alerts_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    // Hits reference alerts, so they have to be dropped first and created last
+    if let Err(e) = query("DROP TABLE IF EXISTS keyword_alert_hits;")
+        .execute(&pool.db_pool)
+        .await
+    {
+        eprintln!("Error dropping 'keyword_alert_hits' table: {}", e);
+        return HttpResponse::InternalServerError().body("Error dropping table");
+    }
+
+    let response = reset_table(
+        &pool,
+        "keyword_alerts",
+        "
+        CREATE TABLE keyword_alerts (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            pattern VARCHAR(255) NOT NULL,
+            is_regex BOOLEAN NOT NULL DEFAULT FALSE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (pattern, is_regex)
+        );",
+    )
+    .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    invalidate_alert_cache();
+
+    reset_table(
+        &pool,
+        "keyword_alert_hits",
+        "
+        CREATE TABLE keyword_alert_hits (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            alert_id INT NOT NULL,
+            message_table VARCHAR(64) NOT NULL,
+            message_id INT NOT NULL,
+            connected VARCHAR(255) NOT NULL,
+            matched VARCHAR(255) NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (alert_id, message_table, message_id),
+            INDEX (message_table, message_id),
+            FOREIGN KEY (alert_id) REFERENCES keyword_alerts(id) ON DELETE CASCADE
+        );",
+    )
+    .await
+}
+
+// Handler function to reset 'message_rules' and 'message_rule_replies' tables
+#[post("/reset-message-rules-table")]
+pub async fn reset_message_rules_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    let response = reset_table(
+        &pool,
+        "message_rules",
+        "
+        CREATE TABLE message_rules (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            message_table VARCHAR(64) NOT NULL,
+            name VARCHAR(255) NOT NULL,
+            enabled BOOLEAN NOT NULL DEFAULT TRUE,
+            position INT NOT NULL DEFAULT 0,
+            conditions JSON NOT NULL,
+            actions JSON NOT NULL,
+            stop_processing BOOLEAN NOT NULL DEFAULT FALSE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            INDEX (message_table, position)
+        );",
+    )
+    .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    reset_table(
+        &pool,
+        "message_rule_replies",
+        "
+        CREATE TABLE message_rule_replies (
+            message_table VARCHAR(64) NOT NULL,
+            connected VARCHAR(255) NOT NULL,
+            replied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            expires_at TIMESTAMP NOT NULL,
+            PRIMARY KEY (message_table, connected)
+        );",
+    )
+    .await
+}
@@ -0,0 +1,167 @@
+use super::message_get_set_handlers::NOT_EXPIRED;
+use super::message_group_chat_handlers::{is_group_conversation, GROUP_CONVERSATION_REFUSED};
+use crate::server::AppState;
+use actix_web::{get, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow};
+
+// Same limit as the close_one_point column
+const MAX_LABEL_CHARS: usize = 255;
+
+// Define a struct to represent a label used in a conversation for API responses
+#[derive(Debug, Serialize, FromRow)]
+struct LabelSummary {
+    label: String,
+    count: i64,
+    last_used: DateTime<Utc>,
+}
+
+// Define a struct to capture a relabelling of a range of messages
+#[derive(Deserialize)]
+struct Relabel {
+    from_id: i32,
+    to_id: i32,
+    label: Option<String>,         // None or blank removes the label
+    current_label: Option<String>, // Only touch messages that carry this label
+}
+
+#[derive(Clone, Serialize)]
+struct RelabelEvent {
+    table: &'static str,
+    connected: String,
+    from_id: i32,
+    to_id: i32,
+    label: Option<String>,
+    updated: u64,
+}
+
+// Tombstones keep no label worth listing, expired messages are already gone for the views
+async fn list_labels(pool: &AppState, table: &str, connected: &str) -> HttpResponse {
+    if is_group_conversation(connected) {
+        return HttpResponse::BadRequest().body(GROUP_CONVERSATION_REFUSED);
+    }
+
+    let query_str = format!(
+        "SELECT close_one_point AS label, COUNT(*) AS count, MAX(timestamp) AS last_used \
+         FROM {} WHERE connected = ? AND close_one_point IS NOT NULL AND deleted_at IS NULL \
+         AND {} GROUP BY close_one_point ORDER BY count DESC, label",
+        table, NOT_EXPIRED
+    );
+
+    match sqlx::query_as::<_, LabelSummary>(&query_str)
+        .bind(connected)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(labels) => HttpResponse::Ok().json(labels),
+        Err(e) => {
+            eprintln!("Error retrieving labels from '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn relabel_messages(
+    pool: &AppState,
+    table: &'static str,
+    connected: &str,
+    relabel: &Relabel,
+) -> HttpResponse {
+    if is_group_conversation(connected) {
+        return HttpResponse::BadRequest().body(GROUP_CONVERSATION_REFUSED);
+    }
+
+    if relabel.from_id > relabel.to_id {
+        return HttpResponse::BadRequest().body("The range has to start before it ends");
+    }
+    let label = relabel
+        .label
+        .as_deref()
+        .map(str::trim)
+        .filter(|label| !label.is_empty());
+    if label.is_some_and(|label| label.chars().count() > MAX_LABEL_CHARS) {
+        return HttpResponse::BadRequest().body(format!(
+            "Labels are limited to {} characters",
+            MAX_LABEL_CHARS
+        ));
+    }
+
+    let update_query = format!(
+        "UPDATE {} SET close_one_point = ? \
+         WHERE connected = ? AND id BETWEEN ? AND ? AND deleted_at IS NULL AND {} \
+         AND (? IS NULL OR close_one_point = ?)",
+        table, NOT_EXPIRED
+    );
+    let result = query(&update_query)
+        .bind(label)
+        .bind(connected)
+        .bind(relabel.from_id)
+        .bind(relabel.to_id)
+        .bind(&relabel.current_label)
+        .bind(&relabel.current_label)
+        .execute(&pool.db_pool)
+        .await;
+
+    match result {
+        Ok(result) => {
+            let updated = result.rows_affected();
+            if updated > 0 {
+                pool.emit_event(
+                    "messages-relabelled",
+                    RelabelEvent {
+                        table,
+                        connected: connected.to_string(),
+                        from_id: relabel.from_id,
+                        to_id: relabel.to_id,
+                        label: label.map(str::to_string),
+                        updated,
+                    },
+                );
+            }
+            HttpResponse::Ok().json(serde_json::json!({ "updated": updated }))
+        }
+        Err(e) => {
+            eprintln!("Error relabelling messages in '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list the labels of a 'my-client' conversation with their message counts
+#[get("/my/conversations/{connected}/labels")]
+pub async fn get_labels_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    list_labels(&pool, "messages_send_to_my_client", &connected).await
+}
+
+// Handler function to list the labels of an 'other-client' conversation with their message counts
+#[get("/other/conversations/{connected}/labels")]
+pub async fn get_labels_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    list_labels(&pool, "messages_send_to_other_client", &connected).await
+}
+
+// Handler function to relabel a range of messages of a 'my-client' conversation
+#[put("/my/conversations/{connected}/labels")]
+pub async fn relabel_messages_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    relabel: web::Json<Relabel>,
+) -> impl Responder {
+    relabel_messages(&pool, "messages_send_to_my_client", &connected, &relabel).await
+}
+
+// Handler function to relabel a range of messages of an 'other-client' conversation
+#[put("/other/conversations/{connected}/labels")]
+pub async fn relabel_messages_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    relabel: web::Json<Relabel>,
+) -> impl Responder {
+    relabel_messages(&pool, "messages_send_to_other_client", &connected, &relabel).await
+}
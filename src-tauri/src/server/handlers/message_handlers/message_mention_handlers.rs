@@ -0,0 +1,637 @@
+use super::message_group_chat_handlers::is_group_conversation;
+use crate::server::AppState;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySql, MySqlConnection, QueryBuilder};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+// Bounds the contact lookup a single message can cause
+const MAX_MENTIONS_PER_MESSAGE: usize = 20;
+// Same limit as the pattern column
+const MAX_PATTERN_CHARS: usize = 255;
+// Compiled size limit of an alert regex, every incoming message is matched against all of them
+const MAX_REGEX_BYTES: usize = 256 * 1024;
+const MAX_MATCHED_CHARS: usize = 255;
+const DEFAULT_FEED_LIMIT: u32 = 50;
+const MAX_FEED_LIMIT: u32 = 200;
+
+// Define a struct to represent a contact mentioned in a message
+#[derive(Debug, Clone, Serialize)]
+pub struct Mention {
+    contact_id: String,
+    nick: String,
+    position: i32, // Offset of the @ in the content, in characters
+}
+
+#[derive(FromRow)]
+struct MentionRow {
+    message_id: i32,
+    contact_id: String,
+    nick: String,
+    position: i32,
+}
+
+#[derive(FromRow)]
+struct MentionedContact {
+    id: String,
+    nick: String,
+}
+
+#[derive(Clone, Serialize)]
+struct MentionNotification {
+    priority: &'static str, // Always "high", mentions are meant to get through
+    table: &'static str,
+    connected: String,
+    message_id: i32,
+    sender: String,
+    mentions: Vec<Mention>,
+}
+
+// Define a struct to represent a keyword alert for API responses
+#[derive(Debug, Serialize, FromRow)]
+struct KeywordAlert {
+    id: i32,
+    pattern: String,
+    is_regex: bool,
+    created_at: DateTime<Utc>,
+}
+
+// Define a struct to capture a new keyword alert from the request
+#[derive(Deserialize)]
+struct NewKeywordAlert {
+    pattern: String,
+    #[serde(default)]
+    is_regex: bool, // A plain word or phrase unless set
+}
+
+// Define the compiled keyword alerts shared by every scan, loaded on the first scan after a change
+struct AlertCache {
+    generation: u64, // Bumped by every change so a load that raced with one is not kept
+    alerts: Option<Arc<Vec<(i32, Regex)>>>,
+}
+
+static ALERT_CACHE: Mutex<AlertCache> = Mutex::new(AlertCache {
+    generation: 0,
+    alerts: None,
+});
+
+#[derive(Clone, Serialize)]
+struct KeywordAlertEvent {
+    table: &'static str,
+    connected: String,
+    message_id: i32,
+    alert_ids: Vec<i32>,
+}
+
+// Define a struct to represent a flagged message in the alerts feed
+#[derive(Debug, Serialize, FromRow)]
+struct AlertHit {
+    id: i32,
+    alert_id: i32,
+    pattern: String,
+    message_table: String,
+    message_id: i32,
+    connected: String,
+    sender: String,
+    preview: String,
+    matched: String, // The text the alert matched
+    created_at: DateTime<Utc>,
+}
+
+// Define the query parameters accepted by the alerts feed
+#[derive(Deserialize)]
+struct FeedQuery {
+    before: Option<i32>, // Only hits older than this id, the newest page when missing
+    limit: Option<u32>,
+}
+
+// Define a struct to represent one page of the alerts feed for API responses
+#[derive(Serialize)]
+struct FeedPage {
+    hits: Vec<AlertHit>,
+    next_before: Option<i32>, // Cursor of the next older page, None on the last page
+}
+
+// Function to map a message table to the contacts its mentions are resolved against
+pub fn people_table(table: &str) -> Option<&'static str> {
+    match table {
+        "messages_send_to_my_client" => Some("my_server_people"),
+        "messages_send_to_other_client" => Some("other_server_people"),
+        _ => None,
+    }
+}
+
+fn is_nick_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+// Finds the @nick mentions of a text with the character offset of their @. The @ has to
+// start a word so addresses like name@example.com are left alone, and a trailing dot is
+// taken as the end of the sentence.
+fn find_mentions(content: &str) -> Vec<(i32, &str)> {
+    let mut mentions = Vec::new();
+    let mut previous = None;
+    for (position, (index, c)) in content.char_indices().enumerate() {
+        if c == '@' && !previous.is_some_and(is_nick_char) {
+            let rest = &content[index + 1..];
+            let end = rest.find(|c: char| !is_nick_char(c)).unwrap_or(rest.len());
+            let nick = rest[..end].trim_end_matches(['.', '-']);
+            if !nick.is_empty() {
+                mentions.push((position as i32, nick));
+                if mentions.len() == MAX_MENTIONS_PER_MESSAGE {
+                    break;
+                }
+            }
+        }
+        previous = Some(c);
+    }
+    mentions
+}
+
+// Function to replace the stored mentions of a message with the ones in its content, returns
+// the mentions that were resolved. A nick resolves when exactly one contact carries it, in
+// any case; ambiguous and unknown nicks stay plain text.
+pub async fn store_mentions(
+    conn: &mut MySqlConnection,
+    table: &str,
+    message_id: i32,
+    content: &str,
+) -> Result<Vec<Mention>, sqlx::Error> {
+    query("DELETE FROM message_mentions WHERE message_table = ? AND message_id = ?")
+        .bind(table)
+        .bind(message_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let found = find_mentions(content);
+    let people_table = match people_table(table) {
+        Some(people_table) if !found.is_empty() => people_table,
+        _ => return Ok(Vec::new()),
+    };
+
+    let mut nicks: Vec<String> = found.iter().map(|(_, nick)| nick.to_lowercase()).collect();
+    nicks.sort_unstable();
+    nicks.dedup();
+
+    let mut builder = QueryBuilder::<MySql>::new(format!(
+        "SELECT id, nick FROM {} WHERE nick IN (",
+        people_table
+    ));
+    let mut separated = builder.separated(", ");
+    for nick in &nicks {
+        separated.push_bind(nick);
+    }
+    separated.push_unseparated(")");
+
+    let mut contacts: HashMap<String, Option<MentionedContact>> = HashMap::new();
+    for contact in builder
+        .build_query_as::<MentionedContact>()
+        .fetch_all(&mut *conn)
+        .await?
+    {
+        contacts
+            .entry(contact.nick.to_lowercase())
+            .and_modify(|resolved| *resolved = None)
+            .or_insert(Some(contact));
+    }
+
+    let mentions: Vec<Mention> = found
+        .into_iter()
+        .filter_map(|(position, nick)| {
+            let contact = contacts.get(&nick.to_lowercase())?.as_ref()?;
+            Some(Mention {
+                contact_id: contact.id.clone(),
+                nick: contact.nick.clone(),
+                position,
+            })
+        })
+        .collect();
+    if mentions.is_empty() {
+        return Ok(mentions);
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "INSERT INTO message_mentions (message_table, message_id, position, contact_id, nick) ",
+    );
+    builder.push_values(&mentions, |mut row, mention| {
+        row.push_bind(table)
+            .push_bind(message_id)
+            .push_bind(mention.position)
+            .push_bind(&mention.contact_id)
+            .push_bind(&mention.nick);
+    });
+    builder.build().execute(&mut *conn).await?;
+
+    Ok(mentions)
+}
+
+// Function to load the mentions of several messages, in the order they appear in the content
+pub async fn fetch_mentions(
+    pool: &AppState,
+    table: &str,
+    message_ids: &[i32],
+) -> Result<HashMap<i32, Vec<Mention>>, sqlx::Error> {
+    let mut mentions: HashMap<i32, Vec<Mention>> = HashMap::new();
+    if message_ids.is_empty() {
+        return Ok(mentions);
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT message_id, contact_id, nick, position FROM message_mentions WHERE message_table = ",
+    );
+    builder.push_bind(table).push(" AND message_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in message_ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(") ORDER BY message_id, position");
+
+    for row in builder
+        .build_query_as::<MentionRow>()
+        .fetch_all(&pool.db_pool)
+        .await?
+    {
+        mentions.entry(row.message_id).or_default().push(Mention {
+            contact_id: row.contact_id,
+            nick: row.nick,
+            position: row.position,
+        });
+    }
+
+    Ok(mentions)
+}
+
+// Plain words and phrases match as whole words in any case, regexes are taken as written
+pub fn compile_alert(pattern: &str, is_regex: bool) -> Result<Regex, regex::Error> {
+    let source = if is_regex {
+        pattern.to_string()
+    } else {
+        let boundary = |c: Option<char>| match c {
+            Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+            _ => "",
+        };
+        format!(
+            "(?i){}{}{}",
+            boundary(pattern.chars().next()),
+            regex::escape(pattern),
+            boundary(pattern.chars().last())
+        )
+    };
+    RegexBuilder::new(&source)
+        .size_limit(MAX_REGEX_BYTES)
+        .build()
+}
+
+// Function to find a plain word or phrase as a whole word in any case, the same matches
+// `compile_alert` finds for a plain pattern without building a regex
+pub fn contains_phrase(content: &str, phrase: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let content = content.to_lowercase();
+    let phrase = phrase.to_lowercase();
+    if phrase.is_empty() {
+        return false;
+    }
+    let check_start = phrase.chars().next().is_some_and(is_word);
+    let check_end = phrase.chars().next_back().is_some_and(is_word);
+
+    content.char_indices().any(|(start, _)| {
+        let end = start + phrase.len();
+        content[start..].starts_with(&phrase)
+            && !(check_start && content[..start].chars().next_back().is_some_and(is_word))
+            && !(check_end && content[end..].chars().next().is_some_and(is_word))
+    })
+}
+
+// Helper function to validate a keyword alert before it is stored
+fn validate_alert(alert: &NewKeywordAlert) -> Result<&str, String> {
+    let pattern = alert.pattern.trim();
+    if pattern.is_empty() {
+        return Err("Pattern cannot be empty".to_string());
+    }
+    if pattern.chars().count() > MAX_PATTERN_CHARS {
+        return Err(format!(
+            "Patterns are limited to {} characters",
+            MAX_PATTERN_CHARS
+        ));
+    }
+    match compile_alert(pattern, alert.is_regex) {
+        Ok(_) => Ok(pattern),
+        Err(e) => Err(format!("Invalid pattern: {}", e)),
+    }
+}
+
+// Function to drop the compiled keyword alerts, the next scan compiles them again
+pub fn invalidate_alert_cache() {
+    let mut cache = ALERT_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    cache.generation += 1;
+    cache.alerts = None;
+}
+
+// Returns the compiled keyword alerts, compiling them only when they changed since the last scan
+async fn compiled_alerts(pool: &AppState) -> Result<Arc<Vec<(i32, Regex)>>, sqlx::Error> {
+    let generation = {
+        let cache = ALERT_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(alerts) = &cache.alerts {
+            return Ok(alerts.clone());
+        }
+        cache.generation
+    };
+
+    let rows = sqlx::query_as::<_, KeywordAlert>(
+        "SELECT id, pattern, is_regex, created_at FROM keyword_alerts ORDER BY id",
+    )
+    .fetch_all(&pool.db_pool)
+    .await?;
+    let mut alerts = Vec::with_capacity(rows.len());
+    for alert in rows {
+        match compile_alert(&alert.pattern, alert.is_regex) {
+            Ok(regex) => alerts.push((alert.id, regex)),
+            Err(e) => eprintln!("Error compiling keyword alert {}: {}", alert.id, e),
+        }
+    }
+    let alerts = Arc::new(alerts);
+
+    let mut cache = ALERT_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    if cache.generation == generation {
+        cache.alerts = Some(alerts.clone());
+    }
+    Ok(alerts)
+}
+
+// Records a hit for every alert matching the content, returns the ids of those alerts
+async fn check_keyword_alerts(
+    pool: &AppState,
+    table: &str,
+    message_id: i32,
+    connected: &str,
+    content: &str,
+) -> Result<Vec<i32>, sqlx::Error> {
+    let alerts = compiled_alerts(pool).await?;
+
+    let mut hits = Vec::new();
+    for (alert_id, regex) in alerts.iter() {
+        // A regex that also matches nothing at all should not flag every message
+        let found = regex.find_iter(content).find(|m| !m.as_str().is_empty());
+        if let Some(found) = found {
+            let matched: String = found.as_str().chars().take(MAX_MATCHED_CHARS).collect();
+            hits.push((*alert_id, matched));
+        }
+    }
+    if hits.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // A collapsed repeat comes back with the id of the message it was folded into
+    let mut builder = QueryBuilder::<MySql>::new(
+        "INSERT IGNORE INTO keyword_alert_hits (alert_id, message_table, message_id, connected, matched) ",
+    );
+    builder.push_values(&hits, |mut row, (alert_id, matched)| {
+        row.push_bind(*alert_id)
+            .push_bind(table)
+            .push_bind(message_id)
+            .push_bind(connected)
+            .push_bind(matched);
+    });
+    builder.build().execute(&pool.db_pool).await?;
+
+    Ok(hits.into_iter().map(|(alert_id, _)| alert_id).collect())
+}
+
+// Function to resolve the mentions of a newly stored message and check it against the keyword
+// alerts. Mentions are stored for every message and notify for incoming ones, and for every
+// message of a group chat. Keyword alerts are only raised for incoming messages outside of
+// group chats. Failures are only logged because the message itself is already stored.
+pub async fn scan_new_message(
+    pool: &AppState,
+    table: &'static str,
+    message_id: i32,
+    sender: &str,
+    connected: &str,
+    content: &str,
+) {
+    // Only messages of the conversation partner are incoming, there is no point in notifying
+    // the local side of its own mentions. Group chats have no local member to tell them
+    // apart, so their mentions always notify and they never raise alerts.
+    let incoming = sender == connected;
+    let notify = incoming || is_group_conversation(connected);
+
+    let mentions = match pool.db_pool.acquire().await {
+        Ok(mut conn) => store_mentions(&mut conn, table, message_id, content).await,
+        Err(e) => Err(e),
+    };
+    match mentions {
+        Ok(mentions) if notify && !mentions.is_empty() => pool.emit_event(
+            "mention-notification",
+            MentionNotification {
+                priority: "high",
+                table,
+                connected: connected.to_string(),
+                message_id,
+                sender: sender.to_string(),
+                mentions,
+            },
+        ),
+        Ok(_) => {}
+        Err(e) => eprintln!(
+            "Error storing mentions of message {} in '{}': {}",
+            message_id, table, e
+        ),
+    }
+
+    if !incoming {
+        return;
+    }
+    match check_keyword_alerts(pool, table, message_id, connected, content).await {
+        Ok(alert_ids) if !alert_ids.is_empty() => pool.emit_event(
+            "keyword-alert",
+            KeywordAlertEvent {
+                table,
+                connected: connected.to_string(),
+                message_id,
+                alert_ids,
+            },
+        ),
+        Ok(_) => {}
+        Err(e) => eprintln!(
+            "Error checking keyword alerts for message {} in '{}': {}",
+            message_id, table, e
+        ),
+    }
+}
+
+// Handler function to list every keyword alert
+#[get("/alerts")]
+pub async fn get_keyword_alerts(pool: web::Data<AppState>) -> impl Responder {
+    match sqlx::query_as::<_, KeywordAlert>(
+        "SELECT id, pattern, is_regex, created_at FROM keyword_alerts ORDER BY created_at, id",
+    )
+    .fetch_all(&pool.db_pool)
+    .await
+    {
+        Ok(alerts) => HttpResponse::Ok().json(alerts),
+        Err(e) => {
+            eprintln!("Error retrieving keyword alerts: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to register a keyword alert, matched against incoming messages from now on
+#[post("/alerts/")]
+pub async fn create_keyword_alert(
+    pool: web::Data<AppState>,
+    alert: web::Json<NewKeywordAlert>,
+) -> impl Responder {
+    let pattern = match validate_alert(&alert) {
+        Ok(pattern) => pattern,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+
+    let result = async {
+        let id = query("INSERT INTO keyword_alerts (pattern, is_regex) VALUES (?, ?)")
+            .bind(pattern)
+            .bind(alert.is_regex)
+            .execute(&pool.db_pool)
+            .await?
+            .last_insert_id() as i32;
+        sqlx::query_as::<_, KeywordAlert>(
+            "SELECT id, pattern, is_regex, created_at FROM keyword_alerts WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_one(&pool.db_pool)
+        .await
+    }
+    .await;
+
+    match result {
+        Ok(alert) => {
+            invalidate_alert_cache();
+            HttpResponse::Ok().json(alert)
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            HttpResponse::Conflict().body("This alert already exists")
+        }
+        Err(e) => {
+            eprintln!("Error creating keyword alert: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to remove a keyword alert along with the messages it flagged
+#[delete("/alerts/{id}")]
+pub async fn delete_keyword_alert(pool: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    match query("DELETE FROM keyword_alerts WHERE id = ?")
+        .bind(id.into_inner())
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => {
+            invalidate_alert_cache();
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("Error deleting keyword alert: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list the messages flagged by keyword alerts, newest first. Alerts only
+// fire on incoming messages, so the ones their receiver deleted for themselves keep their
+// entry without a preview, expired ones drop out.
+#[get("/alerts/feed")]
+pub async fn get_alert_feed(
+    pool: web::Data<AppState>,
+    params: web::Query<FeedQuery>,
+) -> impl Responder {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_FEED_LIMIT)
+        .clamp(1, MAX_FEED_LIMIT);
+    let query_str = "
+        SELECT h.id, h.alert_id, a.pattern, h.message_table, h.message_id, h.connected,
+            COALESCE(mm.sender, mo.sender) AS sender,
+            CASE WHEN hide.message_id IS NULL
+                THEN LEFT(COALESCE(mm.content, mo.content), 100) ELSE '' END AS preview,
+            h.matched, h.created_at
+        FROM keyword_alert_hits h
+        JOIN keyword_alerts a ON a.id = h.alert_id
+        LEFT JOIN messages_send_to_my_client mm
+            ON h.message_table = 'messages_send_to_my_client' AND mm.id = h.message_id
+        LEFT JOIN messages_send_to_other_client mo
+            ON h.message_table = 'messages_send_to_other_client' AND mo.id = h.message_id
+        LEFT JOIN message_hides hide
+            ON hide.message_table = h.message_table AND hide.message_id = h.message_id
+            AND hide.viewer = COALESCE(mm.receiver, mo.receiver)
+        WHERE COALESCE(mm.id, mo.id) IS NOT NULL
+            AND (COALESCE(mm.expires_at, mo.expires_at) IS NULL
+                OR COALESCE(mm.expires_at, mo.expires_at) > CURRENT_TIMESTAMP)
+            AND (? IS NULL OR h.id < ?)
+        ORDER BY h.id DESC
+        LIMIT ?
+    ";
+
+    match sqlx::query_as::<_, AlertHit>(query_str)
+        .bind(params.before)
+        .bind(params.before)
+        .bind(limit)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(hits) => {
+            let next_before = match hits.last() {
+                Some(last) if hits.len() == limit as usize => Some(last.id),
+                _ => None,
+            };
+            HttpResponse::Ok().json(FeedPage { hits, next_before })
+        }
+        Err(e) => {
+            eprintln!("Error retrieving alerts feed: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile_alert, contains_phrase};
+
+    #[test]
+    fn matches_whole_words_in_any_case() {
+        assert!(contains_phrase("Call me URGENT please", "urgent"));
+        assert!(contains_phrase("urgent", "Urgent"));
+        assert!(contains_phrase("see you at 5, ok?", "5,"));
+        assert!(!contains_phrase("not urgently", "urgent"));
+        assert!(!contains_phrase("nonurgent", "urgent"));
+        assert!(!contains_phrase("anything", ""));
+    }
+
+    #[test]
+    fn finds_overlapping_phrases() {
+        assert!(contains_phrase("xa a a", "a a"));
+    }
+
+    #[test]
+    fn agrees_with_plain_alerts() {
+        for (content, phrase) in [
+            ("Über alles", "über"),
+            ("snake_case word", "case"),
+            ("ship it!", "it!"),
+            ("a-b c", "b c"),
+        ] {
+            let regex = compile_alert(phrase, false).unwrap();
+            assert_eq!(
+                contains_phrase(content, phrase),
+                regex.is_match(content),
+                "{:?} in {:?}",
+                phrase,
+                content
+            );
+        }
+    }
+}
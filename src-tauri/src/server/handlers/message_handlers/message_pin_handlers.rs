@@ -0,0 +1,424 @@
+use super::message_get_set_handlers::{to_responses, Message, MESSAGE_COLUMNS, NOT_EXPIRED};
+use super::message_group_chat_handlers::{is_group_conversation, GROUP_CONVERSATION_REFUSED};
+use crate::server::config::env_or;
+use crate::server::AppState;
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow};
+
+const DEFAULT_MAX_PINNED_MESSAGES: i64 = 5;
+const DEFAULT_STARRED_LIMIT: u32 = 50;
+const MAX_STARRED_LIMIT: u32 = 200;
+
+// Define the query parameters accepted by the starred message listing
+#[derive(Deserialize)]
+struct StarredQuery {
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+#[derive(Clone, Serialize)]
+struct MarkEvent {
+    table: &'static str,
+    id: i32,
+    connected: String,
+}
+
+#[derive(FromRow)]
+struct MarkedMessage {
+    connected: String,
+    deleted_at: Option<DateTime<Utc>>,
+    pinned_at: Option<DateTime<Utc>>,
+}
+
+// Function to list the pinned messages of a conversation, most recently pinned first
+pub async fn fetch_pinned_ids(
+    pool: &AppState,
+    table: &str,
+    connected: &str,
+) -> Result<Vec<i32>, sqlx::Error> {
+    let query_str = format!(
+        "SELECT id FROM {} WHERE connected = ? AND pinned_at IS NOT NULL AND {} \
+         ORDER BY pinned_at DESC, id DESC",
+        table, NOT_EXPIRED
+    );
+    sqlx::query_scalar::<_, i32>(&query_str)
+        .bind(connected)
+        .fetch_all(&pool.db_pool)
+        .await
+}
+
+// Define the reasons a message cannot be starred or pinned
+enum Unmarkable {
+    Missing,
+    Unsent, // There is nothing left to keep
+    Group,
+}
+
+impl Unmarkable {
+    fn to_response(&self) -> HttpResponse {
+        match self {
+            Unmarkable::Missing => HttpResponse::NotFound().finish(),
+            Unmarkable::Unsent => HttpResponse::Gone().body("Message was unsent"),
+            Unmarkable::Group => HttpResponse::BadRequest().body(GROUP_CONVERSATION_REFUSED),
+        }
+    }
+}
+
+// Group chat messages are only reachable through the chat endpoints, the starred and pinned
+// listings have no member to check, so they cannot be marked either
+fn check_markable(message: Option<MarkedMessage>) -> Result<MarkedMessage, Unmarkable> {
+    match message {
+        None => Err(Unmarkable::Missing),
+        Some(message) if message.deleted_at.is_some() => Err(Unmarkable::Unsent),
+        Some(message) if is_group_conversation(&message.connected) => Err(Unmarkable::Group),
+        Some(message) => Ok(message),
+    }
+}
+
+async fn set_starred(pool: &AppState, table: &'static str, id: i32, starred: bool) -> HttpResponse {
+    let select_query = format!(
+        "SELECT connected, deleted_at, pinned_at FROM {} WHERE id = ? AND {}",
+        table, NOT_EXPIRED
+    );
+    let message = match sqlx::query_as::<_, MarkedMessage>(&select_query)
+        .bind(id)
+        .fetch_optional(&pool.db_pool)
+        .await
+    {
+        Ok(message) => match check_markable(message) {
+            Ok(message) => message,
+            Err(reason) => return reason.to_response(),
+        },
+        Err(e) => {
+            eprintln!("Error retrieving message from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let update_query = format!(
+        "UPDATE {} SET starred_at = CASE WHEN ? THEN COALESCE(starred_at, CURRENT_TIMESTAMP) END \
+         WHERE id = ?",
+        table
+    );
+    match query(&update_query)
+        .bind(starred)
+        .bind(id)
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(_) => {
+            pool.emit_event(
+                if starred {
+                    "message-starred"
+                } else {
+                    "message-unstarred"
+                },
+                MarkEvent {
+                    table,
+                    id,
+                    connected: message.connected,
+                },
+            );
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("Error starring message in '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Pins are limited per conversation, pinning an already pinned message changes nothing
+async fn pin_message(pool: &AppState, table: &'static str, id: i32) -> HttpResponse {
+    let max_pinned = env_or("MAX_PINNED_MESSAGES", DEFAULT_MAX_PINNED_MESSAGES);
+
+    let result = async {
+        let mut tx = pool.db_pool.begin().await?;
+
+        let select_query = format!(
+            "SELECT connected, deleted_at, pinned_at FROM {} WHERE id = ? AND {} FOR UPDATE",
+            table, NOT_EXPIRED
+        );
+        let message = sqlx::query_as::<_, MarkedMessage>(&select_query)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let message = match check_markable(message) {
+            Ok(message) => message,
+            Err(reason) => return Ok(Err(reason.to_response())),
+        };
+        if message.pinned_at.is_some() {
+            return Ok(Ok(None));
+        }
+
+        let pinned_query = format!(
+            "SELECT id FROM {} WHERE connected = ? AND pinned_at IS NOT NULL AND {} FOR UPDATE",
+            table, NOT_EXPIRED
+        );
+        let pinned = sqlx::query_scalar::<_, i32>(&pinned_query)
+            .bind(&message.connected)
+            .fetch_all(&mut *tx)
+            .await?;
+        if pinned.len() as i64 >= max_pinned {
+            return Ok(Err(HttpResponse::Conflict().body(format!(
+                "A conversation can have at most {} pinned messages",
+                max_pinned
+            ))));
+        }
+
+        let update_query = format!(
+            "UPDATE {} SET pinned_at = CURRENT_TIMESTAMP WHERE id = ?",
+            table
+        );
+        query(&update_query).bind(id).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(Ok(Some(message.connected)))
+    }
+    .await;
+
+    match result {
+        Ok(Ok(pinned)) => {
+            if let Some(connected) = pinned {
+                pool.emit_event(
+                    "message-pinned",
+                    MarkEvent {
+                        table,
+                        id,
+                        connected,
+                    },
+                );
+            }
+            HttpResponse::Ok().finish()
+        }
+        Ok(Err(response)) => response,
+        Err(e) => {
+            eprintln!("Error pinning message in '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn unpin_message(pool: &AppState, table: &'static str, id: i32) -> HttpResponse {
+    let select_query = format!(
+        "SELECT connected, deleted_at, pinned_at FROM {} WHERE id = ? AND {}",
+        table, NOT_EXPIRED
+    );
+    let message = match sqlx::query_as::<_, MarkedMessage>(&select_query)
+        .bind(id)
+        .fetch_optional(&pool.db_pool)
+        .await
+    {
+        Ok(Some(message)) => message,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving message from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if message.pinned_at.is_none() {
+        return HttpResponse::Ok().finish();
+    }
+
+    let update_query = format!("UPDATE {} SET pinned_at = NULL WHERE id = ?", table);
+    match query(&update_query).bind(id).execute(&pool.db_pool).await {
+        Ok(_) => {
+            pool.emit_event(
+                "message-unpinned",
+                MarkEvent {
+                    table,
+                    id,
+                    connected: message.connected,
+                },
+            );
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("Error unpinning message in '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn messages_response(
+    pool: &AppState,
+    table: &str,
+    messages: Result<Vec<Message>, sqlx::Error>,
+) -> HttpResponse {
+    let messages = match messages {
+        Ok(messages) => messages,
+        Err(e) => {
+            eprintln!("Error retrieving messages from '{}' table: {}", table, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    match to_responses(pool, table, None, messages).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => {
+            eprintln!(
+                "Error loading message details from '{}' table: {}",
+                table, e
+            );
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Starred messages of every conversation, most recently starred first
+async fn list_starred(pool: &AppState, table: &str, params: &StarredQuery) -> HttpResponse {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_STARRED_LIMIT)
+        .clamp(1, MAX_STARRED_LIMIT);
+    let query_str = format!(
+        "SELECT {} FROM {} WHERE starred_at IS NOT NULL AND {} \
+         ORDER BY starred_at DESC, id DESC LIMIT ? OFFSET ?",
+        MESSAGE_COLUMNS, table, NOT_EXPIRED
+    );
+    let messages = sqlx::query_as::<_, Message>(&query_str)
+        .bind(limit)
+        .bind(params.offset.unwrap_or(0))
+        .fetch_all(&pool.db_pool)
+        .await;
+    messages_response(pool, table, messages).await
+}
+
+async fn list_pinned(pool: &AppState, table: &str, connected: &str) -> HttpResponse {
+    if is_group_conversation(connected) {
+        return HttpResponse::BadRequest().body(GROUP_CONVERSATION_REFUSED);
+    }
+
+    let query_str = format!(
+        "SELECT {} FROM {} WHERE connected = ? AND pinned_at IS NOT NULL AND {} \
+         ORDER BY pinned_at DESC, id DESC",
+        MESSAGE_COLUMNS, table, NOT_EXPIRED
+    );
+    let messages = sqlx::query_as::<_, Message>(&query_str)
+        .bind(connected)
+        .fetch_all(&pool.db_pool)
+        .await;
+    messages_response(pool, table, messages).await
+}
+
+// Handler function to star a 'my-client' message
+#[put("/my/messages/{id}/star")]
+pub async fn star_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    set_starred(&pool, "messages_send_to_my_client", id.into_inner(), true).await
+}
+
+// Handler function to star an 'other-client' message
+#[put("/other/messages/{id}/star")]
+pub async fn star_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    set_starred(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        true,
+    )
+    .await
+}
+
+// Handler function to remove the star of a 'my-client' message
+#[delete("/my/messages/{id}/star")]
+pub async fn unstar_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    set_starred(&pool, "messages_send_to_my_client", id.into_inner(), false).await
+}
+
+// Handler function to remove the star of an 'other-client' message
+#[delete("/other/messages/{id}/star")]
+pub async fn unstar_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    set_starred(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        false,
+    )
+    .await
+}
+
+// Handler function to list the starred 'my-client' messages of every conversation
+#[get("/my/starred")]
+pub async fn get_starred_messages_my_client(
+    pool: web::Data<AppState>,
+    params: web::Query<StarredQuery>,
+) -> impl Responder {
+    list_starred(&pool, "messages_send_to_my_client", &params).await
+}
+
+// Handler function to list the starred 'other-client' messages of every conversation
+#[get("/other/starred")]
+pub async fn get_starred_messages_other_client(
+    pool: web::Data<AppState>,
+    params: web::Query<StarredQuery>,
+) -> impl Responder {
+    list_starred(&pool, "messages_send_to_other_client", &params).await
+}
+
+// Handler function to pin a 'my-client' message to the top of its conversation
+#[put("/my/messages/{id}/pin")]
+pub async fn pin_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    pin_message(&pool, "messages_send_to_my_client", id.into_inner()).await
+}
+
+// Handler function to pin an 'other-client' message to the top of its conversation
+#[put("/other/messages/{id}/pin")]
+pub async fn pin_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    pin_message(&pool, "messages_send_to_other_client", id.into_inner()).await
+}
+
+// Handler function to unpin a 'my-client' message
+#[delete("/my/messages/{id}/pin")]
+pub async fn unpin_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    unpin_message(&pool, "messages_send_to_my_client", id.into_inner()).await
+}
+
+// Handler function to unpin an 'other-client' message
+#[delete("/other/messages/{id}/pin")]
+pub async fn unpin_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    unpin_message(&pool, "messages_send_to_other_client", id.into_inner()).await
+}
+
+// Handler function to list the pinned messages of a 'my-client' conversation
+#[get("/my/conversations/{connected}/pinned")]
+pub async fn get_pinned_messages_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    list_pinned(&pool, "messages_send_to_my_client", &connected).await
+}
+
+// Handler function to list the pinned messages of an 'other-client' conversation
+#[get("/other/conversations/{connected}/pinned")]
+pub async fn get_pinned_messages_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+) -> impl Responder {
+    list_pinned(&pool, "messages_send_to_other_client", &connected).await
+}
@@ -0,0 +1,348 @@
+use super::message_get_set_handlers::NOT_EXPIRED;
+use crate::server::AppState;
+use actix_web::{delete, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow, MySql, QueryBuilder};
+use std::collections::HashMap;
+use unicode_properties::emoji::{
+    is_emoji_presentation_selector, is_regional_indicator, is_text_presentation_selector, is_zwj,
+    UnicodeEmoji,
+};
+
+const MAX_EMOJI_BYTES: usize = 64;
+
+// Define a struct to represent the reactions of one emoji on a message
+#[derive(Debug, Clone, Serialize)]
+pub struct ReactionSummary {
+    emoji: String,
+    count: usize,
+    reactors: Vec<String>,
+}
+
+// Define a struct to capture a reaction from the request
+#[derive(Deserialize)]
+struct Reaction {
+    reactor: String,
+    emoji: String,
+}
+
+#[derive(Clone, Serialize)]
+struct ReactionEvent {
+    table: &'static str,
+    message_id: i32,
+    connected: String,
+    reactor: String,
+    emoji: String,
+}
+
+#[derive(FromRow)]
+struct ReactionRow {
+    message_id: i32,
+    reactor: String,
+    emoji: String,
+}
+
+#[derive(FromRow)]
+struct ReactedMessage {
+    connected: String,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+// Function to load the reactions of several messages, grouped per emoji in the order
+// the emoji was first used
+pub async fn fetch_reactions(
+    pool: &AppState,
+    table: &str,
+    message_ids: &[i32],
+) -> Result<HashMap<i32, Vec<ReactionSummary>>, sqlx::Error> {
+    let mut reactions: HashMap<i32, Vec<ReactionSummary>> = HashMap::new();
+    if message_ids.is_empty() {
+        return Ok(reactions);
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT message_id, reactor, emoji FROM message_reactions WHERE message_table = ",
+    );
+    builder.push_bind(table).push(" AND message_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in message_ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(") ORDER BY created_at, reactor");
+
+    for row in builder
+        .build_query_as::<ReactionRow>()
+        .fetch_all(&pool.db_pool)
+        .await?
+    {
+        let summaries = reactions.entry(row.message_id).or_default();
+        match summaries.iter_mut().find(|s| s.emoji == row.emoji) {
+            Some(summary) => {
+                summary.count += 1;
+                summary.reactors.push(row.reactor);
+            }
+            None => summaries.push(ReactionSummary {
+                emoji: row.emoji,
+                count: 1,
+                reactors: vec![row.reactor],
+            }),
+        }
+    }
+
+    Ok(reactions)
+}
+
+// Skin tone modifiers, the Fitzpatrick types 1-2 to 6
+fn is_skin_tone(c: char) -> bool {
+    matches!(c, '\u{1F3FB}'..='\u{1F3FF}')
+}
+
+// Pictographs, flags and the characters gluing them into sequences, ASCII such as the keycap
+// digits is left out so plain text never passes
+fn is_emoji_part(c: char) -> bool {
+    !c.is_ascii()
+        && (c.is_emoji_char()
+            || is_zwj(c)
+            || is_emoji_presentation_selector(c)
+            || is_text_presentation_selector(c)
+            || is_skin_tone(c)
+            || is_regional_indicator(c))
+}
+
+// Accepts a unicode emoji (sequence) or a short code such as ':thumbs_up:'
+fn validate_emoji(emoji: &str) -> Result<&str, &'static str> {
+    let emoji = emoji.trim();
+    let valid = if emoji.len() > 2 && emoji.starts_with(':') && emoji.ends_with(':') {
+        emoji[1..emoji.len() - 1]
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b'+')
+    } else {
+        emoji.chars().all(is_emoji_part)
+            && emoji
+                .chars()
+                .any(|c| c.is_emoji_char() && !is_skin_tone(c) && !is_zwj(c))
+    };
+
+    if !valid {
+        Err("Reaction must be an emoji or a :short_code:")
+    } else if emoji.len() > MAX_EMOJI_BYTES {
+        Err("Reaction is too long")
+    } else {
+        Ok(emoji)
+    }
+}
+
+async fn fetch_reacted_message(
+    pool: &AppState,
+    table: &str,
+    id: i32,
+) -> Result<ReactedMessage, HttpResponse> {
+    let query_str = format!(
+        "SELECT connected, deleted_at FROM {} WHERE id = ? AND {}",
+        table, NOT_EXPIRED
+    );
+    match sqlx::query_as::<_, ReactedMessage>(&query_str)
+        .bind(id)
+        .fetch_optional(&pool.db_pool)
+        .await
+    {
+        Ok(Some(message)) => Ok(message),
+        Ok(None) => Err(HttpResponse::NotFound().finish()),
+        Err(e) => {
+            eprintln!("Error retrieving message from '{}' table: {}", table, e);
+            Err(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+async fn add_reaction(
+    pool: &AppState,
+    table: &'static str,
+    id: i32,
+    reaction: &Reaction,
+) -> HttpResponse {
+    let emoji = match validate_emoji(&reaction.emoji) {
+        Ok(emoji) => emoji,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+    let message = match fetch_reacted_message(pool, table, id).await {
+        Ok(message) => message,
+        Err(response) => return response,
+    };
+    if message.deleted_at.is_some() {
+        return HttpResponse::Gone().body("Message was unsent");
+    }
+
+    // The primary key allows one reaction per emoji per reactor
+    let result = query(
+        "INSERT IGNORE INTO message_reactions (message_table, message_id, reactor, emoji) VALUES (?, ?, ?, ?)",
+    )
+    .bind(table)
+    .bind(id)
+    .bind(&reaction.reactor)
+    .bind(emoji)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(result) => {
+            if result.rows_affected() > 0 {
+                pool.emit_event(
+                    "reaction-added",
+                    ReactionEvent {
+                        table,
+                        message_id: id,
+                        connected: message.connected,
+                        reactor: reaction.reactor.clone(),
+                        emoji: emoji.to_string(),
+                    },
+                );
+            }
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("Error adding reaction: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn remove_reaction(
+    pool: &AppState,
+    table: &'static str,
+    id: i32,
+    reaction: &Reaction,
+) -> HttpResponse {
+    let emoji = reaction.emoji.trim();
+    let message = match fetch_reacted_message(pool, table, id).await {
+        Ok(message) => message,
+        Err(response) => return response,
+    };
+
+    let result = query(
+        "DELETE FROM message_reactions WHERE message_table = ? AND message_id = ? AND reactor = ? AND emoji = ?",
+    )
+    .bind(table)
+    .bind(id)
+    .bind(&reaction.reactor)
+    .bind(emoji)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => {
+            pool.emit_event(
+                "reaction-removed",
+                ReactionEvent {
+                    table,
+                    message_id: id,
+                    connected: message.connected,
+                    reactor: reaction.reactor.clone(),
+                    emoji: emoji.to_string(),
+                },
+            );
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("Error removing reaction: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to react to a 'my-client' message
+#[post("/my/messages/{id}/reactions")]
+pub async fn add_reaction_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    reaction: web::Json<Reaction>,
+) -> impl Responder {
+    add_reaction(
+        &pool,
+        "messages_send_to_my_client",
+        id.into_inner(),
+        &reaction,
+    )
+    .await
+}
+
+// Handler function to react to an 'other-client' message
+#[post("/other/messages/{id}/reactions")]
+pub async fn add_reaction_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    reaction: web::Json<Reaction>,
+) -> impl Responder {
+    add_reaction(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        &reaction,
+    )
+    .await
+}
+
+// Handler function to take back a reaction on a 'my-client' message
+#[delete("/my/messages/{id}/reactions")]
+pub async fn remove_reaction_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    reaction: web::Query<Reaction>,
+) -> impl Responder {
+    remove_reaction(
+        &pool,
+        "messages_send_to_my_client",
+        id.into_inner(),
+        &reaction,
+    )
+    .await
+}
+
+// Handler function to take back a reaction on an 'other-client' message
+#[delete("/other/messages/{id}/reactions")]
+pub async fn remove_reaction_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    reaction: web::Query<Reaction>,
+) -> impl Responder {
+    remove_reaction(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        &reaction,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_emoji;
+
+    #[test]
+    fn accepts_emoji_and_sequences() {
+        for emoji in ["👍", "❤️", "👍🏽", "👩‍💻", "👨‍👩‍👧", "🇹🇷", ":thumbs_up:"]
+        {
+            assert_eq!(validate_emoji(emoji), Ok(emoji), "{}", emoji);
+        }
+    }
+
+    #[test]
+    fn rejects_text() {
+        for text in [
+            "Привет",
+            "é",
+            "ok",
+            "1",
+            "",
+            "👍 👍",
+            "\u{200D}",
+            "\u{FE0F}",
+            "🏽",
+            "👍x",
+        ] {
+            assert!(validate_emoji(text).is_err(), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::message_conversation_handlers::refresh_conversation;
+use super::message_get_set_handlers::NOT_EXPIRED;
+use super::message_group_chat_handlers::{is_group_conversation, GROUP_CONVERSATION_REFUSED};
+use crate::server::AppState;
+use actix_web::{post, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, QueryBuilder};
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ReceiptState {
+    Delivered,
+    Read,
+}
+
+// Define a struct to capture a receipt covering a conversation up to a message
+#[derive(Deserialize)]
+struct Receipt {
+    state: ReceiptState,
+    reader: String, // Only messages received by the reader are marked
+    up_to_id: i32,  // Inclusive
+}
+
+#[derive(Clone, Serialize)]
+struct MessageStatusEvent {
+    table: &'static str,
+    connected: String,
+    state: ReceiptState,
+    ids: Vec<i32>,
+}
+
+#[derive(Serialize)]
+struct ReceiptResult {
+    updated: Vec<i32>,
+}
+
+async fn mark_conversation(
+    pool: &AppState,
+    table: &'static str,
+    connected: String,
+    receipt: &Receipt,
+) -> Result<Vec<i32>, sqlx::Error> {
+    // A read message is always delivered too, so both states only move forward
+    let pending_column = match receipt.state {
+        ReceiptState::Delivered => "delivered_at",
+        ReceiptState::Read => "read_at",
+    };
+
+    let mut tx = pool.db_pool.begin().await?;
+
+    let select_query = format!(
+        "SELECT id FROM {} WHERE connected = ? AND receiver = ? AND id <= ? \
+         AND {} IS NULL AND deleted_at IS NULL AND {} FOR UPDATE",
+        table, pending_column, NOT_EXPIRED
+    );
+    let ids = sqlx::query_scalar::<_, i32>(&select_query)
+        .bind(&connected)
+        .bind(&receipt.reader)
+        .bind(receipt.up_to_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    if !ids.is_empty() {
+        let mut builder = QueryBuilder::<MySql>::new(format!(
+            "UPDATE {} SET delivered_at = COALESCE(delivered_at, CURRENT_TIMESTAMP)",
+            table
+        ));
+        if let ReceiptState::Read = receipt.state {
+            builder.push(", read_at = COALESCE(read_at, CURRENT_TIMESTAMP)");
+        }
+        builder.push(" WHERE id IN (");
+        let mut separated = builder.separated(", ");
+        for id in &ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        builder.build().execute(&mut *tx).await?;
+        refresh_conversation(&mut tx, table, &connected).await?;
+    }
+
+    tx.commit().await?;
+
+    if !ids.is_empty() {
+        pool.emit_event(
+            "message-status",
+            MessageStatusEvent {
+                table,
+                connected,
+                state: receipt.state,
+                ids: ids.clone(),
+            },
+        );
+    }
+
+    Ok(ids)
+}
+
+async fn post_receipt(
+    pool: &AppState,
+    table: &'static str,
+    connected: String,
+    receipt: &Receipt,
+) -> HttpResponse {
+    if is_group_conversation(&connected) {
+        return HttpResponse::BadRequest().body(GROUP_CONVERSATION_REFUSED);
+    }
+
+    match mark_conversation(pool, table, connected, receipt).await {
+        Ok(updated) => HttpResponse::Ok().json(ReceiptResult { updated }),
+        Err(e) => {
+            eprintln!("Error updating receipts in '{}' table: {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to mark a 'my-client' conversation delivered or read up to a message
+#[post("/my/receipts/{connected}")]
+pub async fn post_receipt_my_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    receipt: web::Json<Receipt>,
+) -> impl Responder {
+    post_receipt(
+        &pool,
+        "messages_send_to_my_client",
+        connected.into_inner(),
+        &receipt,
+    )
+    .await
+}
+
+// Handler function to mark an 'other-client' conversation delivered or read up to a message
+#[post("/other/receipts/{connected}")]
+pub async fn post_receipt_other_client(
+    pool: web::Data<AppState>,
+    connected: web::Path<String>,
+    receipt: web::Json<Receipt>,
+) -> impl Responder {
+    post_receipt(
+        &pool,
+        "messages_send_to_other_client",
+        connected.into_inner(),
+        &receipt,
+    )
+    .await
+}
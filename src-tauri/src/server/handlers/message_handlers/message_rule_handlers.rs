@@ -0,0 +1,643 @@
+use super::message_conversation_handlers::update_conversation;
+use super::message_forward_handlers::{forward_messages, Channel, ForwardRequest};
+use super::message_get_set_handlers::{
+    check_participants, deliver_message, MessageFormat, NewMessage, SendError,
+};
+use super::message_mention_handlers::{contains_phrase, people_table};
+use crate::server::AppState;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Datelike, Local, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{query, FromRow};
+use std::cmp::Ordering;
+
+// Same limit as the name column
+const MAX_RULE_NAME_CHARS: usize = 255;
+const MAX_REPLY_CHARS: usize = 2000;
+// Same limit as the close_one_point column
+const MAX_LABEL_CHARS: usize = 255;
+const DEFAULT_REPLY_WINDOW_SECONDS: i64 = 60 * 60;
+const MAX_REPLY_WINDOW_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+const RULE_COLUMNS: &str =
+    "id, name, enabled, position, conditions, actions, stop_processing, created_at";
+
+// Define the time of day a rule applies, in the local time of the server
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct RuleSchedule {
+    start: NaiveTime,
+    end: NaiveTime, // Before start for a window running past midnight, equal for the whole day
+    #[serde(default)]
+    days: Vec<Weekday>, // Checked against the current day, every day when empty
+}
+
+impl RuleSchedule {
+    fn contains(&self, now: DateTime<Local>) -> bool {
+        if !self.days.is_empty() && !self.days.contains(&now.weekday()) {
+            return false;
+        }
+        let time = now.time();
+        match self.start.cmp(&self.end) {
+            Ordering::Less => self.start <= time && time < self.end,
+            Ordering::Greater => self.start <= time || time < self.end,
+            Ordering::Equal => true,
+        }
+    }
+}
+
+// Define the conditions an incoming message has to meet, all of the ones given
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct RuleConditions {
+    #[serde(default)]
+    senders: Vec<String>, // Contact ids, any sender when empty
+    group_id: Option<i32>, // Contact group the sender has to be a member of
+    schedule: Option<RuleSchedule>,
+    #[serde(default)]
+    keywords: Vec<String>, // Words or phrases of which at least one has to occur
+    label: Option<String>, // Label the message was sent with
+}
+
+impl RuleConditions {
+    fn matches(&self, message: &NewMessage, groups: &[i32], now: DateTime<Local>) -> bool {
+        (self.senders.is_empty() || self.senders.contains(&message.sender))
+            && self.group_id.is_none_or(|id| groups.contains(&id))
+            && self
+                .schedule
+                .as_ref()
+                .is_none_or(|schedule| schedule.contains(now))
+            && (self.keywords.is_empty()
+                || self
+                    .keywords
+                    .iter()
+                    .any(|keyword| contains_phrase(&message.content, keyword)))
+            && self
+                .label
+                .as_deref()
+                .is_none_or(|label| message.close_one_point.as_deref() == Some(label))
+    }
+}
+
+// Define the conversation a rule forwards matching messages to
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct ForwardTarget {
+    target: Channel, // "my" or "other"
+    sender: String,
+    receiver: String,
+    connected: String,
+}
+
+// Define what a rule does with a matching message, every action given is taken
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct RuleActions {
+    reply: Option<String>, // Template of the auto-reply, {nick} and {sender} are filled in
+    reply_window_seconds: Option<i64>, // A contact gets one auto-reply within it, an hour by default
+    label: Option<String>,
+    #[serde(default)]
+    mute: bool, // Marks the message read so it never counts as unread
+    forward: Option<ForwardTarget>,
+}
+
+impl RuleActions {
+    fn is_empty(&self) -> bool {
+        self.reply.is_none() && self.label.is_none() && !self.mute && self.forward.is_none()
+    }
+}
+
+// Define a struct to represent a message rule for API responses
+#[derive(Debug, Serialize, FromRow)]
+struct MessageRule {
+    id: i32,
+    name: String,
+    enabled: bool,
+    position: i32, // Rules are evaluated by ascending position
+    conditions: Json<RuleConditions>,
+    actions: Json<RuleActions>,
+    stop_processing: bool, // Later rules are skipped once this one matched
+    created_at: DateTime<Utc>,
+}
+
+// Define a struct to capture a new or replaced rule from the request
+#[derive(Deserialize)]
+struct RuleInput {
+    name: String,
+    enabled: Option<bool>, // Enabled unless set to false
+    #[serde(default)]
+    position: i32,
+    #[serde(default)]
+    conditions: RuleConditions,
+    actions: RuleActions,
+    #[serde(default)]
+    stop_processing: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct RulesAppliedEvent {
+    table: &'static str,
+    connected: String,
+    message_id: i32,
+    rule_ids: Vec<i32>,
+    muted: bool,
+    reply_id: Option<i32>,
+}
+
+// Collects what the rules did with one message
+#[derive(Default)]
+struct RulesApplied {
+    rule_ids: Vec<i32>,
+    relabelled: bool,
+    muted: bool,
+    reply_id: Option<i32>,
+}
+
+fn validate_label(label: &str) -> Result<(), String> {
+    if label.trim().is_empty() {
+        Err("Labels cannot be empty".to_string())
+    } else if label.chars().count() > MAX_LABEL_CHARS {
+        Err(format!(
+            "Labels are limited to {} characters",
+            MAX_LABEL_CHARS
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// Helper function to validate a rule before it is stored, returns the trimmed name
+fn validate_rule(rule: &RuleInput) -> Result<&str, String> {
+    let name = rule.name.trim();
+    if name.is_empty() {
+        return Err("Rule name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_RULE_NAME_CHARS {
+        return Err(format!(
+            "Rule names are limited to {} characters",
+            MAX_RULE_NAME_CHARS
+        ));
+    }
+
+    let conditions = &rule.conditions;
+    if conditions.senders.iter().any(|sender| sender.is_empty()) {
+        return Err("Senders cannot be empty".to_string());
+    }
+    if conditions
+        .keywords
+        .iter()
+        .any(|keyword| keyword.trim().is_empty())
+    {
+        return Err("Keywords cannot be empty".to_string());
+    }
+    if let Some(label) = &conditions.label {
+        validate_label(label)?;
+    }
+
+    let actions = &rule.actions;
+    if actions.is_empty() {
+        return Err("A rule needs at least one action".to_string());
+    }
+    if let Some(reply) = &actions.reply {
+        if reply.trim().is_empty() {
+            return Err("Auto-reply cannot be empty".to_string());
+        }
+        if reply.chars().count() > MAX_REPLY_CHARS {
+            return Err(format!(
+                "Auto-replies are limited to {} characters",
+                MAX_REPLY_CHARS
+            ));
+        }
+    }
+    if actions
+        .reply_window_seconds
+        .is_some_and(|seconds| !(0..=MAX_REPLY_WINDOW_SECONDS).contains(&seconds))
+    {
+        return Err(format!(
+            "The auto-reply window has to be between 0 and {} seconds",
+            MAX_REPLY_WINDOW_SECONDS
+        ));
+    }
+    if let Some(label) = &actions.label {
+        validate_label(label)?;
+    }
+    if let Some(forward) = &actions.forward {
+        check_participants(&forward.sender, &forward.receiver, &forward.connected)?;
+    }
+
+    Ok(name)
+}
+
+// Claims the auto-reply to a contact, false while an earlier one is still within its window.
+// The claim is shared by every rule so a contact never gets two replies at once, and it keeps
+// the window of the rule that made it.
+async fn claim_auto_reply(
+    pool: &AppState,
+    table: &str,
+    connected: &str,
+    window_seconds: i64,
+) -> Result<bool, sqlx::Error> {
+    query(
+        "DELETE FROM message_rule_replies WHERE message_table = ? AND connected = ? \
+         AND expires_at <= CURRENT_TIMESTAMP",
+    )
+    .bind(table)
+    .bind(connected)
+    .execute(&pool.db_pool)
+    .await?;
+
+    let result = query(
+        "INSERT IGNORE INTO message_rule_replies (message_table, connected, expires_at) \
+         VALUES (?, ?, CURRENT_TIMESTAMP + INTERVAL ? SECOND)",
+    )
+    .bind(table)
+    .bind(connected)
+    .bind(window_seconds)
+    .execute(&pool.db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// Gives a claim back when its reply could not be sent, the contact would otherwise wait out
+// the window without having been answered
+async fn release_auto_reply(
+    pool: &AppState,
+    table: &str,
+    connected: &str,
+) -> Result<(), sqlx::Error> {
+    query("DELETE FROM message_rule_replies WHERE message_table = ? AND connected = ?")
+        .bind(table)
+        .bind(connected)
+        .execute(&pool.db_pool)
+        .await
+        .map(|_| ())
+}
+
+// Sends the auto-reply back into the conversation, returns the id of the reply
+async fn send_auto_reply(
+    pool: &AppState,
+    table: &'static str,
+    message: &NewMessage,
+    template: &str,
+) -> Result<i32, SendError> {
+    let nick = match people_table(table) {
+        Some(people_table) => {
+            let query_str = format!("SELECT nick FROM {} WHERE id = ?", people_table);
+            sqlx::query_scalar::<_, Option<String>>(&query_str)
+                .bind(&message.sender)
+                .fetch_optional(&pool.db_pool)
+                .await?
+                .flatten()
+        }
+        None => None,
+    };
+    let content = template
+        .replace("{sender}", &message.sender)
+        .replace("{nick}", nick.as_deref().unwrap_or(&message.sender));
+
+    let reply = NewMessage {
+        sender: message.receiver.clone(),
+        receiver: message.sender.clone(),
+        content,
+        close_one_point: None,
+        connected: message.connected.clone(),
+        reply_to: None,
+        attachments: Vec::new(),
+        send_at: None,
+        format: MessageFormat::Plain,
+    };
+    deliver_message(pool, table, &reply)
+        .await
+        .map(|stored| stored.id)
+}
+
+async fn evaluate_rules(
+    pool: &AppState,
+    table: &'static str,
+    message_id: i32,
+    message: &NewMessage,
+) -> Result<RulesApplied, sqlx::Error> {
+    let mut applied = RulesApplied::default();
+    let query_str = format!(
+        "SELECT {} FROM message_rules WHERE message_table = ? AND enabled ORDER BY position, id",
+        RULE_COLUMNS
+    );
+    let rules = sqlx::query_as::<_, MessageRule>(&query_str)
+        .bind(table)
+        .fetch_all(&pool.db_pool)
+        .await?;
+    if rules.is_empty() {
+        return Ok(applied);
+    }
+
+    let groups = sqlx::query_scalar::<_, i32>(
+        "SELECT group_id FROM contact_group_members WHERE contact_id = ?",
+    )
+    .bind(&message.sender)
+    .fetch_all(&pool.db_pool)
+    .await?;
+    let now = Local::now();
+
+    for rule in rules {
+        if !rule.conditions.matches(message, &groups, now) {
+            continue;
+        }
+        applied.rule_ids.push(rule.id);
+        let actions = &rule.actions;
+
+        if let Some(label) = &actions.label {
+            let update_query = format!("UPDATE {} SET close_one_point = ? WHERE id = ?", table);
+            query(&update_query)
+                .bind(label.trim())
+                .bind(message_id)
+                .execute(&pool.db_pool)
+                .await?;
+            applied.relabelled = true;
+        }
+        if actions.mute {
+            let update_query = format!(
+                "UPDATE {} SET delivered_at = COALESCE(delivered_at, CURRENT_TIMESTAMP), \
+                 read_at = COALESCE(read_at, CURRENT_TIMESTAMP) WHERE id = ?",
+                table
+            );
+            query(&update_query)
+                .bind(message_id)
+                .execute(&pool.db_pool)
+                .await?;
+            applied.muted = true;
+        }
+        if let Some(template) = &actions.reply {
+            let window = actions
+                .reply_window_seconds
+                .unwrap_or(DEFAULT_REPLY_WINDOW_SECONDS);
+            if applied.reply_id.is_none()
+                && claim_auto_reply(pool, table, &message.connected, window).await?
+            {
+                match send_auto_reply(pool, table, message, template).await {
+                    Ok(reply_id) => applied.reply_id = Some(reply_id),
+                    Err(e) => {
+                        eprintln!("Error sending auto-reply into '{}' table: {}", table, e);
+                        release_auto_reply(pool, table, &message.connected).await?;
+                    }
+                }
+            }
+        }
+        if let Some(forward) = &actions.forward {
+            let request = ForwardRequest {
+                ids: vec![message_id],
+                target: forward.target,
+                sender: forward.sender.clone(),
+                receiver: forward.receiver.clone(),
+                connected: forward.connected.clone(),
+                comment: None,
+            };
+            if let Err(e) = forward_messages(pool, table, &request).await {
+                eprintln!(
+                    "Error forwarding message {} for rule {}: {}",
+                    message_id, rule.id, e
+                );
+            }
+        }
+
+        if rule.stop_processing {
+            break;
+        }
+    }
+
+    Ok(applied)
+}
+
+// Function to run the enabled rules of a channel on a newly delivered message. Only incoming
+// messages, the ones sent by the conversation partner, are looked at. Failures are only
+// logged because the message itself is already stored.
+pub async fn apply_message_rules(
+    pool: &AppState,
+    table: &'static str,
+    message_id: i32,
+    message: &NewMessage,
+) {
+    if message.sender != message.connected {
+        return;
+    }
+
+    match evaluate_rules(pool, table, message_id, message).await {
+        Ok(applied) if !applied.rule_ids.is_empty() => {
+            // Labels and read markers show up in the conversation summary
+            if applied.relabelled || applied.muted {
+                update_conversation(pool, table, &message.connected).await;
+            }
+            pool.emit_event(
+                "message-rules-applied",
+                RulesAppliedEvent {
+                    table,
+                    connected: message.connected.clone(),
+                    message_id,
+                    rule_ids: applied.rule_ids,
+                    muted: applied.muted,
+                    reply_id: applied.reply_id,
+                },
+            );
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!(
+            "Error applying message rules to message {} in '{}': {}",
+            message_id, table, e
+        ),
+    }
+}
+
+async fn fetch_rule(
+    pool: &AppState,
+    table: &str,
+    id: i32,
+) -> Result<Option<MessageRule>, sqlx::Error> {
+    let query_str = format!(
+        "SELECT {} FROM message_rules WHERE id = ? AND message_table = ?",
+        RULE_COLUMNS
+    );
+    sqlx::query_as::<_, MessageRule>(&query_str)
+        .bind(id)
+        .bind(table)
+        .fetch_optional(&pool.db_pool)
+        .await
+}
+
+async fn list_rules(pool: &AppState, table: &str) -> HttpResponse {
+    let query_str = format!(
+        "SELECT {} FROM message_rules WHERE message_table = ? ORDER BY position, id",
+        RULE_COLUMNS
+    );
+    match sqlx::query_as::<_, MessageRule>(&query_str)
+        .bind(table)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(rules) => HttpResponse::Ok().json(rules),
+        Err(e) => {
+            eprintln!("Error retrieving message rules for '{}': {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn create_rule(pool: &AppState, table: &str, rule: &RuleInput) -> HttpResponse {
+    let name = match validate_rule(rule) {
+        Ok(name) => name,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+
+    let result = async {
+        let id = query(
+            "INSERT INTO message_rules \
+             (message_table, name, enabled, position, conditions, actions, stop_processing) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(table)
+        .bind(name)
+        .bind(rule.enabled.unwrap_or(true))
+        .bind(rule.position)
+        .bind(Json(&rule.conditions))
+        .bind(Json(&rule.actions))
+        .bind(rule.stop_processing)
+        .execute(&pool.db_pool)
+        .await?
+        .last_insert_id() as i32;
+        fetch_rule(pool, table, id).await
+    }
+    .await;
+
+    match result {
+        Ok(Some(rule)) => HttpResponse::Ok().json(rule),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error creating message rule for '{}': {}", table, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn update_rule(pool: &AppState, table: &str, id: i32, rule: &RuleInput) -> HttpResponse {
+    let name = match validate_rule(rule) {
+        Ok(name) => name,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+
+    let result = async {
+        let updated = query(
+            "UPDATE message_rules SET name = ?, enabled = ?, position = ?, conditions = ?, \
+             actions = ?, stop_processing = ? WHERE id = ? AND message_table = ?",
+        )
+        .bind(name)
+        .bind(rule.enabled.unwrap_or(true))
+        .bind(rule.position)
+        .bind(Json(&rule.conditions))
+        .bind(Json(&rule.actions))
+        .bind(rule.stop_processing)
+        .bind(id)
+        .bind(table)
+        .execute(&pool.db_pool)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Ok(None);
+        }
+        fetch_rule(pool, table, id).await
+    }
+    .await;
+
+    match result {
+        Ok(Some(rule)) => HttpResponse::Ok().json(rule),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error updating message rule {}: {}", id, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn delete_rule(pool: &AppState, table: &str, id: i32) -> HttpResponse {
+    match query("DELETE FROM message_rules WHERE id = ? AND message_table = ?")
+        .bind(id)
+        .bind(table)
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 0 => HttpResponse::NotFound().finish(),
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            eprintln!("Error deleting message rule {}: {}", id, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list the rules applied to incoming 'my-client' messages
+#[get("/my/rules")]
+pub async fn get_rules_my_client(pool: web::Data<AppState>) -> impl Responder {
+    list_rules(&pool, "messages_send_to_my_client").await
+}
+
+// Handler function to list the rules applied to incoming 'other-client' messages
+#[get("/other/rules")]
+pub async fn get_rules_other_client(pool: web::Data<AppState>) -> impl Responder {
+    list_rules(&pool, "messages_send_to_other_client").await
+}
+
+// Handler function to add a rule for incoming 'my-client' messages
+#[post("/my/rules/")]
+pub async fn create_rule_my_client(
+    pool: web::Data<AppState>,
+    rule: web::Json<RuleInput>,
+) -> impl Responder {
+    create_rule(&pool, "messages_send_to_my_client", &rule).await
+}
+
+// Handler function to add a rule for incoming 'other-client' messages
+#[post("/other/rules/")]
+pub async fn create_rule_other_client(
+    pool: web::Data<AppState>,
+    rule: web::Json<RuleInput>,
+) -> impl Responder {
+    create_rule(&pool, "messages_send_to_other_client", &rule).await
+}
+
+// Handler function to replace a rule for incoming 'my-client' messages
+#[put("/my/rules/{id}")]
+pub async fn update_rule_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    rule: web::Json<RuleInput>,
+) -> impl Responder {
+    update_rule(&pool, "messages_send_to_my_client", id.into_inner(), &rule).await
+}
+
+// Handler function to replace a rule for incoming 'other-client' messages
+#[put("/other/rules/{id}")]
+pub async fn update_rule_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    rule: web::Json<RuleInput>,
+) -> impl Responder {
+    update_rule(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        &rule,
+    )
+    .await
+}
+
+// Handler function to remove a rule for incoming 'my-client' messages
+#[delete("/my/rules/{id}")]
+pub async fn delete_rule_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    delete_rule(&pool, "messages_send_to_my_client", id.into_inner()).await
+}
+
+// Handler function to remove a rule for incoming 'other-client' messages
+#[delete("/other/rules/{id}")]
+pub async fn delete_rule_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    delete_rule(&pool, "messages_send_to_other_client", id.into_inner()).await
+}
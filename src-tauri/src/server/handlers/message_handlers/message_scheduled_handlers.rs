@@ -0,0 +1,501 @@
+use super::message_attachment_handlers::AttachmentRef;
+use super::message_get_set_handlers::{
+    announce_message, check_new_message, known_message_table, store_message, MessageFormat,
+    NewMessage, SendError,
+};
+use super::message_rule_handlers::apply_message_rules;
+use crate::server::AppState;
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{query, FromRow};
+
+// How many due messages one scheduler run delivers at most
+const DELIVERY_BATCH_SIZE: i64 = 100;
+
+// How often a message is tried again after database trouble before it is given up
+const MAX_DELIVERY_ATTEMPTS: i32 = 5;
+
+// Rows a scheduler claimed but never finished, e.g. because the app quit mid run, are picked
+// up again after this long. Nothing was stored for them, the message and the sent status are
+// written together.
+const STALE_CLAIM_SECONDS: i32 = 10 * 60;
+
+// Define a struct to represent a message waiting to be sent
+#[derive(Debug, Serialize, FromRow)]
+struct ScheduledMessage {
+    id: i32,
+    #[serde(skip)]
+    message_table: String,
+    sender: String,
+    receiver: String,
+    content: String,
+    close_one_point: Option<String>,
+    connected: String,
+    reply_to: Option<i32>,
+    attachments: Json<Vec<AttachmentRef>>,
+    format: String,
+    send_at: DateTime<Utc>,
+    status: String,          // "pending", "sending", "sent" or "failed"
+    message_id: Option<i32>, // The delivered message once sent
+    error: Option<String>,   // Why the delivery failed, or the last failed attempt
+    attempts: i32,           // Delivery attempts so far
+    created_at: DateTime<Utc>,
+}
+
+impl ScheduledMessage {
+    fn into_new_message(self) -> NewMessage {
+        NewMessage {
+            sender: self.sender,
+            receiver: self.receiver,
+            content: self.content,
+            close_one_point: self.close_one_point,
+            connected: self.connected,
+            reply_to: self.reply_to,
+            attachments: self.attachments.0,
+            send_at: None,
+            format: MessageFormat::parse(&self.format),
+        }
+    }
+}
+
+// Define the query parameters accepted by the scheduled message listing
+#[derive(Deserialize)]
+struct ScheduledQuery {
+    connected: Option<String>,
+    status: Option<String>, // Defaults to the pending messages
+}
+
+// Define a struct to capture changes to a pending message
+#[derive(Deserialize)]
+struct ScheduledEdit {
+    content: Option<String>,
+    send_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize)]
+struct ScheduledEvent {
+    table: &'static str,
+    scheduled_id: i32,
+    connected: String,
+    message_id: Option<i32>,
+    error: Option<String>,
+}
+
+const SCHEDULED_COLUMNS: &str = "id, message_table, sender, receiver, content, close_one_point, \
+     connected, reply_to, attachments, format, send_at, status, message_id, error, attempts, created_at";
+
+// Same limit as the error column
+const MAX_ERROR_CHARS: usize = 255;
+
+// Condition on the scheduled messages a scheduler run may claim
+const CLAIMABLE: &str = "send_at <= CURRENT_TIMESTAMP AND (status = 'pending' \
+     OR (status = 'sending' AND claimed_at < CURRENT_TIMESTAMP - INTERVAL ? SECOND))";
+
+// Function to store a message for delivery at a later time, it is checked right away so
+// mistakes surface while the sender is still around
+pub async fn schedule_message(
+    pool: &AppState,
+    table: &str,
+    new_message: &NewMessage,
+    send_at: DateTime<Utc>,
+) -> HttpResponse {
+    if let Err(e) = check_new_message(pool, table, new_message).await {
+        return e.to_response(table);
+    }
+
+    let result = query(
+        "INSERT INTO scheduled_messages \
+         (message_table, sender, receiver, content, close_one_point, connected, reply_to, \
+          attachments, format, send_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(table)
+    .bind(&new_message.sender)
+    .bind(&new_message.receiver)
+    .bind(&new_message.content)
+    .bind(&new_message.close_one_point)
+    .bind(&new_message.connected)
+    .bind(new_message.reply_to)
+    .bind(Json(&new_message.attachments))
+    .bind(new_message.format.as_str())
+    .bind(send_at)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(result) => HttpResponse::Accepted().json(serde_json::json!({
+            "scheduled_id": result.last_insert_id(),
+            "send_at": send_at.to_rfc3339(),
+        })),
+        Err(e) => {
+            eprintln!("Error scheduling message: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Function to send every scheduled message that is due. Runs from the background scheduler,
+// the schedule lives in the database so pending messages survive restarts. Every row is
+// claimed before it is sent so overlapping runs never deliver it twice, and a row that keeps
+// failing on the database only holds up itself.
+pub async fn deliver_due_messages(pool: &AppState) -> Result<usize, sqlx::Error> {
+    let query_str = format!(
+        "SELECT {} FROM scheduled_messages WHERE {} ORDER BY send_at, id LIMIT ?",
+        SCHEDULED_COLUMNS, CLAIMABLE
+    );
+    let due = sqlx::query_as::<_, ScheduledMessage>(&query_str)
+        .bind(STALE_CLAIM_SECONDS)
+        .bind(DELIVERY_BATCH_SIZE)
+        .fetch_all(&pool.db_pool)
+        .await?;
+
+    let mut delivered = 0;
+    for scheduled in due {
+        let claim = match claim_scheduled(pool, scheduled.id).await? {
+            Some(claim) => claim,
+            None => continue,
+        };
+        let scheduled_id = scheduled.id;
+        let attempts = scheduled.attempts + 1;
+        let table = match known_message_table(&scheduled.message_table) {
+            Some(table) => table,
+            None => {
+                mark_failed(pool, scheduled_id, &claim, "Unknown message table").await?;
+                continue;
+            }
+        };
+        let new_message = scheduled.into_new_message();
+
+        let (message_id, error) =
+            match send_scheduled(pool, table, scheduled_id, &claim, &new_message).await {
+                Ok(Some(message_id)) => {
+                    delivered += 1;
+                    (Some(message_id), None)
+                }
+                // The claim went stale and another run took the row over, that run reports it
+                Ok(None) => continue,
+                // Database trouble is retried on a later run until the attempts run out
+                Err(SendError::Database(e)) if attempts < MAX_DELIVERY_ATTEMPTS => {
+                    eprintln!(
+                        "Error delivering scheduled message {} (attempt {}): {}",
+                        scheduled_id, attempts, e
+                    );
+                    release_claim(pool, scheduled_id, &claim, &e.to_string()).await?;
+                    continue;
+                }
+                Err(e) => {
+                    let error = e.to_string();
+                    mark_failed(pool, scheduled_id, &claim, &error).await?;
+                    (None, Some(error))
+                }
+            };
+
+        pool.emit_event(
+            if error.is_none() {
+                "scheduled-message-sent"
+            } else {
+                "scheduled-message-failed"
+            },
+            ScheduledEvent {
+                table,
+                scheduled_id,
+                connected: new_message.connected,
+                message_id,
+                error,
+            },
+        );
+    }
+
+    Ok(delivered)
+}
+
+// Marks a due message as being sent under a fresh claim token, None when another run
+// claimed it first. A stale claim can be taken over, so every later update of the row
+// checks the token to know the claim is still this run's
+async fn claim_scheduled(pool: &AppState, id: i32) -> Result<Option<String>, sqlx::Error> {
+    let claim = uuid::Uuid::new_v4().to_string();
+    let update_query = format!(
+        "UPDATE scheduled_messages \
+         SET status = 'sending', claimed_at = CURRENT_TIMESTAMP, claim_token = ?, \
+         attempts = attempts + 1 \
+         WHERE id = ? AND {}",
+        CLAIMABLE
+    );
+    let result = query(&update_query)
+        .bind(&claim)
+        .bind(id)
+        .bind(STALE_CLAIM_SECONDS)
+        .execute(&pool.db_pool)
+        .await?;
+    Ok((result.rows_affected() > 0).then_some(claim))
+}
+
+// Stores a claimed message and marks it sent in one transaction, so it is either delivered
+// and sent or still waiting for the next attempt. None when the claim was lost to another
+// run in the meantime, the stored message is rolled back so only that run delivers it
+async fn send_scheduled(
+    pool: &AppState,
+    table: &'static str,
+    id: i32,
+    claim: &str,
+    new_message: &NewMessage,
+) -> Result<Option<i32>, SendError> {
+    let thread_root_id = check_new_message(pool, table, new_message).await?;
+
+    let mut tx = pool.db_pool.begin().await?;
+    let stored = store_message(&mut tx, table, new_message, thread_root_id).await?;
+    let result = query(
+        "UPDATE scheduled_messages SET status = 'sent', message_id = ?, error = NULL \
+         WHERE id = ? AND status = 'sending' AND claim_token = ?",
+    )
+    .bind(stored.id)
+    .bind(id)
+    .bind(claim)
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() != 1 {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+    tx.commit().await?;
+
+    announce_message(pool, table, new_message, &stored).await;
+    // Same as a message sent right away, a collapsed repeat is not run through the rules again
+    if stored.collapsed.is_none() {
+        apply_message_rules(pool, table, stored.id, new_message).await;
+    }
+    Ok(Some(stored.id))
+}
+
+// Puts a claimed message back in the queue after a failed attempt
+async fn release_claim(
+    pool: &AppState,
+    id: i32,
+    claim: &str,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    query(
+        "UPDATE scheduled_messages \
+         SET status = 'pending', claimed_at = NULL, claim_token = NULL, error = ? \
+         WHERE id = ? AND status = 'sending' AND claim_token = ?",
+    )
+    .bind(truncate_error(error))
+    .bind(id)
+    .bind(claim)
+    .execute(&pool.db_pool)
+    .await
+    .map(|_| ())
+}
+
+async fn mark_failed(
+    pool: &AppState,
+    id: i32,
+    claim: &str,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    query(
+        "UPDATE scheduled_messages SET status = 'failed', error = ? \
+         WHERE id = ? AND status = 'sending' AND claim_token = ?",
+    )
+    .bind(truncate_error(error))
+    .bind(id)
+    .bind(claim)
+    .execute(&pool.db_pool)
+    .await
+    .map(|_| ())
+}
+
+fn truncate_error(error: &str) -> String {
+    error.chars().take(MAX_ERROR_CHARS).collect()
+}
+
+async fn list_scheduled(pool: &AppState, table: &str, params: &ScheduledQuery) -> HttpResponse {
+    let status = params.status.as_deref().unwrap_or("pending");
+    let query_str = format!(
+        "SELECT {} FROM scheduled_messages \
+         WHERE message_table = ? AND status = ? AND (? IS NULL OR connected = ?) \
+         ORDER BY send_at, id",
+        SCHEDULED_COLUMNS
+    );
+
+    match sqlx::query_as::<_, ScheduledMessage>(&query_str)
+        .bind(table)
+        .bind(status)
+        .bind(&params.connected)
+        .bind(&params.connected)
+        .fetch_all(&pool.db_pool)
+        .await
+    {
+        Ok(messages) => HttpResponse::Ok().json(messages),
+        Err(e) => {
+            eprintln!("Error retrieving scheduled messages: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Tells a missing message apart from one that already left the queue
+async fn not_pending_response(pool: &AppState, table: &str, id: i32) -> HttpResponse {
+    match sqlx::query_scalar::<_, String>(
+        "SELECT status FROM scheduled_messages WHERE id = ? AND message_table = ?",
+    )
+    .bind(id)
+    .bind(table)
+    .fetch_optional(&pool.db_pool)
+    .await
+    {
+        Ok(Some(status)) => {
+            HttpResponse::Conflict().body(format!("Scheduled message is already {}", status))
+        }
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            eprintln!("Error retrieving scheduled message: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// The edited message goes through the same checks as a new one, the replied message may have
+// been deleted since it was scheduled
+async fn edit_scheduled(
+    pool: &AppState,
+    table: &str,
+    id: i32,
+    edit: &ScheduledEdit,
+) -> HttpResponse {
+    if edit.content.as_deref().is_some_and(|c| c.trim().is_empty()) {
+        return HttpResponse::BadRequest().body("Message content cannot be empty");
+    }
+    if edit.send_at.is_some_and(|send_at| send_at <= Utc::now()) {
+        return HttpResponse::BadRequest().body("The send time has to be in the future");
+    }
+
+    let query_str = format!(
+        "SELECT {} FROM scheduled_messages WHERE id = ? AND message_table = ? AND status = 'pending'",
+        SCHEDULED_COLUMNS
+    );
+    let scheduled = match sqlx::query_as::<_, ScheduledMessage>(&query_str)
+        .bind(id)
+        .bind(table)
+        .fetch_optional(&pool.db_pool)
+        .await
+    {
+        Ok(Some(scheduled)) => scheduled,
+        Ok(None) => return not_pending_response(pool, table, id).await,
+        Err(e) => {
+            eprintln!("Error retrieving scheduled message: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let mut edited = scheduled.into_new_message();
+    if let Some(content) = &edit.content {
+        edited.content = content.clone();
+    }
+    if let Err(e) = check_new_message(pool, table, &edited).await {
+        return e.to_response(table);
+    }
+
+    let result = query(
+        "UPDATE scheduled_messages \
+         SET content = COALESCE(?, content), send_at = COALESCE(?, send_at) \
+         WHERE id = ? AND message_table = ? AND status = 'pending'",
+    )
+    .bind(&edit.content)
+    .bind(edit.send_at)
+    .bind(id)
+    .bind(table)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => not_pending_response(pool, table, id).await,
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            eprintln!("Error updating scheduled message: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn cancel_scheduled(pool: &AppState, table: &str, id: i32) -> HttpResponse {
+    let result = query(
+        "DELETE FROM scheduled_messages WHERE id = ? AND message_table = ? AND status = 'pending'",
+    )
+    .bind(id)
+    .bind(table)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => not_pending_response(pool, table, id).await,
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            eprintln!("Error cancelling scheduled message: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to list the scheduled messages of 'my-client', soonest first
+#[get("/my/scheduled")]
+pub async fn get_scheduled_messages_my_client(
+    pool: web::Data<AppState>,
+    params: web::Query<ScheduledQuery>,
+) -> impl Responder {
+    list_scheduled(&pool, "messages_send_to_my_client", &params).await
+}
+
+// Handler function to list the scheduled messages of 'other-client', soonest first
+#[get("/other/scheduled")]
+pub async fn get_scheduled_messages_other_client(
+    pool: web::Data<AppState>,
+    params: web::Query<ScheduledQuery>,
+) -> impl Responder {
+    list_scheduled(&pool, "messages_send_to_other_client", &params).await
+}
+
+// Handler function to change the content or send time of a pending 'my-client' message
+#[put("/my/scheduled/{id}")]
+pub async fn edit_scheduled_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    edit: web::Json<ScheduledEdit>,
+) -> impl Responder {
+    edit_scheduled(&pool, "messages_send_to_my_client", id.into_inner(), &edit).await
+}
+
+// Handler function to change the content or send time of a pending 'other-client' message
+#[put("/other/scheduled/{id}")]
+pub async fn edit_scheduled_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+    edit: web::Json<ScheduledEdit>,
+) -> impl Responder {
+    edit_scheduled(
+        &pool,
+        "messages_send_to_other_client",
+        id.into_inner(),
+        &edit,
+    )
+    .await
+}
+
+// Handler function to cancel a pending 'my-client' message
+#[delete("/my/scheduled/{id}")]
+pub async fn cancel_scheduled_message_my_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    cancel_scheduled(&pool, "messages_send_to_my_client", id.into_inner()).await
+}
+
+// Handler function to cancel a pending 'other-client' message
+#[delete("/other/scheduled/{id}")]
+pub async fn cancel_scheduled_message_other_client(
+    pool: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    cancel_scheduled(&pool, "messages_send_to_other_client", id.into_inner()).await
+}
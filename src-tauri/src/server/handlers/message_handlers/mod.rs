@@ -1,22 +1,174 @@
+mod message_attachment_handlers;
+mod message_collapse_handlers;
+mod message_contact_avatar_handlers;
+mod message_contact_group_handlers;
 mod message_contact_handlers;
+mod message_contact_merge_handlers;
+mod message_contact_timeline_handlers;
+mod message_conversation_handlers;
+mod message_delete_handlers;
+mod message_draft_handlers;
+mod message_edit_handlers;
+mod message_expiry_handlers;
+mod message_forward_handlers;
 mod message_get_set_handlers;
+mod message_group_chat_handlers;
 mod message_handler_package;
+mod message_label_handlers;
+mod message_mention_handlers;
+mod message_pin_handlers;
+mod message_reaction_handlers;
+mod message_receipt_handlers;
+mod message_rule_handlers;
+mod message_scheduled_handlers;
 
+use message_attachment_handlers::collect_attachment_garbage_handler;
+use message_attachment_handlers::download_attachment;
+use message_attachment_handlers::get_attachment_thumbnail;
+use message_attachment_handlers::upload_attachments;
+use message_collapse_handlers::get_conversation_collapse_my_client;
+use message_collapse_handlers::get_conversation_collapse_other_client;
+use message_collapse_handlers::set_conversation_collapse_my_client;
+use message_collapse_handlers::set_conversation_collapse_other_client;
+use message_contact_avatar_handlers::delete_avatar_my_client;
+use message_contact_avatar_handlers::delete_avatar_other_client;
+use message_contact_avatar_handlers::get_avatar;
+use message_contact_avatar_handlers::upload_avatar_my_client;
+use message_contact_avatar_handlers::upload_avatar_other_client;
+use message_contact_group_handlers::add_contact_group_members;
+use message_contact_group_handlers::create_contact_group;
+use message_contact_group_handlers::delete_contact_group;
+use message_contact_group_handlers::get_contact_group_members;
+use message_contact_group_handlers::get_contact_groups;
+use message_contact_group_handlers::remove_contact_group_member;
+use message_contact_group_handlers::rename_contact_group;
+use message_contact_group_handlers::send_group_message_my_client;
+use message_contact_group_handlers::send_group_message_other_client;
 use message_contact_handlers::add_contact_my_client;
 use message_contact_handlers::add_contact_other_client;
 use message_contact_handlers::get_my_server_people_handler;
 use message_contact_handlers::get_other_server_people_handler;
+use message_contact_merge_handlers::get_contact_merges;
+use message_contact_merge_handlers::get_duplicate_people_my_client;
+use message_contact_merge_handlers::get_duplicate_people_other_client;
+use message_contact_merge_handlers::merge_people_my_client;
+use message_contact_merge_handlers::merge_people_other_client;
+use message_contact_merge_handlers::undo_contact_merge;
+use message_contact_timeline_handlers::get_contact_timeline;
+use message_conversation_handlers::get_conversations_my_client;
+use message_conversation_handlers::get_conversations_other_client;
+use message_conversation_handlers::rebuild_conversations_handler;
+use message_delete_handlers::delete_message_my_client;
+use message_delete_handlers::delete_message_other_client;
+use message_draft_handlers::clear_draft_my_client;
+use message_draft_handlers::clear_draft_other_client;
+use message_draft_handlers::get_draft_my_client;
+use message_draft_handlers::get_draft_other_client;
+use message_draft_handlers::save_draft_my_client;
+use message_draft_handlers::save_draft_other_client;
+use message_edit_handlers::edit_message_my_client;
+use message_edit_handlers::edit_message_other_client;
+use message_edit_handlers::get_message_revisions_my_client;
+use message_edit_handlers::get_message_revisions_other_client;
+use message_expiry_handlers::get_conversation_expiry_my_client;
+use message_expiry_handlers::get_conversation_expiry_other_client;
+use message_expiry_handlers::set_conversation_expiry_my_client;
+use message_expiry_handlers::set_conversation_expiry_other_client;
+use message_forward_handlers::forward_messages_my_client;
+use message_forward_handlers::forward_messages_other_client;
+use message_get_set_handlers::get_history_my_client;
+use message_get_set_handlers::get_history_other_client;
 use message_get_set_handlers::get_messages_my_client;
 use message_get_set_handlers::get_messages_other_client;
+use message_get_set_handlers::get_thread_my_client;
+use message_get_set_handlers::get_thread_other_client;
 use message_get_set_handlers::send_message_my_client;
 use message_get_set_handlers::send_message_other_client;
+use message_group_chat_handlers::create_group_chat_my_client;
+use message_group_chat_handlers::create_group_chat_other_client;
+use message_group_chat_handlers::get_group_chat_members;
+use message_group_chat_handlers::get_group_chat_messages;
+use message_group_chat_handlers::get_group_chats_my_client;
+use message_group_chat_handlers::get_group_chats_other_client;
+use message_group_chat_handlers::join_group_chat;
+use message_group_chat_handlers::kick_group_chat_member;
+use message_group_chat_handlers::leave_group_chat;
+use message_group_chat_handlers::post_group_chat_receipt;
+use message_group_chat_handlers::send_group_chat_message;
+use message_group_chat_handlers::set_group_chat_role;
+use message_handler_package::reset_attachments_table_handler;
 use message_handler_package::reset_connected_people_table_handler;
 use message_handler_package::reset_connecting_people_table_handler;
+use message_handler_package::reset_contact_avatars_table_handler;
+use message_handler_package::reset_contact_edits_table_handler;
+use message_handler_package::reset_contact_groups_table_handler;
+use message_handler_package::reset_contact_merge_log_table_handler;
+use message_handler_package::reset_conversation_settings_table_handler;
+use message_handler_package::reset_conversations_table_handler;
+use message_handler_package::reset_group_chats_table_handler;
+use message_handler_package::reset_keyword_alerts_table_handler;
+use message_handler_package::reset_message_drafts_table_handler;
+use message_handler_package::reset_message_hides_table_handler;
+use message_handler_package::reset_message_mentions_table_handler;
+use message_handler_package::reset_message_reactions_table_handler;
+use message_handler_package::reset_message_revisions_table_handler;
+use message_handler_package::reset_message_rules_table_handler;
 use message_handler_package::reset_messages_send_to_my_client_table_handler;
 use message_handler_package::reset_messages_send_to_other_client_table_handler;
+use message_handler_package::reset_scheduled_messages_table_handler;
+use message_label_handlers::get_labels_my_client;
+use message_label_handlers::get_labels_other_client;
+use message_label_handlers::relabel_messages_my_client;
+use message_label_handlers::relabel_messages_other_client;
+use message_mention_handlers::create_keyword_alert;
+use message_mention_handlers::delete_keyword_alert;
+use message_mention_handlers::get_alert_feed;
+use message_mention_handlers::get_keyword_alerts;
+use message_pin_handlers::get_pinned_messages_my_client;
+use message_pin_handlers::get_pinned_messages_other_client;
+use message_pin_handlers::get_starred_messages_my_client;
+use message_pin_handlers::get_starred_messages_other_client;
+use message_pin_handlers::pin_message_my_client;
+use message_pin_handlers::pin_message_other_client;
+use message_pin_handlers::star_message_my_client;
+use message_pin_handlers::star_message_other_client;
+use message_pin_handlers::unpin_message_my_client;
+use message_pin_handlers::unpin_message_other_client;
+use message_pin_handlers::unstar_message_my_client;
+use message_pin_handlers::unstar_message_other_client;
+use message_reaction_handlers::add_reaction_my_client;
+use message_reaction_handlers::add_reaction_other_client;
+use message_reaction_handlers::remove_reaction_my_client;
+use message_reaction_handlers::remove_reaction_other_client;
+use message_receipt_handlers::post_receipt_my_client;
+use message_receipt_handlers::post_receipt_other_client;
+use message_rule_handlers::create_rule_my_client;
+use message_rule_handlers::create_rule_other_client;
+use message_rule_handlers::delete_rule_my_client;
+use message_rule_handlers::delete_rule_other_client;
+use message_rule_handlers::get_rules_my_client;
+use message_rule_handlers::get_rules_other_client;
+use message_rule_handlers::update_rule_my_client;
+use message_rule_handlers::update_rule_other_client;
+use message_scheduled_handlers::cancel_scheduled_message_my_client;
+use message_scheduled_handlers::cancel_scheduled_message_other_client;
+use message_scheduled_handlers::edit_scheduled_message_my_client;
+use message_scheduled_handlers::edit_scheduled_message_other_client;
+use message_scheduled_handlers::get_scheduled_messages_my_client;
+use message_scheduled_handlers::get_scheduled_messages_other_client;
+
+// Used by the background jobs and the admin handlers of the server
+pub use message_attachment_handlers::collect_attachment_garbage;
+pub use message_expiry_handlers::matching_message_ids;
+pub use message_expiry_handlers::purge_messages;
+pub use message_expiry_handlers::sweep_expired_messages;
+pub use message_expiry_handlers::PurgeReport;
+pub use message_scheduled_handlers::deliver_due_messages;
 
 pub fn message_handler_config(conf: &mut actix_web::web::ServiceConfig) {
     let scope = actix_web::web::scope("/message")
+        // Avatar uploads are sent as raw request bodies
+        .app_data(actix_web::web::PayloadConfig::new(8 * 1024 * 1024))
         .service(reset_messages_send_to_my_client_table_handler)
         .service(reset_messages_send_to_other_client_table_handler)
         .service(send_message_my_client)
@@ -28,6 +180,128 @@ pub fn message_handler_config(conf: &mut actix_web::web::ServiceConfig) {
         .service(get_my_server_people_handler)
         .service(add_contact_my_client)
         .service(add_contact_other_client)
-        .service(get_other_server_people_handler);
+        .service(get_other_server_people_handler)
+        .service(reset_contact_groups_table_handler)
+        .service(get_contact_groups)
+        .service(create_contact_group)
+        .service(rename_contact_group)
+        .service(delete_contact_group)
+        .service(get_contact_group_members)
+        .service(add_contact_group_members)
+        .service(remove_contact_group_member)
+        .service(send_group_message_my_client)
+        .service(send_group_message_other_client)
+        .service(reset_contact_merge_log_table_handler)
+        .service(get_duplicate_people_my_client)
+        .service(get_duplicate_people_other_client)
+        .service(merge_people_my_client)
+        .service(merge_people_other_client)
+        .service(get_contact_merges)
+        .service(undo_contact_merge)
+        .service(reset_contact_avatars_table_handler)
+        .service(upload_avatar_my_client)
+        .service(upload_avatar_other_client)
+        .service(delete_avatar_my_client)
+        .service(delete_avatar_other_client)
+        .service(get_avatar)
+        .service(reset_contact_edits_table_handler)
+        .service(get_contact_timeline)
+        .service(reset_message_revisions_table_handler)
+        .service(edit_message_my_client)
+        .service(edit_message_other_client)
+        .service(get_message_revisions_my_client)
+        .service(get_message_revisions_other_client)
+        .service(delete_message_my_client)
+        .service(delete_message_other_client)
+        .service(post_receipt_my_client)
+        .service(post_receipt_other_client)
+        .service(reset_conversations_table_handler)
+        .service(rebuild_conversations_handler)
+        .service(get_conversations_my_client)
+        .service(get_conversations_other_client)
+        .service(get_thread_my_client)
+        .service(get_thread_other_client)
+        .service(reset_message_reactions_table_handler)
+        .service(add_reaction_my_client)
+        .service(add_reaction_other_client)
+        .service(remove_reaction_my_client)
+        .service(remove_reaction_other_client)
+        .service(reset_attachments_table_handler)
+        .service(upload_attachments)
+        .service(download_attachment)
+        .service(get_attachment_thumbnail)
+        .service(collect_attachment_garbage_handler)
+        .service(reset_group_chats_table_handler)
+        .service(create_group_chat_my_client)
+        .service(create_group_chat_other_client)
+        .service(get_group_chats_my_client)
+        .service(get_group_chats_other_client)
+        .service(get_group_chat_members)
+        .service(join_group_chat)
+        .service(leave_group_chat)
+        .service(kick_group_chat_member)
+        .service(set_group_chat_role)
+        .service(send_group_chat_message)
+        .service(get_group_chat_messages)
+        .service(post_group_chat_receipt)
+        .service(reset_scheduled_messages_table_handler)
+        .service(get_scheduled_messages_my_client)
+        .service(get_scheduled_messages_other_client)
+        .service(edit_scheduled_message_my_client)
+        .service(edit_scheduled_message_other_client)
+        .service(cancel_scheduled_message_my_client)
+        .service(cancel_scheduled_message_other_client)
+        .service(reset_conversation_settings_table_handler)
+        .service(get_conversation_expiry_my_client)
+        .service(get_conversation_expiry_other_client)
+        .service(set_conversation_expiry_my_client)
+        .service(set_conversation_expiry_other_client)
+        .service(get_conversation_collapse_my_client)
+        .service(get_conversation_collapse_other_client)
+        .service(set_conversation_collapse_my_client)
+        .service(set_conversation_collapse_other_client)
+        .service(get_labels_my_client)
+        .service(get_labels_other_client)
+        .service(relabel_messages_my_client)
+        .service(relabel_messages_other_client)
+        .service(star_message_my_client)
+        .service(star_message_other_client)
+        .service(unstar_message_my_client)
+        .service(unstar_message_other_client)
+        .service(get_starred_messages_my_client)
+        .service(get_starred_messages_other_client)
+        .service(pin_message_my_client)
+        .service(pin_message_other_client)
+        .service(unpin_message_my_client)
+        .service(unpin_message_other_client)
+        .service(get_pinned_messages_my_client)
+        .service(get_pinned_messages_other_client)
+        .service(get_history_my_client)
+        .service(get_history_other_client)
+        .service(reset_message_drafts_table_handler)
+        .service(get_draft_my_client)
+        .service(get_draft_other_client)
+        .service(save_draft_my_client)
+        .service(save_draft_other_client)
+        .service(clear_draft_my_client)
+        .service(clear_draft_other_client)
+        .service(forward_messages_my_client)
+        .service(forward_messages_other_client)
+        .service(reset_message_hides_table_handler)
+        .service(reset_message_mentions_table_handler)
+        .service(reset_keyword_alerts_table_handler)
+        .service(get_keyword_alerts)
+        .service(create_keyword_alert)
+        .service(delete_keyword_alert)
+        .service(get_alert_feed)
+        .service(reset_message_rules_table_handler)
+        .service(get_rules_my_client)
+        .service(get_rules_other_client)
+        .service(create_rule_my_client)
+        .service(create_rule_other_client)
+        .service(update_rule_my_client)
+        .service(update_rule_other_client)
+        .service(delete_rule_my_client)
+        .service(delete_rule_other_client);
     conf.service(scope);
 }
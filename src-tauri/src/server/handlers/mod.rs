@@ -1,3 +1,4 @@
+pub mod admin_handlers;
 pub mod form_handlers;
 pub mod message_handlers;
 pub mod wailing_wall_handlers;
@@ -1,7 +1,16 @@
 mod wailing_handler_package;
+use wailing_handler_package::create_wall_post;
 use wailing_handler_package::handle;
+use wailing_handler_package::pin_wall_post;
+use wailing_handler_package::reset_wall_posts_table_handler;
+use wailing_handler_package::unpin_wall_post;
 
 pub fn message_handler_config(conf: &mut actix_web::web::ServiceConfig) {
-    let scope = actix_web::web::scope("/wailing").service(handle);
+    let scope = actix_web::web::scope("/wailing")
+        .service(handle)
+        .service(reset_wall_posts_table_handler)
+        .service(pin_wall_post)
+        .service(unpin_wall_post)
+        .service(create_wall_post);
     conf.service(scope);
 }
@@ -1,6 +1,124 @@
-use actix_web::{get, HttpResponse};
+use crate::server::AppState;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use serde::Deserialize;
+
+const MAX_AUTHOR_CHARS: usize = 256;
+const MAX_POST_BYTES: usize = 65_535;
+
+// Define the body of a new wall post
+#[derive(Deserialize)]
+struct NewWallPost {
+    author: String,
+    content: String,
+}
 
 #[get("/example")]
 pub async fn handle() -> HttpResponse {
     HttpResponse::Ok().body("wailing Example handler")
 }
+
+// Handler function to reset 'wailing_wall_posts' table
+#[post("/reset-wall-posts-table")]
+pub async fn reset_wall_posts_table_handler(pool: web::Data<AppState>) -> impl Responder {
+    let drop_table_query = "DROP TABLE IF EXISTS wailing_wall_posts;";
+    let create_table_query = "
+        CREATE TABLE wailing_wall_posts (
+            id INT AUTO_INCREMENT PRIMARY KEY,
+            author VARCHAR(256) NOT NULL,
+            content TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            pinned_at TIMESTAMP NULL DEFAULT NULL,
+            INDEX (author, created_at)
+        );";
+
+    match sqlx::query(drop_table_query).execute(&pool.db_pool).await {
+        Ok(_) => match sqlx::query(create_table_query).execute(&pool.db_pool).await {
+            Ok(_) => HttpResponse::Ok().body("Table 'wailing_wall_posts' reset successfully"),
+            Err(e) => {
+                eprintln!("Error creating 'wailing_wall_posts' table: {}", e);
+                HttpResponse::InternalServerError().body("Error creating table")
+            }
+        },
+        Err(e) => {
+            eprintln!("Error dropping 'wailing_wall_posts' table: {}", e);
+            HttpResponse::InternalServerError().body("Error dropping table")
+        }
+    }
+}
+
+// Handler function to publish a post on the wall
+#[post("/posts/")]
+pub async fn create_wall_post(
+    pool: web::Data<AppState>,
+    post: web::Json<NewWallPost>,
+) -> impl Responder {
+    let author = post.author.trim();
+    if author.is_empty() || author.chars().count() > MAX_AUTHOR_CHARS {
+        return HttpResponse::BadRequest().body("Author must be 1 to 256 characters");
+    }
+    if post.content.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Post cannot be empty");
+    }
+    if post.content.len() > MAX_POST_BYTES {
+        return HttpResponse::PayloadTooLarge().body("Post is too large");
+    }
+
+    match sqlx::query("INSERT INTO wailing_wall_posts (author, content) VALUES (?, ?)")
+        .bind(author)
+        .bind(&post.content)
+        .execute(&pool.db_pool)
+        .await
+    {
+        Ok(result) => HttpResponse::Ok().json(serde_json::json!({ "id": result.last_insert_id() })),
+        Err(e) => {
+            eprintln!("Error saving wall post: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Pinned posts are exempt from the retention policy
+async fn set_post_pinned(pool: &AppState, id: i32, pinned: bool) -> HttpResponse {
+    let result = sqlx::query(
+        "UPDATE wailing_wall_posts \
+         SET pinned_at = CASE WHEN ? THEN COALESCE(pinned_at, CURRENT_TIMESTAMP) END \
+         WHERE id = ?",
+    )
+    .bind(pinned)
+    .bind(id)
+    .execute(&pool.db_pool)
+    .await;
+
+    match result {
+        Ok(_) => match sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM wailing_wall_posts WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_one(&pool.db_pool)
+        .await
+        {
+            Ok(0) => HttpResponse::NotFound().finish(),
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => {
+                eprintln!("Error retrieving wall post: {}", e);
+                HttpResponse::InternalServerError().finish()
+            }
+        },
+        Err(e) => {
+            eprintln!("Error updating wall post: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// Handler function to pin a wall post
+#[put("/posts/{id}/pin")]
+pub async fn pin_wall_post(pool: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    set_post_pinned(&pool, id.into_inner(), true).await
+}
+
+// Handler function to unpin a wall post
+#[delete("/posts/{id}/pin")]
+pub async fn unpin_wall_post(pool: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
+    set_post_pinned(&pool, id.into_inner(), false).await
+}
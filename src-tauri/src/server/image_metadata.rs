@@ -0,0 +1,374 @@
+// Helpers to remove privacy sensitive metadata (EXIF, GPS, XMP, IPTC, text chunks) from
+// encoded images. The pixel data is copied untouched, so nothing is lost to re-encoding.
+
+const JPEG_SOI: [u8; 2] = [0xFF, 0xD8];
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+
+// Function to strip the metadata of an image, None when the data is not a valid image of
+// the given type. Types without metadata support are returned unchanged.
+pub fn strip_metadata(mime: &str, bytes: &[u8]) -> Option<Vec<u8>> {
+    match mime {
+        "image/jpeg" => strip_jpeg(bytes),
+        "image/png" => strip_png(bytes),
+        "image/webp" => strip_webp(bytes),
+        _ => Some(bytes.to_vec()),
+    }
+}
+
+// Function to read the EXIF orientation (1 to 8) of a JPEG image
+pub fn jpeg_orientation(bytes: &[u8]) -> Option<u16> {
+    let mut orientation = None;
+    for_each_jpeg_segment(bytes, |marker, segment| {
+        if marker == 0xE1 && segment.starts_with(EXIF_HEADER) {
+            orientation = exif_orientation(&segment[EXIF_HEADER.len()..]);
+        }
+    })?;
+    orientation
+}
+
+fn read_u16(bytes: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let raw: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if little_endian {
+        u16::from_le_bytes(raw)
+    } else {
+        u16::from_be_bytes(raw)
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let raw: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if little_endian {
+        u32::from_le_bytes(raw)
+    } else {
+        u32::from_be_bytes(raw)
+    })
+}
+
+// Looks the orientation tag up in the first IFD of a TIFF structure
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let ifd = read_u32(tiff, 4, little_endian)? as usize;
+    let entries = read_u16(tiff, ifd, little_endian)? as usize;
+    (0..entries)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|entry| read_u16(tiff, *entry, little_endian) == Some(EXIF_ORIENTATION_TAG))
+        .and_then(|entry| read_u16(tiff, entry + 8, little_endian))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+// Walks the segments before the image data, handing every segment payload to `visit`.
+// Returns the offset of the start-of-scan marker, where the entropy coded data begins.
+fn for_each_jpeg_segment(bytes: &[u8], mut visit: impl FnMut(u8, &[u8])) -> Option<usize> {
+    if !bytes.starts_with(&JPEG_SOI) {
+        return None;
+    }
+
+    let mut pos = JPEG_SOI.len();
+    loop {
+        if *bytes.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(pos + 1)?;
+        match marker {
+            0xFF => pos += 1, // Fill byte
+            0xDA => return Some(pos),
+            0x01 | 0xD0..=0xD7 => pos += 2, // Markers without payload
+            _ => {
+                let length = read_u16(bytes, pos + 2, false)? as usize;
+                let segment = bytes.get(pos + 4..pos + 2 + length)?;
+                visit(marker, segment);
+                pos += 2 + length;
+            }
+        }
+    }
+}
+
+// Drops APP1 (EXIF, XMP), APP13 (IPTC) and comment segments, colour profiles are kept
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut kept = Vec::new();
+    let scan_start = for_each_jpeg_segment(bytes, |marker, segment| {
+        if !matches!(marker, 0xE1 | 0xED | 0xFE) {
+            kept.push((marker, segment.to_vec()));
+        }
+    })?;
+
+    let mut stripped = Vec::with_capacity(bytes.len());
+    stripped.extend_from_slice(&JPEG_SOI);
+    for (marker, segment) in kept {
+        stripped.extend_from_slice(&[0xFF, marker]);
+        stripped.extend_from_slice(&((segment.len() + 2) as u16).to_be_bytes());
+        stripped.extend_from_slice(&segment);
+    }
+    stripped.extend_from_slice(&bytes[scan_start..]);
+    Some(stripped)
+}
+
+// Drops the EXIF, text and modification time chunks
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    if !bytes.starts_with(&PNG_SIGNATURE) {
+        return None;
+    }
+
+    let mut stripped = Vec::with_capacity(bytes.len());
+    stripped.extend_from_slice(&PNG_SIGNATURE);
+    let mut pos = PNG_SIGNATURE.len();
+    loop {
+        let length = read_u32(bytes, pos, false)? as usize;
+        let chunk_type = bytes.get(pos + 4..pos + 8)?;
+        let end = pos + 12 + length; // Length, type, data and CRC
+        let chunk = bytes.get(pos..end)?;
+        if !matches!(chunk_type, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            stripped.extend_from_slice(chunk);
+        }
+        if chunk_type == b"IEND" {
+            return Some(stripped);
+        }
+        pos = end;
+    }
+}
+
+// Drops the EXIF and XMP chunks and clears their flags in the extended header
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+
+    let mut stripped = Vec::with_capacity(bytes.len());
+    stripped.extend_from_slice(&bytes[0..12]);
+    let mut pos = 12;
+    while pos < bytes.len() {
+        let fourcc = bytes.get(pos..pos + 4)?;
+        let length = read_u32(bytes, pos + 4, true)? as usize;
+        if pos + 8 + length > bytes.len() {
+            return None;
+        }
+        // Chunks are padded to even sizes, some encoders leave out the last padding byte
+        let end = (pos + 8 + length + (length & 1)).min(bytes.len());
+        let chunk = &bytes[pos..end];
+        match fourcc {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let flags_at = stripped.len() + 8;
+                stripped.extend_from_slice(chunk);
+                *stripped.get_mut(flags_at)? &= !(0x08 | 0x04);
+            }
+            _ => stripped.extend_from_slice(chunk),
+        }
+        pos = end;
+    }
+
+    let riff_size = (stripped.len() - 8) as u32;
+    stripped[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{jpeg_orientation, strip_metadata, EXIF_HEADER, JPEG_SOI, PNG_SIGNATURE};
+
+    // Start of scan with a few bytes of entropy coded data and the end marker
+    const JPEG_SCAN: &[u8] = &[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9];
+
+    fn jpeg_segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    fn jpeg(segments: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = JPEG_SOI.to_vec();
+        for segment in segments {
+            bytes.extend_from_slice(segment);
+        }
+        bytes.extend_from_slice(JPEG_SCAN);
+        bytes
+    }
+
+    // Little endian TIFF structure with a single IFD entry holding the orientation
+    fn exif_payload(orientation: u16) -> Vec<u8> {
+        let mut payload = EXIF_HEADER.to_vec();
+        payload.extend_from_slice(b"II*\0");
+        payload.extend_from_slice(&8u32.to_le_bytes());
+        payload.extend_from_slice(&1u16.to_le_bytes());
+        payload.extend_from_slice(&0x0112u16.to_le_bytes());
+        payload.extend_from_slice(&3u16.to_le_bytes());
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&orientation.to_le_bytes());
+        payload.extend_from_slice(&[0, 0]);
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload
+    }
+
+    fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]); // The CRC is copied, never checked
+        chunk
+    }
+
+    fn webp_chunk(fourcc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = fourcc.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn webp(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&((body.len() + 4) as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WEBP");
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    #[test]
+    fn strips_exif_comments_and_iptc_from_jpeg() {
+        let app0 = jpeg_segment(0xE0, b"JFIF\0\x01\x02");
+        let icc = jpeg_segment(0xE2, b"ICC_PROFILE\0");
+        let original = jpeg(&[
+            app0.clone(),
+            jpeg_segment(0xE1, &exif_payload(6)),
+            jpeg_segment(0xED, b"Photoshop 3.0\0"),
+            jpeg_segment(0xFE, b"taken at home"),
+            icc.clone(),
+        ]);
+        assert_eq!(jpeg_orientation(&original), Some(6));
+
+        let stripped = strip_metadata("image/jpeg", &original).unwrap();
+        assert_eq!(stripped, jpeg(&[app0, icc]));
+        assert_eq!(jpeg_orientation(&stripped), None);
+    }
+
+    #[test]
+    fn rejects_broken_jpeg_segments() {
+        let exif = jpeg_segment(0xE1, &exif_payload(6));
+        let mut truncated = JPEG_SOI.to_vec();
+        truncated.extend_from_slice(&exif[..exif.len() - 3]);
+        assert_eq!(strip_metadata("image/jpeg", &truncated), None);
+        assert_eq!(jpeg_orientation(&truncated), None);
+
+        for length in [0u16, 1, u16::MAX] {
+            let mut bytes = JPEG_SOI.to_vec();
+            bytes.extend_from_slice(&[0xFF, 0xE1]);
+            bytes.extend_from_slice(&length.to_be_bytes());
+            bytes.extend_from_slice(JPEG_SCAN);
+            assert_eq!(strip_metadata("image/jpeg", &bytes), None, "{}", length);
+        }
+
+        // Without a start of scan the segments run off the end
+        let mut unterminated = JPEG_SOI.to_vec();
+        unterminated.extend_from_slice(&jpeg_segment(0xE0, b"JFIF\0"));
+        assert_eq!(strip_metadata("image/jpeg", &unterminated), None);
+        assert_eq!(strip_metadata("image/jpeg", b"not a jpeg"), None);
+    }
+
+    #[test]
+    fn ignores_out_of_range_orientations() {
+        assert_eq!(
+            jpeg_orientation(&jpeg(&[jpeg_segment(0xE1, &exif_payload(9))])),
+            None
+        );
+
+        // The IFD offset points past the end of the TIFF structure
+        let mut payload = exif_payload(6);
+        payload[EXIF_HEADER.len() + 4..EXIF_HEADER.len() + 8]
+            .copy_from_slice(&0xFFFFu32.to_le_bytes());
+        assert_eq!(
+            jpeg_orientation(&jpeg(&[jpeg_segment(0xE1, &payload)])),
+            None
+        );
+    }
+
+    #[test]
+    fn strips_exif_and_text_chunks_from_png() {
+        let ihdr = png_chunk(b"IHDR", &[0; 13]);
+        let idat = png_chunk(b"IDAT", &[1, 2, 3]);
+        let iend = png_chunk(b"IEND", &[]);
+        let original = [
+            PNG_SIGNATURE.to_vec(),
+            ihdr.clone(),
+            png_chunk(b"eXIf", &exif_payload(6)[EXIF_HEADER.len()..]),
+            png_chunk(b"tEXt", b"Author\0someone"),
+            png_chunk(b"tIME", &[0; 7]),
+            idat.clone(),
+            iend.clone(),
+        ]
+        .concat();
+
+        let stripped = strip_metadata("image/png", &original).unwrap();
+        assert_eq!(
+            stripped,
+            [PNG_SIGNATURE.to_vec(), ihdr, idat, iend].concat()
+        );
+    }
+
+    #[test]
+    fn rejects_broken_png_chunks() {
+        let ihdr = png_chunk(b"IHDR", &[0; 13]);
+        let truncated = [PNG_SIGNATURE.to_vec(), ihdr[..ihdr.len() - 2].to_vec()].concat();
+        assert_eq!(strip_metadata("image/png", &truncated), None);
+
+        let mut oversized = png_chunk(b"tEXt", b"x");
+        oversized[0..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let oversized = [PNG_SIGNATURE.to_vec(), oversized, png_chunk(b"IEND", &[])].concat();
+        assert_eq!(strip_metadata("image/png", &oversized), None);
+
+        let without_end = [PNG_SIGNATURE.to_vec(), ihdr].concat();
+        assert_eq!(strip_metadata("image/png", &without_end), None);
+    }
+
+    #[test]
+    fn strips_exif_and_xmp_chunks_from_webp() {
+        let image = webp_chunk(b"VP8L", &[1, 2, 3]);
+        let original = webp(&[
+            webp_chunk(b"VP8X", &[0x08 | 0x04 | 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            image.clone(),
+            webp_chunk(b"EXIF", &exif_payload(6)[EXIF_HEADER.len()..]),
+            webp_chunk(b"XMP ", b"<x:xmpmeta/>"),
+        ]);
+
+        let stripped = strip_metadata("image/webp", &original).unwrap();
+        let expected = webp(&[
+            webp_chunk(b"VP8X", &[0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            image,
+        ]);
+        assert_eq!(stripped, expected);
+    }
+
+    #[test]
+    fn rejects_broken_webp_chunks() {
+        let mut truncated = webp(&[webp_chunk(b"VP8L", &[1, 2, 3, 4])]);
+        truncated.truncate(truncated.len() - 2);
+        assert_eq!(strip_metadata("image/webp", &truncated), None);
+
+        let mut oversized = webp(&[webp_chunk(b"EXIF", &[0; 4])]);
+        oversized[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(strip_metadata("image/webp", &oversized), None);
+
+        // A VP8X chunk too short to hold its flags
+        let short = webp(&[webp_chunk(b"VP8X", &[])]);
+        assert_eq!(strip_metadata("image/webp", &short), None);
+    }
+
+    #[test]
+    fn keeps_a_missing_final_padding_byte() {
+        let mut bytes = webp(&[webp_chunk(b"VP8L", &[1, 2, 3])]);
+        bytes.pop();
+        assert_eq!(
+            strip_metadata("image/webp", &bytes).map(|b| b.len()),
+            Some(bytes.len())
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use super::config::env_or;
+use super::handlers::{admin_handlers, message_handlers};
+use super::AppState;
+use actix_web::{rt, web};
+use std::future::Future;
+use std::time::Duration;
+
+const DEFAULT_ATTACHMENT_GC_INTERVAL_SECONDS: u64 = 60 * 60;
+const DEFAULT_SCHEDULER_INTERVAL_SECONDS: u64 = 10;
+const DEFAULT_EXPIRY_SWEEP_INTERVAL_SECONDS: u64 = 60;
+const DEFAULT_RETENTION_INTERVAL_SECONDS: u64 = 24 * 60 * 60;
+
+// Runs a job every `interval_seconds` on the server runtime, the first run happens right away
+fn spawn_periodic<F, Fut>(
+    name: &'static str,
+    interval_seconds: u64,
+    state: web::Data<AppState>,
+    job: F,
+) where
+    F: Fn(web::Data<AppState>) -> Fut + 'static,
+    Fut: Future<Output = ()> + 'static,
+{
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(interval_seconds.max(1)));
+        loop {
+            interval.tick().await;
+            job(state.clone()).await;
+        }
+    });
+    println!("⏱️ Background job '{}' scheduled", name);
+}
+
+// Function to start every background job of the server
+pub fn spawn_jobs(state: web::Data<AppState>) {
+    spawn_periodic(
+        "scheduled message delivery",
+        env_or(
+            "SCHEDULER_INTERVAL_SECONDS",
+            DEFAULT_SCHEDULER_INTERVAL_SECONDS,
+        ),
+        state.clone(),
+        |state| async move {
+            match message_handlers::deliver_due_messages(&state).await {
+                Ok(delivered) if delivered > 0 => {
+                    println!("Delivered {} scheduled messages", delivered)
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Error delivering scheduled messages: {}", e),
+            }
+        },
+    );
+    spawn_periodic(
+        "disappearing message sweeper",
+        env_or(
+            "EXPIRY_SWEEP_INTERVAL_SECONDS",
+            DEFAULT_EXPIRY_SWEEP_INTERVAL_SECONDS,
+        ),
+        state.clone(),
+        |state| async move {
+            match message_handlers::sweep_expired_messages(&state).await {
+                Ok(report) if report.deleted > 0 => println!(
+                    "Deleted {} expired messages and {} of their attachments",
+                    report.deleted, report.removed_attachments
+                ),
+                Ok(_) => {}
+                Err(e) => eprintln!("Error deleting expired messages: {}", e),
+            }
+        },
+    );
+    spawn_periodic(
+        "data retention",
+        env_or(
+            "RETENTION_INTERVAL_SECONDS",
+            DEFAULT_RETENTION_INTERVAL_SECONDS,
+        ),
+        state.clone(),
+        |state| async move {
+            let policy = admin_handlers::RetentionPolicy::from_env();
+            if !policy.is_enabled() {
+                return;
+            }
+            match admin_handlers::enforce_retention(&state, policy, false, "schedule").await {
+                Ok(report) => println!(
+                    "Retention purged {} messages, {} wall posts and {} form submissions",
+                    report.messages, report.wall_posts, report.form_submissions
+                ),
+                Err(e) => eprintln!("Error enforcing the retention policy: {}", e),
+            }
+        },
+    );
+    spawn_periodic(
+        "attachment garbage collection",
+        env_or(
+            "ATTACHMENT_GC_INTERVAL_SECONDS",
+            DEFAULT_ATTACHMENT_GC_INTERVAL_SECONDS,
+        ),
+        state,
+        |state| async move {
+            match message_handlers::collect_attachment_garbage(&state).await {
+                Ok(report) if report.removed > 0 => println!(
+                    "Removed {} unreferenced attachments ({} bytes)",
+                    report.removed, report.freed_bytes
+                ),
+                Ok(_) => {}
+                Err(e) => eprintln!("Error collecting unreferenced attachments: {}", e),
+            }
+        },
+    );
+}
@@ -0,0 +1,268 @@
+// Renders the markdown subset messages may use (bold, italics, inline code, code blocks,
+// links and lists) to HTML that is safe to insert into the page. Raw HTML is dropped, other
+// block elements fall back to paragraphs and only http, https and mailto links are kept.
+
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+
+const ALLOWED_URL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+// Function to render the markdown source of a message to sanitised HTML
+pub fn render_markdown(source: &str) -> String {
+    // Whether each open link was kept, so its end tag is dropped along with it
+    let mut open_links = Vec::new();
+    let events = Parser::new_ext(source, Options::empty())
+        .filter_map(|event| sanitize_event(event, &mut open_links));
+
+    let mut output = String::with_capacity(source.len() + source.len() / 2);
+    html::push_html(&mut output, events);
+    output
+}
+
+fn is_safe_url(url: &str) -> bool {
+    match url.split_once(':') {
+        Some((scheme, _)) => ALLOWED_URL_SCHEMES
+            .iter()
+            .any(|allowed| scheme.eq_ignore_ascii_case(allowed)),
+        None => false,
+    }
+}
+
+fn sanitize_event<'a>(event: Event<'a>, open_links: &mut Vec<bool>) -> Option<Event<'a>> {
+    match event {
+        Event::Start(tag) => match tag {
+            Tag::Paragraph | Tag::Emphasis | Tag::Strong | Tag::List(_) | Tag::Item => {
+                Some(Event::Start(tag))
+            }
+            Tag::Heading { .. } => Some(Event::Start(Tag::Paragraph)),
+            // The info string of a fence would end up in a class attribute
+            Tag::CodeBlock(_) => Some(Event::Start(Tag::CodeBlock(CodeBlockKind::Indented))),
+            Tag::Link { dest_url, .. } => {
+                let safe = is_safe_url(&dest_url);
+                open_links.push(safe);
+                safe.then_some(Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url,
+                    title: CowStr::Borrowed(""),
+                    id: CowStr::Borrowed(""),
+                }))
+            }
+            // Quotes and images keep only their text, raw HTML blocks are dropped entirely
+            _ => None,
+        },
+        Event::End(tag) => match tag {
+            TagEnd::Paragraph
+            | TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::List(_)
+            | TagEnd::Item
+            | TagEnd::CodeBlock => Some(Event::End(tag)),
+            TagEnd::Heading(_) => Some(Event::End(TagEnd::Paragraph)),
+            TagEnd::Link => open_links
+                .pop()
+                .unwrap_or(false)
+                .then_some(Event::End(TagEnd::Link)),
+            _ => None,
+        },
+        Event::Text(_) | Event::Code(_) | Event::SoftBreak | Event::HardBreak => Some(event),
+        // Raw HTML, rules and everything outside of the subset
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    #[test]
+    fn renders_the_supported_subset() {
+        assert_eq!(
+            render_markdown("**bold** and *italics*"),
+            "<p><strong>bold</strong> and <em>italics</em></p>\n"
+        );
+        assert_eq!(
+            render_markdown("use `cargo build`"),
+            "<p>use <code>cargo build</code></p>\n"
+        );
+        assert_eq!(
+            render_markdown("```rust\nfn main() {}\n```"),
+            "<pre><code>fn main() {}\n</code></pre>\n"
+        );
+        assert_eq!(
+            render_markdown("[docs](https://example.com/docs)"),
+            "<p><a href=\"https://example.com/docs\">docs</a></p>\n"
+        );
+        assert_eq!(
+            render_markdown("- one\n- two"),
+            "<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n"
+        );
+        assert_eq!(
+            render_markdown("3. three\n4. four"),
+            "<ol start=\"3\">\n<li>three</li>\n<li>four</li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_paragraphs_outside_of_the_subset() {
+        assert_eq!(render_markdown("# Title"), "<p>Title</p>\n");
+        assert_eq!(render_markdown("> quoted"), "<p>quoted</p>\n");
+        assert_eq!(
+            render_markdown("above\n\n---\n\nbelow"),
+            "<p>above</p>\n<p>below</p>\n"
+        );
+        assert_eq!(
+            render_markdown("![a cat](https://example.com/cat.png)"),
+            "<p>a cat</p>\n"
+        );
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(
+            render_markdown("1 < 2 & 3 > 2"),
+            "<p>1 &lt; 2 &amp; 3 &gt; 2</p>\n"
+        );
+        assert_eq!(
+            render_markdown("`<b>not bold</b>`"),
+            "<p><code>&lt;b&gt;not bold&lt;/b&gt;</code></p>\n"
+        );
+    }
+
+    // Every payload has to come out without markup that runs scripts, loads resources or
+    // leaves the allowed tags
+    const XSS_CORPUS: &[&str] = &[
+        "<script>alert(1)</script>",
+        "<SCRIPT SRC=https://example.com/xss.js></SCRIPT>",
+        "text <script>alert(1)</script> text",
+        "<img src=x onerror=alert(1)>",
+        "<IMG SRC=\"javascript:alert('XSS');\">",
+        "<svg/onload=alert(1)>",
+        "<svg><script>alert(1)</script></svg>",
+        "<iframe src=\"javascript:alert(1)\"></iframe>",
+        "<body onload=alert(1)>",
+        "<div style=\"background:url(javascript:alert(1))\">x</div>",
+        "<a href=\"javascript:alert(1)\">click</a>",
+        "<style>body{display:none}</style>",
+        "<object data=\"data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==\"></object>",
+        "<embed src=\"javascript:alert(1)\">",
+        "<meta http-equiv=\"refresh\" content=\"0;url=javascript:alert(1)\">",
+        "<form action=\"javascript:alert(1)\"><input type=submit></form>",
+        "<details open ontoggle=alert(1)>",
+        "<!-- <script>alert(1)</script> -->",
+        "<![CDATA[<script>alert(1)</script>]]>",
+        "[click](javascript:alert(1))",
+        "[click](JaVaScRiPt:alert(1))",
+        "[click]( javascript:alert(1))",
+        "[click](<javascript:alert(1)>)",
+        "[click](java\tscript:alert(1))",
+        "[click](jav&#x09;ascript:alert(1))",
+        "[click](&#106;avascript:alert(1))",
+        "[click](javascript&colon;alert(1))",
+        "[click](vbscript:msgbox(1))",
+        "[click](data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==)",
+        "[click](file:///etc/passwd)",
+        "[click](//example.com)",
+        "[click](https://example.com \"onmouseover=alert(1)\")",
+        "[click][ref]\n\n[ref]: javascript:alert(1)",
+        "<javascript:alert(1)>",
+        "![x](javascript:alert(1))",
+        "![x\" onerror=\"alert(1)](https://example.com/x.png)",
+        "[x](https://example.com/\"onmouseover=\"alert(1))",
+        "[x](https://example.com/<script>)",
+        "```\"><script>alert(1)</script>\n```",
+        "``` onclick=alert(1)\ncode\n```",
+        "**<script>alert(1)</script>**",
+        "*<img src=x onerror=alert(1)>*",
+        "- <script>alert(1)</script>\n- item",
+        "# <script>alert(1)</script>",
+        "> <iframe src=https://example.com></iframe>",
+        "&lt;script&gt;alert(1)&lt;/script&gt;",
+        "<scr<script>ipt>alert(1)</script>",
+        "<<script>script>alert(1)<</script>/script>",
+        "<a href=\"https://example.com\" onclick=\"alert(1)\">x</a>",
+        "<p onmouseover=alert(1)>hover</p>",
+        "<input autofocus onfocus=alert(1)>",
+        "<math><mtext><table><mglyph><style><img src=x onerror=alert(1)>",
+    ];
+
+    const ALLOWED_TAGS: [&str; 10] = [
+        "p", "strong", "em", "code", "pre", "a", "ul", "ol", "li", "br",
+    ];
+
+    // Checks every tag of the rendered HTML against the allowed tags and attributes
+    fn assert_safe(payload: &str, html: &str) {
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..]
+                .find('>')
+                .unwrap_or_else(|| panic!("unclosed tag for {:?}: {}", payload, html));
+            let tag = rest[start + 1..start + end]
+                .trim_end_matches('/')
+                .trim_end();
+            let tag = tag.strip_prefix('/').unwrap_or(tag);
+            let mut parts = tag.splitn(2, ' ');
+            let name = parts.next().unwrap_or_default();
+            assert!(
+                ALLOWED_TAGS.contains(&name),
+                "tag <{}> for {:?}: {}",
+                name,
+                payload,
+                html
+            );
+            if let Some(attributes) = parts.next() {
+                let allowed = match name {
+                    "a" => {
+                        attributes.starts_with("href=\"http://")
+                            || attributes.starts_with("href=\"https://")
+                            || attributes.starts_with("href=\"mailto:")
+                    }
+                    "ol" => attributes.starts_with("start=\""),
+                    _ => false,
+                };
+                assert!(
+                    allowed && attributes.matches('"').count() == 2,
+                    "attributes {:?} for {:?}: {}",
+                    attributes,
+                    payload,
+                    html
+                );
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        // Payloads may survive as text, escaped so they never turn into markup
+        let lowercase = html.to_lowercase();
+        for needle in ["<script", "<img", "<iframe", "<svg"] {
+            assert!(
+                !lowercase.contains(needle),
+                "{:?} for {:?}: {}",
+                needle,
+                payload,
+                html
+            );
+        }
+    }
+
+    #[test]
+    fn neutralises_the_xss_corpus() {
+        for payload in XSS_CORPUS {
+            assert_safe(payload, &render_markdown(payload));
+        }
+    }
+
+    #[test]
+    fn drops_raw_html_but_keeps_the_text_around_it() {
+        assert_eq!(
+            render_markdown("before <b>bold</b> after"),
+            "<p>before bold after</p>\n"
+        );
+        assert_eq!(render_markdown("<div>\nblock\n</div>"), "");
+    }
+
+    #[test]
+    fn keeps_the_text_of_unsafe_links() {
+        assert_eq!(
+            render_markdown("[click](javascript:alert(1)) here"),
+            "<p>click here</p>\n"
+        );
+    }
+}
@@ -2,19 +2,33 @@ use actix_cors::Cors;
 use actix_web::{middleware, web, App, HttpServer};
 use dotenv::dotenv;
 use env_logger;
+use serde::Serialize;
 use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
 use std::sync::Arc;
-use tauri::AppHandle; // Ensure to import env_logger
+use tauri::{AppHandle, Manager}; // Ensure to import env_logger
 
+mod config;
 mod handlers;
-use handlers::{form_handlers, message_handlers, wailing_wall_handlers};
+mod image_metadata;
+mod jobs;
+mod markdown;
+mod storage;
+use handlers::{admin_handlers, form_handlers, message_handlers, wailing_wall_handlers};
 
-#[allow(dead_code)]
 pub struct AppState {
     tauri_app: Arc<AppHandle>,
     db_pool: MySqlPool,
 }
 
+impl AppState {
+    // Pushes an event to every frontend window, a failed push is only logged
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Err(e) = self.tauri_app.emit_all(event, payload) {
+            eprintln!("Error emitting '{}' event: {}", event, e);
+        }
+    }
+}
+
 // Function to initialize logging
 fn init_logging() {
     if std::env::var_os("RUST_LOG").is_none() {
@@ -48,6 +62,9 @@ pub async fn init(tauri_app: AppHandle) -> std::io::Result<()> {
         db_pool,
     });
 
+    // Start the periodic maintenance jobs
+    jobs::spawn_jobs(app_state.clone());
+
     // Configure and start the HTTP server
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -60,6 +77,11 @@ pub async fn init(tauri_app: AppHandle) -> std::io::Result<()> {
                 actix_web::http::header::ACCEPT,
             ])
             .allowed_header(actix_web::http::header::CONTENT_TYPE)
+            .allowed_header(actix_web::http::header::RANGE)
+            .expose_headers(vec![
+                actix_web::http::header::CONTENT_RANGE,
+                actix_web::http::header::ACCEPT_RANGES,
+            ])
             .max_age(3600);
 
         App::new()
@@ -69,6 +91,7 @@ pub async fn init(tauri_app: AppHandle) -> std::io::Result<()> {
             .configure(message_handlers::message_handler_config)
             .configure(form_handlers::form_handler_config)
             .configure(wailing_wall_handlers::message_handler_config)
+            .configure(admin_handlers::admin_handler_config)
     })
     .bind(("127.0.0.1", 4875))?
     .run()
@@ -0,0 +1,24 @@
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::PathBuf;
+use tauri::AppHandle;
+
+// Function to resolve (and create) a media directory inside the app data directory
+pub fn media_dir(tauri_app: &AppHandle, kind: &str) -> io::Result<PathBuf> {
+    let base = tauri_app.path_resolver().app_data_dir().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "App data directory is unavailable")
+    })?;
+    let dir = base.join(kind);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// Function to compute the content address (hex encoded SHA-256) of a blob
+pub fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+// Function to check that a path segment is a content address and not a path traversal
+pub fn is_content_hash(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}